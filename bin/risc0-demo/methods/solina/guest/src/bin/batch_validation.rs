@@ -0,0 +1,21 @@
+#![no_main]
+use anyhow::Result;
+use risc0_zkvm::guest::env;
+use solina::batch::BatchValidationInputs;
+
+risc0_zkvm::guest::entry!(main);
+
+pub fn main() {
+    let result = batch_validation().unwrap_or_else(|e| e.to_string());
+    env::commit(&result);
+}
+
+/// Validates a batch solution, and commits the batch root, the score at the input prices
+/// and the verdict.
+/// A solution violating any constraint still gets a journal, with `is_valid` unset, so
+/// that solvers get a proof of why their solution was rejected.
+fn batch_validation() -> Result<String> {
+    let data: String = env::read();
+    let inputs: BatchValidationInputs = serde_json::from_str(&data)?;
+    Ok(serde_json::to_string(&inputs.journal())?)
+}
@@ -7,6 +7,7 @@ use actix_web::{web, web::post, HttpRequest, HttpResponse, Result};
 use json::object;
 use risc0_zkvm::{serde::to_vec, Prover};
 use serde::{Deserialize, Serialize};
+use solina_methods::BATCH_VALIDATION_ELF;
 use std::{
     collections::HashMap,
     io::Read,
@@ -104,6 +105,10 @@ async fn main() -> std::io::Result<()> {
             .service(web::resource("/").route(web::get().to(index)))
             .service(web::resource("/pkg/{verifier_file}").route(web::get().to(get_verifier)))
             .service(web::resource("/job").route(post().to(post_job)))
+            // registered before `/job/{job_id}`, which would otherwise match it
+            .service(
+                web::resource("/job/batch_validation").route(post().to(post_batch_validation_job)),
+            )
             .service(web::resource("/job/{job_id}").route(web::get().to(get_job)))
     })
     .bind(http_address)?
@@ -124,13 +129,31 @@ async fn post_job(
     debug!("post_job");
     let mut buffer: Vec<u8> = vec![0; form.code.size];
     form.code.file.read_exact(buffer.as_mut())?;
+    queue_job(buffer, form.input.0.clone(), context).await
+}
+
+/// Queues a job running the Solina batch validation guest, whose input is a JSON encoded
+/// `BatchValidationInputs`.
+async fn post_batch_validation_job(
+    input: String,
+    context: web::Data<Context>,
+) -> Result<HttpResponse> {
+    debug!("post_batch_validation_job");
+    queue_job(BATCH_VALIDATION_ELF.to_vec(), input, context).await
+}
+
+async fn queue_job(
+    code: Vec<u8>,
+    input: String,
+    context: web::Data<Context>,
+) -> Result<HttpResponse> {
     let job_id = get_job_id();
     match context
         .sender
         .send(Job {
             id: job_id,
-            code: buffer,
-            input: form.input.0.clone(),
+            code,
+            input,
         })
         .await
     {
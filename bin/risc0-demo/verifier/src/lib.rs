@@ -1,5 +1,8 @@
-use risc0_zkvm::Receipt;
+use risc0_zkvm::{serde::from_slice, Receipt};
 use serde::{Deserialize, Serialize};
+use solina::batch::BatchValidationJournal;
+use solina_methods::BATCH_VALIDATION_ID;
+use solina_verify::{verify_batch_solution_proof, BatchSolutionPublicInputs};
 use wasm_bindgen::prelude::*;
 
 #[derive(Serialize, Deserialize)]
@@ -9,31 +12,96 @@ pub struct ProvedResult {
     receipt: String,
 }
 
+/// Outcome of a verification. The batch root (hex encoded) and the score are only set
+/// for Solina batch proofs, and the error only for invalid proofs.
+#[wasm_bindgen(getter_with_clone)]
+pub struct VerificationResult {
+    pub is_valid: bool,
+    pub batch_root: Option<String>,
+    pub score: Option<String>,
+    pub error: Option<String>,
+}
+
+impl VerificationResult {
+    fn invalid(error: String) -> Self {
+        Self {
+            is_valid: false,
+            batch_root: None,
+            score: None,
+            error: Some(error),
+        }
+    }
+}
+
+/// Verifies a risc0 `ProvedResult`. For jobs running the Solina batch validation guest,
+/// as told by their method id, the receipt is verified against the pinned guest id, its
+/// journal must be a `BatchValidationJournal`, and the proof is only valid if the guest
+/// accepted the solution. The unproven `outputs` of the result are never trusted.
 #[wasm_bindgen]
-pub fn verify(serialized_result: String) -> bool {
+pub fn verify(serialized_result: String) -> VerificationResult {
     let result: ProvedResult = match serde_json::from_str(&serialized_result) {
         Ok(r) => r,
-        Err(e) => {
-            alert(&format!("Failed to decerialize: {}", e));
-            return false;
-        }
+        Err(e) => return VerificationResult::invalid(format!("Failed to deserialize: {}", e)),
     };
 
     let receipt: Receipt = match serde_json::from_str(&result.receipt) {
         Ok(r) => r,
-        Err(e) => {
-            alert(&format!("Failed to decerialize: {}", e));
-            return false;
-        }
+        Err(e) => return VerificationResult::invalid(format!("Failed to deserialize: {}", e)),
     };
 
-    match receipt.verify(result.method_id.as_slice()) {
-        Ok(_) => true,
-        Err(_e) => false,
+    if result.method_id.as_slice() != BATCH_VALIDATION_ID {
+        return match receipt.verify(result.method_id.as_slice()) {
+            Ok(()) => VerificationResult {
+                is_valid: true,
+                batch_root: None,
+                score: None,
+                error: None,
+            },
+            Err(e) => VerificationResult::invalid(format!("Invalid receipt: {}", e)),
+        };
     }
+
+    if let Err(e) = receipt.verify(&BATCH_VALIDATION_ID[..]) {
+        return VerificationResult::invalid(format!("Invalid receipt: {}", e));
+    }
+
+    match batch_validation_journal(&receipt) {
+        Ok(journal) => VerificationResult {
+            is_valid: journal.is_valid,
+            batch_root: Some(hex::encode(journal.batch_root)),
+            score: Some(journal.score.to_string()),
+            error: (!journal.is_valid).then(|| "The solution was rejected".to_string()),
+        },
+        Err(e) => VerificationResult::invalid(format!("Invalid batch validation journal: {}", e)),
+    }
+}
+
+/// Decodes the JSON encoded `BatchValidationJournal` committed by the batch validation guest.
+fn batch_validation_journal(receipt: &Receipt) -> Result<BatchValidationJournal, String> {
+    let journal: String = from_slice(&receipt.journal).map_err(|e| e.to_string())?;
+    serde_json::from_str(&journal).map_err(|e| e.to_string())
 }
 
+/// Verifies a plonky2 Solina batch solution proof, against the verifier data of its
+/// circuit and the JSON encoded `BatchSolutionPublicInputs` expected for the batch.
 #[wasm_bindgen]
-extern "C" {
-    pub fn alert(s: &str);
+pub fn verify_batch_solution(
+    verifier_data: &[u8],
+    proof: &[u8],
+    public_inputs: String,
+) -> VerificationResult {
+    let public_inputs: BatchSolutionPublicInputs = match serde_json::from_str(&public_inputs) {
+        Ok(p) => p,
+        Err(e) => return VerificationResult::invalid(format!("Invalid public inputs: {}", e)),
+    };
+
+    let error = verify_batch_solution_proof(verifier_data, proof, &public_inputs)
+        .err()
+        .map(|e| e.to_string());
+    VerificationResult {
+        is_valid: error.is_none(),
+        batch_root: Some(hex::encode(public_inputs.batch_root)),
+        score: Some(public_inputs.score.to_string()),
+        error,
+    }
 }
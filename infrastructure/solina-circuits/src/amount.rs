@@ -0,0 +1,250 @@
+// AMOUNT_DESIGN:
+//
+// 1. Token amounts are at most 256 bits long, as `uint256` amounts on Ethereum. In circuit,
+//    they are eight little endian u32 limbs, each range checked, so that they can be compared
+//    with the u32 gadgets.
+// 2. Intent amounts are hashed as big endian bytes (see `intent_hash`). Their limbs are
+//    recomposed from the same byte targets, so that the compared amounts are the signed ones.
+// 3. Swapped amounts are witnesses, constrained by the amounts of the matched intents.
+// 4. Sums and products of amounts (scores, see `batch_circuit`) are constrained not to
+//    overflow `AMOUNT_LIMBS` limbs, rather than being reduced.
+use num_bigint::BigUint;
+use plonky2::{
+    field::extension::Extendable,
+    hash::hash_types::RichField,
+    iop::{
+        target::{BoolTarget, Target},
+        witness::PartialWitness,
+    },
+    plonk::circuit_builder::CircuitBuilder,
+};
+use plonky2_u32::{
+    gadgets::{
+        arithmetic_u32::{CircuitBuilderU32, U32Target},
+        multiple_comparison::list_le_u32_circuit,
+    },
+    witness::WitnessU32,
+};
+
+pub use crate::verifier::AMOUNT_LIMBS;
+/// Maximum length of the big endian bytes of an amount.
+pub const AMOUNT_BYTES: usize = 4 * AMOUNT_LIMBS;
+
+/// Little endian u32 limbs of an amount.
+pub type AmountTargets = [U32Target; AMOUNT_LIMBS];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AmountOutOfRange;
+
+/// Adds the (range checked) limbs of an amount.
+pub fn add_virtual_amount_targets<F, const D: usize>(
+    circuit_builder: &mut CircuitBuilder<F, D>,
+) -> AmountTargets
+where
+    F: RichField + Extendable<D>,
+{
+    let limbs = circuit_builder.add_virtual_u32_targets(AMOUNT_LIMBS);
+    circuit_builder.range_check_u32(limbs.clone());
+    limbs
+        .try_into()
+        .expect("Amounts are AMOUNT_LIMBS limbs long")
+}
+
+/// Recomposes the limbs of an amount from its (range checked) big endian bytes.
+pub fn amount_from_be_bytes<F, const D: usize>(
+    circuit_builder: &mut CircuitBuilder<F, D>,
+    bytes: &[Target],
+) -> AmountTargets
+where
+    F: RichField + Extendable<D>,
+{
+    assert!(
+        bytes.len() <= AMOUNT_BYTES,
+        "Amounts are at most {} bytes long",
+        AMOUNT_BYTES
+    );
+    let zero = circuit_builder.zero();
+    let mut padded_bytes = vec![zero; AMOUNT_BYTES - bytes.len()];
+    padded_bytes.extend_from_slice(bytes);
+
+    let limbs = padded_bytes
+        .chunks(4)
+        .rev()
+        .map(|chunk| {
+            let limb = chunk.iter().fold(zero, |limb, byte| {
+                circuit_builder.mul_const_add(F::from_canonical_u32(1 << 8), limb, *byte)
+            });
+            U32Target(limb)
+        })
+        .collect::<Vec<_>>();
+    limbs
+        .try_into()
+        .expect("Amounts are AMOUNT_LIMBS limbs long")
+}
+
+/// Constrains `lhs` to be at most `rhs`.
+pub fn assert_amount_le<F, const D: usize>(
+    circuit_builder: &mut CircuitBuilder<F, D>,
+    lhs: &AmountTargets,
+    rhs: &AmountTargets,
+) where
+    F: RichField + Extendable<D>,
+{
+    let is_le = list_le_u32_circuit(circuit_builder, lhs.to_vec(), rhs.to_vec());
+    let one = circuit_builder.one();
+    circuit_builder.connect(is_le.target, one);
+}
+
+/// Constant zero amount.
+pub fn zero_amount<F, const D: usize>(circuit_builder: &mut CircuitBuilder<F, D>) -> AmountTargets
+where
+    F: RichField + Extendable<D>,
+{
+    [circuit_builder.zero_u32(); AMOUNT_LIMBS]
+}
+
+/// Sum of two amounts, constrained not to overflow.
+pub fn add_amounts<F, const D: usize>(
+    circuit_builder: &mut CircuitBuilder<F, D>,
+    lhs: &AmountTargets,
+    rhs: &AmountTargets,
+) -> AmountTargets
+where
+    F: RichField + Extendable<D>,
+{
+    let mut carry = circuit_builder.zero_u32();
+    let mut limbs = zero_amount(circuit_builder);
+    for (i, (lhs_limb, rhs_limb)) in lhs.iter().zip(rhs).enumerate() {
+        let (limb, next_carry) =
+            circuit_builder.add_u32s_with_carry(&[*lhs_limb, *rhs_limb], carry);
+        limbs[i] = limb;
+        carry = next_carry;
+    }
+    circuit_builder.assert_zero_u32(carry);
+    limbs
+}
+
+/// Product of two amounts, constrained not to overflow. Limbs are multiplied as in
+/// schoolbook multiplication, and the partial products of each limb summed with carries.
+pub fn mul_amounts<F, const D: usize>(
+    circuit_builder: &mut CircuitBuilder<F, D>,
+    lhs: &AmountTargets,
+    rhs: &AmountTargets,
+) -> AmountTargets
+where
+    F: RichField + Extendable<D>,
+{
+    let mut to_add = vec![vec![]; 2 * AMOUNT_LIMBS];
+    for (i, lhs_limb) in lhs.iter().enumerate() {
+        for (j, rhs_limb) in rhs.iter().enumerate() {
+            let (product, carry) = circuit_builder.mul_u32(*lhs_limb, *rhs_limb);
+            to_add[i + j].push(product);
+            to_add[i + j + 1].push(carry);
+        }
+    }
+
+    let mut carry = circuit_builder.zero_u32();
+    let mut limbs = vec![];
+    for summands in to_add {
+        let (limb, next_carry) = circuit_builder.add_u32s_with_carry(&summands, carry);
+        limbs.push(limb);
+        carry = next_carry;
+    }
+    limbs[AMOUNT_LIMBS..]
+        .iter()
+        .for_each(|limb| circuit_builder.assert_zero_u32(*limb));
+    circuit_builder.assert_zero_u32(carry);
+
+    limbs[..AMOUNT_LIMBS]
+        .try_into()
+        .expect("Amounts are AMOUNT_LIMBS limbs long")
+}
+
+/// `amount` if `condition` is set, zero otherwise.
+pub fn select_amount<F, const D: usize>(
+    circuit_builder: &mut CircuitBuilder<F, D>,
+    condition: BoolTarget,
+    amount: &AmountTargets,
+) -> AmountTargets
+where
+    F: RichField + Extendable<D>,
+{
+    amount.map(|limb| U32Target(circuit_builder.mul(condition.target, limb.0)))
+}
+
+pub fn set_amount_targets<F: RichField>(
+    partial_witness: &mut PartialWitness<F>,
+    amount: &BigUint,
+    targets: &AmountTargets,
+) -> Result<(), AmountOutOfRange> {
+    let mut limbs = amount.to_u32_digits();
+    if limbs.len() > AMOUNT_LIMBS {
+        return Err(AmountOutOfRange);
+    }
+    limbs.resize(AMOUNT_LIMBS, 0);
+    limbs
+        .into_iter()
+        .zip(targets)
+        .for_each(|(limb, target)| partial_witness.set_u32_target(*target, limb));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plonky2::{
+        field::types::Field,
+        plonk::{
+            circuit_data::CircuitConfig,
+            config::{GenericConfig, PoseidonGoldilocksConfig},
+        },
+    };
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    /// Proves the sum and product of `lhs` and `rhs`, and returns their limbs.
+    fn prove_arithmetic(lhs: &BigUint, rhs: &BigUint) -> (Vec<F>, Vec<F>) {
+        let mut circuit_builder =
+            CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let lhs_targets = add_virtual_amount_targets(&mut circuit_builder);
+        let rhs_targets = add_virtual_amount_targets(&mut circuit_builder);
+        let sum = add_amounts(&mut circuit_builder, &lhs_targets, &rhs_targets);
+        let product = mul_amounts(&mut circuit_builder, &lhs_targets, &rhs_targets);
+        circuit_builder.register_public_inputs(&sum.map(|limb| limb.0));
+        circuit_builder.register_public_inputs(&product.map(|limb| limb.0));
+        let circuit_data = circuit_builder.build::<C>();
+
+        let mut partial_witness = PartialWitness::new();
+        set_amount_targets(&mut partial_witness, lhs, &lhs_targets).unwrap();
+        set_amount_targets(&mut partial_witness, rhs, &rhs_targets).unwrap();
+        let proof = circuit_data.prove(partial_witness).unwrap();
+        circuit_data.verify(proof.clone()).unwrap();
+
+        let (sum, product) = proof.public_inputs.split_at(AMOUNT_LIMBS);
+        (sum.to_vec(), product.to_vec())
+    }
+
+    fn limbs(amount: &BigUint) -> Vec<F> {
+        let mut limbs = amount.to_u32_digits();
+        limbs.resize(AMOUNT_LIMBS, 0);
+        limbs.into_iter().map(F::from_canonical_u32).collect()
+    }
+
+    #[test]
+    fn it_works_amount_arithmetic() {
+        let lhs = BigUint::from(u64::MAX) * BigUint::from(3_u8);
+        let rhs = BigUint::from(u128::MAX);
+        let (sum, product) = prove_arithmetic(&lhs, &rhs);
+        assert_eq!(sum, limbs(&(&lhs + &rhs)));
+        assert_eq!(product, limbs(&(&lhs * &rhs)));
+    }
+
+    #[test]
+    #[should_panic]
+    fn it_rejects_overflowing_products() {
+        let amount = BigUint::from(1_u8) << 128;
+        prove_arithmetic(&amount, &amount);
+    }
+}
@@ -0,0 +1,644 @@
+// BATCH_DESIGN:
+//
+// 1. The batch solution circuit proves a whole solution at once. Its public inputs are laid
+//    out as `solina-verify` expects them: batch root, batch timestamp, score, settlement
+//    digest, then the nullifiers of every match slot.
+// 2. The circuit has a fixed number of leaves (a power of two) and of match slots, given by
+//    its shape (see `batch_solution_shape`). Slots beyond the solution matches are inactive:
+//    they prove a copy of the first match, contribute nothing to the score, and output zero
+//    nullifiers, which verifiers accept as padding.
+// 3. The batch root is recomputed from the leaves, as in `solina::batch::batch_root`. A batch
+//    of `n` intents is padded to `n.next_power_of_two()` leaves, so its root is the leftmost
+//    node of the level of that depth, which is selected by the (witnessed) depth flags. Every
+//    matched intent is looked up among the leaves below that depth, so that it is committed
+//    to by the batch root. Leaves hash the signer public key with the structured hash (see
+//    `solina::batch::batch_leaf`), so that intents re-signed by anyone else are not found.
+// 4. Each active slot proves a match (see `match_circuit`). Intents are matched at most once
+//    in a solution, as each match only checks the amounts of its own intents.
+// 5. The score is the total liquidity of the solution, that is, the sum over matches of the
+//    token B amount priced in the quote token of intent B. Prices are witnesses, bound to the
+//    batch price snapshot by the settlement digest: active slots fold their price and swapped
+//    amounts into it, so that verifiers recompute it from the submitted solution and the
+//    snapshot (see `solina-verify`), and a proof cannot settle other amounts or prices.
+use crate::{
+    amount::{
+        add_amounts, add_virtual_amount_targets, mul_amounts, select_amount, set_amount_targets,
+        zero_amount, AmountOutOfRange, AmountTargets,
+    },
+    expiry::{add_virtual_timestamp_targets, register_batch_timestamp},
+    intent_hash::{digest_to_limbs, to_hash_out, two_to_one_circuit},
+    match_circuit::{add_match_targets, set_match_targets, MatchCircuitError, MatchTargets},
+    prover::{Plonky2Prover, SolutionProverError, SolutionWitness, WitnessGenerator},
+    registry::{CircuitRegistry, CircuitRegistryError, CircuitShape},
+    verifier::{SolinaGateSerializer, C, D, F},
+};
+use plonky2::{
+    field::{extension::Extendable, types::Field},
+    hash::{
+        hash_types::{HashOut, HashOutTarget, RichField},
+        poseidon::PoseidonHash,
+    },
+    iop::{
+        target::{BoolTarget, Target},
+        witness::{PartialWitness, WitnessWrite},
+    },
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::{CircuitConfig, CircuitData},
+        config::GenericConfig,
+    },
+    util::serialization::DefaultGeneratorSerializer,
+};
+use solina::{
+    batch::batch_leaf,
+    price_oracle::PriceOracle,
+    structured_hash::{StructuredHash, StructuredHashInterface},
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    marker::PhantomData,
+    path::Path,
+};
+
+pub const BATCH_SOLUTION_CIRCUIT: &str = "batch_solution";
+/// Largest number of leaves, as random access gates of the standard recursion config look up
+/// at most 64 entries.
+pub const MAX_BATCH_TREE_SIZE: usize = 64;
+
+/// Number of leaves of the batch solution circuit for batches of up to `max_batch_size`
+/// intents.
+pub fn batch_tree_size(max_batch_size: usize) -> usize {
+    max_batch_size.next_power_of_two().max(2)
+}
+
+/// Shape of the batch solution circuit, for batches of up to `max_batch_size` intents and
+/// solutions of up to `max_matches` matches.
+pub fn batch_solution_shape(max_batch_size: usize, max_matches: usize) -> CircuitShape {
+    CircuitShape::new(
+        BATCH_SOLUTION_CIRCUIT,
+        vec![batch_tree_size(max_batch_size), max_matches],
+    )
+}
+
+struct MatchSlotTargets {
+    is_active: BoolTarget,
+    match_targets: MatchTargets,
+    /// Leaf indices of intents A and B.
+    intent_indices: [Target; 2],
+    /// Price of the quote token of intent B.
+    price: AmountTargets,
+}
+
+pub struct BatchSolutionTargets {
+    leaves: Vec<HashOutTarget>,
+    /// Flag `k` is set if the batch tree is deeper than `k`.
+    depth_flags: Vec<BoolTarget>,
+    slots: Vec<MatchSlotTargets>,
+}
+
+pub struct BatchSolutionCircuitData<F: RichField + Extendable<D>, const D: usize> {
+    circuit_builder: CircuitBuilder<F, D>,
+    targets: BatchSolutionTargets,
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> BatchSolutionCircuitData<F, D> {
+    /// Builds the batch solution circuit, together with the targets its witness is set on.
+    pub fn build<C: GenericConfig<D, F = F>>(self) -> (CircuitData<F, C, D>, BatchSolutionTargets) {
+        (self.circuit_builder.build::<C>(), self.targets)
+    }
+}
+
+/// Builds the batch solution circuit, with `batch_tree_size` leaves and `max_matches` match
+/// slots.
+///
+/// Panics if `batch_tree_size` is not a power of two between 2 and `MAX_BATCH_TREE_SIZE`, or
+/// if there are no match slots.
+pub fn generate_batch_solution_circuit<F, const D: usize>(
+    batch_tree_size: usize,
+    max_matches: usize,
+) -> BatchSolutionCircuitData<F, D>
+where
+    F: RichField + Extendable<D>,
+{
+    assert!(
+        batch_tree_size.is_power_of_two() && (2..=MAX_BATCH_TREE_SIZE).contains(&batch_tree_size),
+        "Batch trees have a power of two number of leaves, between 2 and {}",
+        MAX_BATCH_TREE_SIZE
+    );
+    assert!(max_matches > 0, "Batch solutions have at least one match");
+    let max_depth = batch_tree_size.trailing_zeros() as usize;
+
+    let mut circuit_builder =
+        CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+
+    // 1. Compute the batch root, as the leftmost node at the batch tree depth. Depth flags
+    //    are decreasing, so that their sum is the depth.
+    let leaves = (0..batch_tree_size)
+        .map(|_| circuit_builder.add_virtual_hash())
+        .collect::<Vec<_>>();
+    let depth_flags = (0..max_depth)
+        .map(|_| circuit_builder.add_virtual_bool_target_safe())
+        .collect::<Vec<_>>();
+    for flags in depth_flags.windows(2) {
+        let is_shallower = circuit_builder.not(flags[0]);
+        let is_unordered = circuit_builder.and(is_shallower, flags[1]);
+        circuit_builder.assert_zero(is_unordered.target);
+    }
+
+    let mut level = leaves.clone();
+    let mut level_roots = vec![level[0]];
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| two_to_one_circuit(&mut circuit_builder, pair[0], pair[1]))
+            .collect();
+        level_roots.push(level[0]);
+    }
+    level_roots.resize(level_roots.len().next_power_of_two(), level[0]);
+    let depth = circuit_builder.add_many(depth_flags.iter().map(|flag| flag.target));
+    let batch_root = circuit_builder.random_access_hash(depth, level_roots);
+    let batch_root_limbs = digest_to_limbs(&mut circuit_builder, batch_root);
+    circuit_builder.register_public_inputs(&batch_root_limbs);
+
+    let batch_timestamp = add_virtual_timestamp_targets(&mut circuit_builder);
+    register_batch_timestamp(&mut circuit_builder, &batch_timestamp);
+
+    // 2. Prove each match, whose intents are leaves below the batch tree depth, and add its
+    //    liquidity to the score if the slot is active
+    let mut score = zero_amount(&mut circuit_builder);
+    let mut settlement_digest = circuit_builder.constant_hash(HashOut::ZERO);
+    let mut slots = vec![];
+    for _ in 0..max_matches {
+        let is_active = circuit_builder.add_virtual_bool_target_safe();
+        let match_targets = add_match_targets(&mut circuit_builder, &batch_timestamp);
+
+        let intent_indices = [
+            circuit_builder.add_virtual_target(),
+            circuit_builder.add_virtual_target(),
+        ];
+        let intent_leaves = match_targets
+            .public_key_hashes()
+            .into_iter()
+            .zip(match_targets.structured_hashes())
+            .map(|(public_key_hash, structured_hash)| {
+                two_to_one_circuit(&mut circuit_builder, public_key_hash, structured_hash)
+            })
+            .collect::<Vec<_>>();
+        for (index, intent_leaf) in intent_indices.iter().zip(intent_leaves) {
+            let leaf = circuit_builder.random_access_hash(*index, leaves.clone());
+            circuit_builder.connect_hashes(leaf, intent_leaf);
+
+            let index_bits = circuit_builder.split_le(*index, max_depth);
+            for (bit, flag) in index_bits.into_iter().zip(&depth_flags) {
+                let is_shallower = circuit_builder.not(*flag);
+                let is_beyond_depth = circuit_builder.and(bit, is_shallower);
+                circuit_builder.assert_zero(is_beyond_depth.target);
+            }
+        }
+
+        let price = add_virtual_amount_targets(&mut circuit_builder);
+        let liquidity = mul_amounts(&mut circuit_builder, match_targets.token_b_amount(), &price);
+        let liquidity = select_amount(&mut circuit_builder, is_active, &liquidity);
+        score = add_amounts(&mut circuit_builder, &score, &liquidity);
+
+        let mut settlement = settlement_digest.elements.to_vec();
+        for amount in [
+            &price,
+            match_targets.token_a_amount(),
+            match_targets.token_b_amount(),
+        ] {
+            settlement.extend(amount.iter().map(|limb| limb.0));
+        }
+        let settled_digest = circuit_builder.hash_n_to_hash_no_pad::<PoseidonHash>(settlement);
+        settlement_digest =
+            circuit_builder.select_hash(is_active, settled_digest, settlement_digest);
+
+        slots.push(MatchSlotTargets {
+            is_active,
+            match_targets,
+            intent_indices,
+            price,
+        });
+    }
+
+    // 3. Intents of active slots are matched at most once
+    let indices = slots
+        .iter()
+        .flat_map(|slot| slot.intent_indices.map(|index| (slot.is_active, index)))
+        .collect::<Vec<_>>();
+    for (i, (is_active, index)) in indices.iter().enumerate() {
+        for (other_is_active, other_index) in &indices[i + 1..] {
+            let are_active = circuit_builder.and(*is_active, *other_is_active);
+            let are_equal = circuit_builder.is_equal(*index, *other_index);
+            let is_rematched = circuit_builder.and(are_active, are_equal);
+            circuit_builder.assert_zero(is_rematched.target);
+        }
+    }
+
+    // 4. Register the score and the settlement digest, followed by the nullifiers of active
+    //    slots (zero otherwise)
+    circuit_builder.register_public_inputs(&score.map(|limb| limb.0));
+    circuit_builder.register_public_inputs(&settlement_digest.elements);
+    for slot in &slots {
+        for nullifier in slot.match_targets.nullifiers() {
+            let elements = nullifier
+                .elements
+                .map(|element| circuit_builder.mul(slot.is_active.target, element));
+            circuit_builder.register_public_inputs(&elements);
+        }
+    }
+
+    BatchSolutionCircuitData {
+        circuit_builder,
+        targets: BatchSolutionTargets {
+            leaves,
+            depth_flags,
+            slots,
+        },
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BatchSolutionCircuitError {
+    /// The batch has more intents than the circuit has leaves
+    BatchTooLarge(usize),
+    /// The solution has no matches, or more than the circuit has slots
+    UnsupportedMatchCount(usize),
+    /// The intent, with given structured hash, does not belong to the batch
+    UnknownIntent(StructuredHash),
+    /// The intent, with given structured hash, is matched more than once
+    IntentMatchedTwice(StructuredHash),
+    /// A price does not fit in `AMOUNT_LIMBS` limbs
+    AmountOutOfRange,
+    Match(MatchCircuitError),
+}
+
+impl From<AmountOutOfRange> for BatchSolutionCircuitError {
+    fn from(_: AmountOutOfRange) -> Self {
+        Self::AmountOutOfRange
+    }
+}
+
+impl From<MatchCircuitError> for BatchSolutionCircuitError {
+    fn from(e: MatchCircuitError) -> Self {
+        Self::Match(e)
+    }
+}
+
+/// Sets the witness of the batch solution circuit targets, from the batch intents (in batch
+/// order), the solution and the prices of the batch.
+pub fn set_batch_solution_targets<F, const D: usize>(
+    partial_witness: &mut PartialWitness<F>,
+    witness: &SolutionWitness,
+    targets: &BatchSolutionTargets,
+) -> Result<(), BatchSolutionCircuitError>
+where
+    F: RichField + Extendable<D>,
+{
+    let intents = witness.intents;
+    if intents.len() > targets.leaves.len() {
+        return Err(BatchSolutionCircuitError::BatchTooLarge(intents.len()));
+    }
+    let matches = witness.solution.batch_matches();
+    if matches.is_empty() || matches.len() > targets.slots.len() {
+        return Err(BatchSolutionCircuitError::UnsupportedMatchCount(
+            matches.len(),
+        ));
+    }
+
+    for (i, leaf) in targets.leaves.iter().enumerate() {
+        let intent_leaf = intents
+            .get(i)
+            .map(|intent| to_hash_out(&batch_leaf(intent)))
+            .unwrap_or(HashOut::ZERO);
+        partial_witness.set_hash_target(*leaf, intent_leaf);
+    }
+    let depth = intents.len().next_power_of_two().trailing_zeros() as usize;
+    for (k, flag) in targets.depth_flags.iter().enumerate() {
+        partial_witness.set_bool_target(*flag, k < depth);
+    }
+
+    let positions = intents
+        .iter()
+        .enumerate()
+        .map(|(i, intent)| (intent.key(), i))
+        .collect::<BTreeMap<_, _>>();
+    let mut matched = BTreeSet::new();
+    let mut indices = vec![];
+    for m in matches {
+        let mut match_indices = [0; 2];
+        for (index, intent) in match_indices.iter_mut().zip([m.intent_a(), m.intent_b()]) {
+            let structured_hash = intent.structured_hash();
+            *index = *positions
+                .get(&intent.key())
+                .ok_or(BatchSolutionCircuitError::UnknownIntent(structured_hash))?;
+            if !matched.insert(*index) {
+                return Err(BatchSolutionCircuitError::IntentMatchedTwice(
+                    structured_hash,
+                ));
+            }
+        }
+        indices.push(match_indices);
+    }
+
+    for (i, slot) in targets.slots.iter().enumerate() {
+        // inactive slots prove a copy of the first match
+        let is_active = i < matches.len();
+        let (m, match_indices) = if is_active {
+            (&matches[i], indices[i])
+        } else {
+            (&matches[0], indices[0])
+        };
+
+        partial_witness.set_bool_target(slot.is_active, is_active);
+        // the batch timestamp targets are shared by all slots, and set to the same value
+        set_match_targets::<F, D>(
+            partial_witness,
+            m,
+            &witness.batch_timestamp,
+            &slot.match_targets,
+        )?;
+        for (index, target) in match_indices.iter().zip(slot.intent_indices) {
+            partial_witness.set_target(target, F::from_canonical_usize(*index));
+        }
+        let price = witness
+            .prices
+            .get_current_price(m.intent_b().inputs.quote_token);
+        set_amount_targets(partial_witness, &price, &slot.price)?;
+    }
+
+    Ok(())
+}
+
+/// Plonky2 prover of batch solutions, for batches of up to `max_batch_size` intents and
+/// solutions of up to `max_matches` matches. The circuit is obtained from `registry`.
+pub fn batch_solution_prover(
+    registry: &mut CircuitRegistry<F, C, D>,
+    max_batch_size: usize,
+    max_matches: usize,
+) -> Result<Plonky2Prover, CircuitRegistryError> {
+    // the gates are generated for their targets, which witnesses are set on, but the circuit
+    // is only built if the registry has neither it in memory nor its artifacts on disk
+    let BatchSolutionCircuitData {
+        circuit_builder,
+        targets,
+    } = generate_batch_solution_circuit::<F, D>(batch_tree_size(max_batch_size), max_matches);
+    let circuit_data = registry.get_or_build(
+        &batch_solution_shape(max_batch_size, max_matches),
+        move || circuit_builder.build::<C>(),
+    )?;
+
+    let witness_generator: WitnessGenerator = Box::new(move |witness| {
+        let mut partial_witness = PartialWitness::new();
+        set_batch_solution_targets::<F, D>(&mut partial_witness, witness, &targets)
+            .map_err(|e| SolutionProverError::WitnessError(format!("{:?}", e)))?;
+        Ok(partial_witness)
+    });
+    Ok(Plonky2Prover::new(circuit_data, witness_generator))
+}
+
+/// Registry of batch solution circuits, storing their artifacts under `artifacts_dir`, where
+/// the service reads their verifier data from.
+pub fn batch_solution_registry<P: AsRef<Path>>(artifacts_dir: P) -> CircuitRegistry<F, C, D> {
+    CircuitRegistry::new(
+        artifacts_dir,
+        Box::new(SolinaGateSerializer),
+        Box::new(DefaultGeneratorSerializer::<C, D> {
+            _phantom: PhantomData,
+        }),
+    )
+}
+
+#[cfg(all(test, feature = "ecdsa"))]
+mod tests {
+    use super::*;
+    use crate::{
+        signature::sign_intent,
+        verifier::{
+            verify_batch_solution_proof, verify_batch_solution_proof_with,
+            BatchSolutionPublicInputs, ProofVerificationError,
+        },
+    };
+    use chrono::{NaiveDate, NaiveDateTime};
+    use hex::encode;
+    use num_bigint::BigUint;
+    use plonky2::field::secp256k1_scalar::Secp256K1Scalar;
+    use plonky2_ecdsa::curve::ecdsa::ECDSASecretKey;
+    use solina::{
+        batch::batch_root,
+        intent::{Intent, IntentConstraints, IntentInputs, TradeDirection},
+        price_oracle::PriceSnapshot,
+        solver::{BatchSolution, Match, SwappedAmount},
+        Signature,
+    };
+
+    fn date(day: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2023, 11, day)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+    }
+
+    /// An intent quoting 1000 tokens, for at least 900 base tokens, expiring on the 28th.
+    fn signed_intent(secret_key: u64, tokens: (u8, u8)) -> Intent {
+        let mut intent = Intent::new(
+            [0u8; 32],
+            IntentInputs::new(
+                [tokens.0; 32],
+                [tokens.1; 32],
+                BigUint::from(1_000_u64),
+                TradeDirection::Sell,
+            ),
+            IntentConstraints::new(BigUint::from(900_u64)),
+            Signature([0u8; 64]),
+            date(28),
+        );
+        sign_intent(
+            &mut intent,
+            ECDSASecretKey(Secp256K1Scalar::from_canonical_u64(secret_key)),
+        );
+        intent
+    }
+
+    fn match_instance(intent_a: &Intent, intent_b: &Intent) -> Match {
+        Match::new(
+            intent_a.clone(),
+            intent_b.clone(),
+            SwappedAmount::new(BigUint::from(1_000_u64), BigUint::from(950_u64)),
+        )
+    }
+
+    /// Prices token 2 at 3, and token 1 at 5.
+    fn prices() -> PriceSnapshot {
+        PriceSnapshot {
+            prices: [
+                (encode([2u8; 32]), BigUint::from(3_u64)),
+                (encode([1u8; 32]), BigUint::from(5_u64)),
+            ]
+            .into_iter()
+            .collect(),
+        }
+    }
+
+    #[test]
+    fn it_works_batch_solution_circuit() {
+        // a batch of three intents, in a batch tree of four leaves, with two match slots
+        let intents = vec![
+            signed_intent(1, (1, 2)),
+            signed_intent(2, (2, 1)),
+            signed_intent(3, (1, 2)),
+        ];
+        let prices = prices();
+        let solution = BatchSolution::new(
+            vec![match_instance(&intents[0], &intents[1])],
+            prices.clone(),
+        );
+        assert_eq!(*solution.total_liquidity(), BigUint::from(2_850_u64));
+        let batch_root = batch_root(&intents);
+        let batch_timestamp = date(10);
+
+        let (circuit_data, targets) = generate_batch_solution_circuit::<F, D>(4, 2).build::<C>();
+        let mut partial_witness = PartialWitness::new();
+        set_batch_solution_targets::<F, D>(
+            &mut partial_witness,
+            &SolutionWitness {
+                intents: &intents,
+                batch_root,
+                batch_timestamp,
+                solution: &solution,
+                prices: &prices,
+            },
+            &targets,
+        )
+        .unwrap();
+        let proof = circuit_data.prove(partial_witness).unwrap().to_bytes();
+
+        let public_inputs =
+            BatchSolutionPublicInputs::new(batch_root, batch_timestamp, &solution, &prices);
+        let verifier_data = circuit_data.verifier_data();
+        verify_batch_solution_proof_with(&verifier_data, &proof, &public_inputs).unwrap();
+        verify_batch_solution_proof(
+            &verifier_data.to_bytes(&SolinaGateSerializer).unwrap(),
+            &proof,
+            &public_inputs,
+        )
+        .unwrap();
+
+        // the proof is bound to the batch it was generated for
+        for (name, public_inputs) in [
+            (
+                "batch root",
+                BatchSolutionPublicInputs::new(
+                    solina::batch::batch_root(&intents[..2]),
+                    batch_timestamp,
+                    &solution,
+                    &prices,
+                ),
+            ),
+            (
+                "batch timestamp",
+                BatchSolutionPublicInputs::new(batch_root, date(11), &solution, &prices),
+            ),
+            (
+                "score",
+                BatchSolutionPublicInputs {
+                    score: BigUint::from(2_851_u64),
+                    ..public_inputs.clone()
+                },
+            ),
+            (
+                "settlements",
+                BatchSolutionPublicInputs {
+                    settlements: BatchSolution::new(
+                        vec![Match::new(
+                            intents[0].clone(),
+                            intents[1].clone(),
+                            SwappedAmount::new(BigUint::from(1_000_u64), BigUint::from(951_u64)),
+                        )],
+                        prices.clone(),
+                    )
+                    .settlements_with(&prices),
+                    ..public_inputs.clone()
+                },
+            ),
+            (
+                "nullifiers",
+                BatchSolutionPublicInputs {
+                    nullifiers: public_inputs.nullifiers.iter().rev().cloned().collect(),
+                    ..public_inputs.clone()
+                },
+            ),
+        ] {
+            match verify_batch_solution_proof_with(&verifier_data, &proof, &public_inputs) {
+                Err(ProofVerificationError::PublicInputMismatch(found)) => assert_eq!(found, name),
+                result => panic!("Unexpected result: {:?}", result),
+            }
+        }
+    }
+
+    #[test]
+    fn it_rejects_unsupported_solutions() {
+        let intents = vec![
+            signed_intent(1, (1, 2)),
+            signed_intent(2, (2, 1)),
+            signed_intent(3, (1, 2)),
+        ];
+        let prices = prices();
+        let targets = generate_batch_solution_circuit::<F, D>(2, 2).targets;
+        let set_targets = |intents: &[Intent], matches: Vec<Match>| {
+            set_batch_solution_targets::<F, D>(
+                &mut PartialWitness::new(),
+                &SolutionWitness {
+                    intents,
+                    batch_root: batch_root(intents),
+                    batch_timestamp: date(10),
+                    solution: &BatchSolution::new(matches, prices.clone()),
+                    prices: &prices,
+                },
+                &targets,
+            )
+        };
+
+        assert_eq!(
+            set_targets(&intents, vec![match_instance(&intents[0], &intents[1])]),
+            Err(BatchSolutionCircuitError::BatchTooLarge(3))
+        );
+        assert_eq!(
+            set_targets(&intents[..2], vec![]),
+            Err(BatchSolutionCircuitError::UnsupportedMatchCount(0))
+        );
+        assert_eq!(
+            set_targets(
+                &intents[1..],
+                vec![match_instance(&intents[0], &intents[1])]
+            ),
+            Err(BatchSolutionCircuitError::UnknownIntent(
+                intents[0].structured_hash()
+            ))
+        );
+        // the contents of intent 1, re-signed by someone else, are not in the batch
+        let mut forged = intents[1].clone();
+        sign_intent(
+            &mut forged,
+            ECDSASecretKey(Secp256K1Scalar::from_canonical_u64(9)),
+        );
+        assert_eq!(
+            set_targets(&intents[..2], vec![match_instance(&intents[0], &forged)]),
+            Err(BatchSolutionCircuitError::UnknownIntent(
+                intents[1].structured_hash()
+            ))
+        );
+        assert_eq!(
+            set_targets(
+                &intents[..2],
+                vec![
+                    match_instance(&intents[0], &intents[1]),
+                    match_instance(&intents[0], &intents[1])
+                ]
+            ),
+            Err(BatchSolutionCircuitError::IntentMatchedTwice(
+                intents[0].structured_hash()
+            ))
+        );
+    }
+}
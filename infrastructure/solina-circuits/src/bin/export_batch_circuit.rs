@@ -0,0 +1,46 @@
+use solina_circuits::batch_circuit::{
+    batch_solution_prover, batch_solution_registry, batch_solution_shape, MAX_BATCH_TREE_SIZE,
+};
+use std::{env, process::exit};
+
+const USAGE: &str = "Usage: export_batch_circuit <artifacts dir> <max batch size> <max matches>";
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    if args.len() != 3 {
+        eprintln!("{}", USAGE);
+        exit(2);
+    }
+
+    match export(&args[0], &args[1], &args[2]) {
+        Ok(circuit_id) => println!("Exported circuit {}", circuit_id),
+        Err(e) => {
+            eprintln!("Circuit export failed: {}", e);
+            exit(1);
+        }
+    }
+}
+
+/// Builds the batch solution circuit, unless its artifacts are already up to date under
+/// `artifacts_dir`, and returns its id, to be set as the service `solution_circuit_id`.
+fn export(artifacts_dir: &str, max_batch_size: &str, max_matches: &str) -> Result<String, String> {
+    let parse = |value: &str| {
+        value
+            .parse::<usize>()
+            .map_err(|e| format!("Invalid size {}: {}", value, e))
+    };
+    let (max_batch_size, max_matches) = (parse(max_batch_size)?, parse(max_matches)?);
+    if !(1..=MAX_BATCH_TREE_SIZE).contains(&max_batch_size) {
+        return Err(format!(
+            "Max batch size must be between 1 and {}",
+            MAX_BATCH_TREE_SIZE
+        ));
+    }
+    if max_matches == 0 {
+        return Err("Max matches must be positive".to_string());
+    }
+
+    let mut registry = batch_solution_registry(artifacts_dir);
+    batch_solution_prover(&mut registry, max_batch_size, max_matches).map_err(|e| e.to_string())?;
+    Ok(batch_solution_shape(max_batch_size, max_matches).id())
+}
@@ -0,0 +1,684 @@
+// CHAIN_DESIGN:
+//
+// 1. Each batch gets its own solution proof, but nothing links batch N to batch N - 1. The
+//    chain circuit is a cyclic recursive circuit which, for batch N, verifies both the batch
+//    N solution proof and the chain proof of batch N - 1. Holding the latest chain proof is
+//    then enough to check the whole settlement history of a Solina instance, in constant time.
+// 2. The chain proof commits to a running state:
+//      - nullifier root: the root of the set of nullifiers spent by every batch so far (see
+//        `nullifier_set`),
+//      - state root: Poseidon hash of the previous state root, of the nullifier root, of the
+//        batch root and of the batch timestamp, so it commits to every batch so far,
+//      - batch root: the root of the latest batch, 8 u32 limbs (as in `verifier`),
+//      - batch timestamp: the timestamp of the latest batch, 2 u32 limbs (as in `expiry`),
+//      - batch count: the number of batches in the chain.
+//    The first batch of the chain starts from a zero state, but for the root of the empty
+//    nullifier set (see `ChainState::genesis`).
+// 3. The batch solution proofs are verified against constant verifier data, so all batches
+//    of a chain must be proven with the same batch circuit (same `registry` shape).
+// 4. Every nullifier output by the batch proof is inserted into the nullifier set, which
+//    fails if it was already spent, so that no intent is settled twice along the chain. Each
+//    batch must also be sealed strictly after the previous one, which, in particular, rejects
+//    a batch proof replayed right after itself. The Solina service rejects solutions spending
+//    already settled intents too, before they are proven.
+// 5. A cyclic circuit must verify proofs of its own common data, which is only known once
+//    the circuit is built. As in plonky2 examples, both are padded to a fixed degree.
+// 6. Batch circuits are much larger than the chain circuit (signature verification, in
+//    particular). Batch proofs are first shrunk by wrapper circuits, each verifying the proof
+//    of the previous one and exposing the same public inputs, until their degree is at most
+//    `MAX_BATCH_DEGREE_BITS`.
+// 7. Nullifier insertions grow with the number of match slots of the batch circuit, and must
+//    fit in the chain circuit degree along with both proof verifications.
+use crate::{
+    nullifier_set::{
+        add_nullifier_insertion_targets, set_nullifier_insertion_targets,
+        NullifierInsertionTargets, NullifierSet, NullifierSetError, NULLIFIER_SET_DEPTH,
+    },
+    verifier::{
+        structured_hash_elements, BatchSolutionPublicInputs, ProofVerificationError,
+        BATCH_ROOT_LIMBS, C, D, F, PUBLIC_INPUTS_HEADER_LEN, TIMESTAMP_LIMBS,
+    },
+};
+use plonky2::{
+    field::types::{Field, PrimeField64},
+    gates::noop::NoopGate,
+    hash::{
+        hash_types::{HashOut, HashOutTarget},
+        poseidon::PoseidonHash,
+    },
+    iop::{
+        target::BoolTarget,
+        witness::{PartialWitness, WitnessWrite},
+    },
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::{
+            CircuitConfig, CircuitData, CommonCircuitData, VerifierCircuitData,
+            VerifierCircuitTarget,
+        },
+        config::Hasher,
+        proof::{ProofWithPublicInputs, ProofWithPublicInputsTarget},
+    },
+    recursion::{
+        cyclic_recursion::check_cyclic_proof_verifier_data, dummy_circuit::cyclic_base_proof,
+    },
+};
+use plonky2_u32::gadgets::{arithmetic_u32::U32Target, multiple_comparison::list_le_u32_circuit};
+use solina::structured_hash::StructuredHash;
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Degree of the chain circuit, large enough to verify a batch proof and a chain proof.
+const CHAIN_DEGREE_BITS: usize = 14;
+/// Largest degree of the batch proofs verified by the chain circuit.
+const MAX_BATCH_DEGREE_BITS: usize = 13;
+/// Offset of the batch timestamp in the chain state public inputs.
+const CHAIN_TIMESTAMP_OFFSET: usize = 4 + 4 + BATCH_ROOT_LIMBS;
+/// Number of chain state public inputs, preceding the verifier data public inputs.
+const CHAIN_STATE_LEN: usize = CHAIN_TIMESTAMP_OFFSET + TIMESTAMP_LIMBS + 1;
+
+#[derive(Debug, Error)]
+pub enum BatchChainError {
+    #[error("Incompatible batch circuit: `{0}`")]
+    IncompatibleBatchCircuit(String),
+    #[error("Circuit Error: `{0}`")]
+    CircuitError(String),
+    #[error("Prover Error: `{0}`")]
+    ProverError(String),
+    #[error("Batch sealed before the latest batch of the chain")]
+    StaleBatch,
+    #[error("Nullifier Set Error: `{0}`")]
+    NullifierSetError(#[from] NullifierSetError),
+}
+
+/// The running state committed to by a chain proof.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChainState {
+    pub state_root: HashOut<F>,
+    pub nullifier_root: HashOut<F>,
+    pub batch_root: StructuredHash,
+    /// Unix seconds.
+    pub batch_timestamp: u64,
+    pub batch_count: u64,
+}
+
+impl ChainState {
+    pub fn genesis() -> Self {
+        Self {
+            state_root: HashOut::ZERO,
+            nullifier_root: NullifierSet::empty_root(),
+            batch_root: [0u8; 32],
+            batch_timestamp: 0,
+            batch_count: 0,
+        }
+    }
+
+    /// The state after settling a batch, as computed in circuit, from the public inputs of
+    /// its solution proof (see `BatchChainCircuit::batch_public_inputs`), and the root of the
+    /// nullifier set once its nullifiers are inserted (see `BatchChainCircuit::prove`).
+    pub fn next(&self, nullifier_root: HashOut<F>, batch_public_inputs: &[F]) -> Self {
+        let batch_root = &batch_public_inputs[..BATCH_ROOT_LIMBS];
+        let batch_timestamp =
+            &batch_public_inputs[BATCH_ROOT_LIMBS..BATCH_ROOT_LIMBS + TIMESTAMP_LIMBS];
+        let state_root = PoseidonHash::hash_no_pad(
+            &[
+                self.state_root.elements.to_vec(),
+                nullifier_root.elements.to_vec(),
+                batch_root.to_vec(),
+                batch_timestamp.to_vec(),
+            ]
+            .concat(),
+        );
+
+        Self {
+            state_root,
+            nullifier_root,
+            batch_root: batch_root
+                .iter()
+                .flat_map(|limb| (limb.to_canonical_u64() as u32).to_be_bytes())
+                .collect::<Vec<_>>()
+                .try_into()
+                .expect("Batch roots are 8 u32 limbs long"),
+            batch_timestamp: timestamp_from_limbs(batch_timestamp),
+            batch_count: self.batch_count + 1,
+        }
+    }
+
+    /// Chain state public inputs, in the order they are registered by the circuit.
+    pub fn to_field_elements(&self) -> Vec<F> {
+        [
+            self.state_root.elements.to_vec(),
+            self.nullifier_root.elements.to_vec(),
+            structured_hash_elements(&self.batch_root),
+            vec![
+                F::from_canonical_u32(self.batch_timestamp as u32),
+                F::from_canonical_u32((self.batch_timestamp >> 32) as u32),
+                F::from_canonical_u64(self.batch_count),
+            ],
+        ]
+        .concat()
+    }
+}
+
+/// Timestamp of two little endian u32 limbs.
+fn timestamp_from_limbs(limbs: &[F]) -> u64 {
+    limbs[0].to_canonical_u64() | (limbs[1].to_canonical_u64() << 32)
+}
+
+pub struct BatchChainTargets {
+    has_previous: BoolTarget,
+    previous_proof: ProofWithPublicInputsTarget<D>,
+    batch_proof: ProofWithPublicInputsTarget<D>,
+    nullifier_insertions: Vec<NullifierInsertionTargets>,
+    verifier_data: VerifierCircuitTarget,
+}
+
+/// Circuit verifying a batch proof, exposing the same public inputs, with a lower degree
+/// than the batch circuit.
+struct ShrinkingCircuit {
+    circuit_data: CircuitData<F, C, D>,
+    proof: ProofWithPublicInputsTarget<D>,
+}
+
+impl ShrinkingCircuit {
+    fn new(inner_circuit: &VerifierCircuitData<F, C, D>) -> Self {
+        let mut circuit_builder =
+            CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let proof = circuit_builder.add_virtual_proof_with_pis(&inner_circuit.common);
+        let verifier_data = circuit_builder.constant_verifier_data(&inner_circuit.verifier_only);
+        circuit_builder.verify_proof::<C>(&proof, &verifier_data, &inner_circuit.common);
+        circuit_builder.register_public_inputs(&proof.public_inputs);
+
+        Self {
+            circuit_data: circuit_builder.build::<C>(),
+            proof,
+        }
+    }
+
+    fn prove(
+        &self,
+        inner_proof: &ProofWithPublicInputs<F, C, D>,
+    ) -> Result<ProofWithPublicInputs<F, C, D>, BatchChainError> {
+        let mut partial_witness = PartialWitness::new();
+        partial_witness.set_proof_with_pis_target(&self.proof, inner_proof);
+        self.circuit_data
+            .prove(partial_witness)
+            .map_err(|e| BatchChainError::ProverError(e.to_string()))
+    }
+}
+
+pub struct BatchChainCircuit {
+    shrinking_circuits: Vec<ShrinkingCircuit>,
+    circuit_data: CircuitData<F, C, D>,
+    targets: BatchChainTargets,
+    num_batch_public_inputs: usize,
+}
+
+impl BatchChainCircuit {
+    /// Builds the chain circuit for batch proofs of the given batch circuit.
+    pub fn new(batch_circuit: &VerifierCircuitData<F, C, D>) -> Result<Self, BatchChainError> {
+        let num_batch_public_inputs = batch_circuit.common.num_public_inputs;
+        if num_batch_public_inputs < PUBLIC_INPUTS_HEADER_LEN
+            || (num_batch_public_inputs - PUBLIC_INPUTS_HEADER_LEN) % 4 != 0
+        {
+            return Err(BatchChainError::IncompatibleBatchCircuit(format!(
+                "unexpected number of public inputs {}",
+                num_batch_public_inputs
+            )));
+        }
+
+        // 0. Shrink batch proofs, until the chain circuit can verify them
+        let mut shrinking_circuits = vec![];
+        let mut shrunk_circuit = None;
+        loop {
+            let inner_circuit = shrunk_circuit.as_ref().unwrap_or(batch_circuit);
+            let inner_degree_bits = inner_circuit.common.degree_bits();
+            if inner_degree_bits <= MAX_BATCH_DEGREE_BITS {
+                break;
+            }
+            let shrinking_circuit = ShrinkingCircuit::new(inner_circuit);
+            if shrinking_circuit.circuit_data.common.degree_bits() >= inner_degree_bits {
+                return Err(BatchChainError::IncompatibleBatchCircuit(format!(
+                    "batch proofs of degree 2^{} cannot be shrunk",
+                    inner_degree_bits
+                )));
+            }
+            shrunk_circuit = Some(shrinking_circuit.circuit_data.verifier_data());
+            shrinking_circuits.push(shrinking_circuit);
+        }
+        let batch_circuit = shrunk_circuit.as_ref().unwrap_or(batch_circuit);
+
+        let mut circuit_builder =
+            CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+
+        // 1. Verify the (shrunk) batch solution proof
+        let batch_proof = circuit_builder.add_virtual_proof_with_pis(&batch_circuit.common);
+        let batch_verifier_data =
+            circuit_builder.constant_verifier_data(&batch_circuit.verifier_only);
+        circuit_builder.verify_proof::<C>(
+            &batch_proof,
+            &batch_verifier_data,
+            &batch_circuit.common,
+        );
+        let batch_root = batch_proof.public_inputs[..BATCH_ROOT_LIMBS].to_vec();
+        let batch_timestamp = batch_proof.public_inputs
+            [BATCH_ROOT_LIMBS..BATCH_ROOT_LIMBS + TIMESTAMP_LIMBS]
+            .to_vec();
+        let nullifiers = batch_proof.public_inputs[PUBLIC_INPUTS_HEADER_LEN..]
+            .chunks(4)
+            .map(|nullifier| HashOutTarget::from_vec(nullifier.to_vec()))
+            .collect::<Vec<_>>();
+
+        // 2. Register the chain state, followed by the chain circuit verifier data
+        let state_root = circuit_builder.add_virtual_hash();
+        let nullifier_root = circuit_builder.add_virtual_hash();
+        let batch_count = circuit_builder.add_virtual_target();
+        circuit_builder.register_public_inputs(&state_root.elements);
+        circuit_builder.register_public_inputs(&nullifier_root.elements);
+        circuit_builder.register_public_inputs(&batch_root);
+        circuit_builder.register_public_inputs(&batch_timestamp);
+        circuit_builder.register_public_input(batch_count);
+
+        let mut common_data = common_data_for_recursion();
+        let verifier_data = circuit_builder.add_verifier_data_public_inputs();
+        common_data.num_public_inputs = circuit_builder.num_public_inputs();
+
+        // 3. Read the previous chain state, or the genesis state for the first batch
+        let has_previous = circuit_builder.add_virtual_bool_target_safe();
+        let previous_proof = circuit_builder.add_virtual_proof_with_pis(&common_data);
+        let previous_state = &previous_proof.public_inputs;
+
+        let zero_hash = circuit_builder.constant_hash(HashOut::ZERO);
+        let previous_state_root = HashOutTarget::from_vec(previous_state[0..4].to_vec());
+        let previous_state_root =
+            circuit_builder.select_hash(has_previous, previous_state_root, zero_hash);
+        let empty_nullifier_root = circuit_builder.constant_hash(NullifierSet::empty_root());
+        let previous_nullifier_root = HashOutTarget::from_vec(previous_state[4..8].to_vec());
+        let previous_nullifier_root = circuit_builder.select_hash(
+            has_previous,
+            previous_nullifier_root,
+            empty_nullifier_root,
+        );
+        let previous_batch_timestamp = previous_state
+            [CHAIN_TIMESTAMP_OFFSET..CHAIN_TIMESTAMP_OFFSET + TIMESTAMP_LIMBS]
+            .iter()
+            .map(|limb| U32Target(*limb))
+            .collect();
+        let previous_batch_count = previous_state[CHAIN_STATE_LEN - 1];
+
+        // 4. Batches must be sealed strictly after the previous one
+        // timestamp > previous_timestamp <=> !(timestamp <= previous_timestamp)
+        let is_stale = list_le_u32_circuit(
+            &mut circuit_builder,
+            batch_timestamp
+                .iter()
+                .map(|limb| U32Target(*limb))
+                .collect(),
+            previous_batch_timestamp,
+        );
+        let is_stale = circuit_builder.and(has_previous, is_stale);
+        circuit_builder.assert_zero(is_stale.target);
+
+        // 5. Insert the batch nullifiers into the set of spent nullifiers
+        let mut next_nullifier_root = previous_nullifier_root;
+        let nullifier_insertions = nullifiers
+            .into_iter()
+            .map(|nullifier| {
+                let insertion = add_nullifier_insertion_targets(
+                    &mut circuit_builder,
+                    next_nullifier_root,
+                    nullifier,
+                );
+                next_nullifier_root = insertion.new_root;
+                insertion
+            })
+            .collect::<Vec<_>>();
+        circuit_builder.connect_hashes(nullifier_root, next_nullifier_root);
+
+        // 6. Fold the batch into the chain state
+        let next_state_root = circuit_builder.hash_n_to_hash_no_pad::<PoseidonHash>(
+            [
+                previous_state_root.elements.to_vec(),
+                nullifier_root.elements.to_vec(),
+                batch_root,
+                batch_timestamp,
+            ]
+            .concat(),
+        );
+        circuit_builder.connect_hashes(state_root, next_state_root);
+
+        let one = circuit_builder.one();
+        let next_batch_count =
+            circuit_builder.mul_add(has_previous.target, previous_batch_count, one);
+        circuit_builder.connect(batch_count, next_batch_count);
+
+        // 7. Verify the previous chain proof, if any
+        circuit_builder
+            .conditionally_verify_cyclic_proof_or_dummy::<C>(
+                has_previous,
+                &previous_proof,
+                &common_data,
+            )
+            .map_err(|e| BatchChainError::CircuitError(e.to_string()))?;
+
+        if circuit_builder.num_gates() > 1 << CHAIN_DEGREE_BITS {
+            return Err(BatchChainError::IncompatibleBatchCircuit(format!(
+                "{} nullifiers do not fit in a chain circuit of degree 2^{}",
+                nullifier_insertions.len(),
+                CHAIN_DEGREE_BITS
+            )));
+        }
+        while circuit_builder.num_gates() < 1 << CHAIN_DEGREE_BITS {
+            circuit_builder.add_gate(NoopGate, vec![]);
+        }
+
+        Ok(Self {
+            shrinking_circuits,
+            circuit_data: circuit_builder.build::<C>(),
+            targets: BatchChainTargets {
+                has_previous,
+                previous_proof,
+                batch_proof,
+                nullifier_insertions,
+                verifier_data,
+            },
+            num_batch_public_inputs,
+        })
+    }
+
+    pub fn circuit_data(&self) -> &CircuitData<F, C, D> {
+        &self.circuit_data
+    }
+
+    /// Public inputs of a batch solution proof of the batch circuit, that is, the expected
+    /// public inputs followed by the nullifier padding of unused match slots.
+    pub fn batch_public_inputs(
+        &self,
+        batch: &BatchSolutionPublicInputs,
+    ) -> Result<Vec<F>, ProofVerificationError> {
+        let mut public_inputs = batch.to_field_elements()?;
+        if public_inputs.len() > self.num_batch_public_inputs {
+            return Err(ProofVerificationError::PublicInputMismatch(format!(
+                "expected at most {} public inputs, found {}",
+                self.num_batch_public_inputs,
+                public_inputs.len()
+            )));
+        }
+        public_inputs.resize(self.num_batch_public_inputs, F::ZERO);
+        Ok(public_inputs)
+    }
+
+    /// Proves the next link of the chain, from the chain proof of the previous batch
+    /// (`None` for the first batch) and the solution proof of the current batch, inserting
+    /// the batch nullifiers into `nullifier_set`, the set of nullifiers spent by the chain so
+    /// far. The set is left unchanged on error.
+    pub fn prove(
+        &self,
+        previous_proof: Option<&ProofWithPublicInputs<F, C, D>>,
+        batch_proof: &ProofWithPublicInputs<F, C, D>,
+        nullifier_set: &mut NullifierSet,
+    ) -> Result<ProofWithPublicInputs<F, C, D>, BatchChainError> {
+        if let Some(previous_proof) = previous_proof {
+            let batch_timestamp = timestamp_from_limbs(
+                &batch_proof.public_inputs[BATCH_ROOT_LIMBS..BATCH_ROOT_LIMBS + TIMESTAMP_LIMBS],
+            );
+            let previous_batch_timestamp =
+                timestamp_from_limbs(&previous_proof.public_inputs[CHAIN_TIMESTAMP_OFFSET..]);
+            if batch_timestamp <= previous_batch_timestamp {
+                return Err(BatchChainError::StaleBatch);
+            }
+        }
+
+        let mut next_nullifier_set = nullifier_set.clone();
+        let nullifier_siblings = batch_proof.public_inputs[PUBLIC_INPUTS_HEADER_LEN..]
+            .chunks(4)
+            .map(|nullifier| match HashOut::from_vec(nullifier.to_vec()) {
+                // padding, left out of the set
+                nullifier if nullifier == HashOut::ZERO => {
+                    Ok(vec![HashOut::ZERO; NULLIFIER_SET_DEPTH])
+                }
+                nullifier => next_nullifier_set.insert(nullifier),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let batch_proof = self
+            .shrinking_circuits
+            .iter()
+            .try_fold(batch_proof.clone(), |proof, shrinking_circuit| {
+                shrinking_circuit.prove(&proof)
+            })?;
+
+        let mut partial_witness = PartialWitness::new();
+        partial_witness.set_proof_with_pis_target(&self.targets.batch_proof, &batch_proof);
+        partial_witness.set_verifier_data_target(
+            &self.targets.verifier_data,
+            &self.circuit_data.verifier_only,
+        );
+        self.targets
+            .nullifier_insertions
+            .iter()
+            .zip(&nullifier_siblings)
+            .for_each(|(targets, siblings)| {
+                set_nullifier_insertion_targets(&mut partial_witness, siblings, targets)
+            });
+
+        match previous_proof {
+            Some(previous_proof) => {
+                partial_witness.set_bool_target(self.targets.has_previous, true);
+                partial_witness
+                    .set_proof_with_pis_target(&self.targets.previous_proof, previous_proof);
+            }
+            None => {
+                partial_witness.set_bool_target(self.targets.has_previous, false);
+                partial_witness.set_proof_with_pis_target(
+                    &self.targets.previous_proof,
+                    &cyclic_base_proof(
+                        &self.circuit_data.common,
+                        &self.circuit_data.verifier_only,
+                        HashMap::new(),
+                    ),
+                );
+            }
+        }
+
+        let proof = self
+            .circuit_data
+            .prove(partial_witness)
+            .map_err(|e| BatchChainError::ProverError(e.to_string()))?;
+        *nullifier_set = next_nullifier_set;
+        Ok(proof)
+    }
+
+    /// Verifies a chain proof, and that it commits to the expected chain state.
+    pub fn verify(
+        &self,
+        proof: ProofWithPublicInputs<F, C, D>,
+        expected_state: &ChainState,
+    ) -> Result<(), ProofVerificationError> {
+        check_cyclic_proof_verifier_data(
+            &proof,
+            &self.circuit_data.verifier_only,
+            &self.circuit_data.common,
+        )
+        .map_err(|e| ProofVerificationError::InvalidProof(e.to_string()))?;
+
+        if proof.public_inputs[..CHAIN_STATE_LEN] != expected_state.to_field_elements() {
+            return Err(ProofVerificationError::PublicInputMismatch(
+                "chain state".to_string(),
+            ));
+        }
+
+        self.circuit_data
+            .verify(proof)
+            .map_err(|e| ProofVerificationError::InvalidProof(e.to_string()))
+    }
+}
+
+/// Common data of a circuit verifying two recursion circuits, padded to the chain circuit
+/// degree, which the chain circuit common data must be equal to.
+fn common_data_for_recursion() -> CommonCircuitData<F, D> {
+    let config = CircuitConfig::standard_recursion_config();
+    let circuit_builder = CircuitBuilder::<F, D>::new(config.clone());
+    let circuit_data = circuit_builder.build::<C>();
+
+    let mut circuit_builder = CircuitBuilder::<F, D>::new(config.clone());
+    let proof = circuit_builder.add_virtual_proof_with_pis(&circuit_data.common);
+    let verifier_data =
+        circuit_builder.add_virtual_verifier_data(circuit_data.common.config.fri_config.cap_height);
+    circuit_builder.verify_proof::<C>(&proof, &verifier_data, &circuit_data.common);
+    let circuit_data = circuit_builder.build::<C>();
+
+    let mut circuit_builder = CircuitBuilder::<F, D>::new(config);
+    let proof = circuit_builder.add_virtual_proof_with_pis(&circuit_data.common);
+    let verifier_data =
+        circuit_builder.add_virtual_verifier_data(circuit_data.common.config.fri_config.cap_height);
+    circuit_builder.verify_proof::<C>(&proof, &verifier_data, &circuit_data.common);
+    while circuit_builder.num_gates() < 1 << CHAIN_DEGREE_BITS {
+        circuit_builder.add_gate(NoopGate, vec![]);
+    }
+    circuit_builder.build::<C>().common
+}
+
+#[cfg(all(test, feature = "ecdsa"))]
+mod tests {
+    use super::*;
+    use crate::{
+        batch_circuit::{generate_batch_solution_circuit, set_batch_solution_targets},
+        prover::SolutionWitness,
+        signature::sign_intent,
+    };
+    use chrono::{NaiveDate, NaiveDateTime};
+    use num_bigint::BigUint;
+    use plonky2::field::secp256k1_scalar::Secp256K1Scalar;
+    use plonky2_ecdsa::curve::ecdsa::ECDSASecretKey;
+    use solina::{
+        batch::batch_root,
+        intent::{Intent, IntentConstraints, IntentInputs, TradeDirection},
+        price_oracle::PriceSnapshot,
+        solver::{BatchSolution, Match, SwappedAmount},
+        Signature,
+    };
+
+    fn date(day: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2023, 11, day)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+    }
+
+    fn signed_intent(secret_key: u64, tokens: (u8, u8)) -> Intent {
+        let mut intent = Intent::new(
+            [0u8; 32],
+            IntentInputs::new(
+                [tokens.0; 32],
+                [tokens.1; 32],
+                BigUint::from(1_000_u64),
+                TradeDirection::Sell,
+            ),
+            IntentConstraints::new(BigUint::from(900_u64)),
+            Signature([0u8; 64]),
+            date(28),
+        );
+        sign_intent(
+            &mut intent,
+            ECDSASecretKey(Secp256K1Scalar::from_canonical_u64(secret_key)),
+        );
+        intent
+    }
+
+    #[test]
+    fn it_works_batch_chain() {
+        // batches of two intents, settled by a single match, with two match slots
+        let (batch_circuit, batch_targets) =
+            generate_batch_solution_circuit::<F, D>(2, 2).build::<C>();
+        let chain = BatchChainCircuit::new(&batch_circuit.verifier_data()).unwrap();
+
+        let prove_batch = |secret_keys: (u64, u64), day: u32| {
+            let intents = vec![
+                signed_intent(secret_keys.0, (1, 2)),
+                signed_intent(secret_keys.1, (2, 1)),
+            ];
+            let prices = PriceSnapshot::default();
+            let solution = BatchSolution::new(
+                vec![Match::new(
+                    intents[0].clone(),
+                    intents[1].clone(),
+                    SwappedAmount::new(BigUint::from(1_000_u64), BigUint::from(950_u64)),
+                )],
+                prices.clone(),
+            );
+            let batch_root = batch_root(&intents);
+            let batch_timestamp = date(day);
+
+            let mut partial_witness = PartialWitness::new();
+            set_batch_solution_targets::<F, D>(
+                &mut partial_witness,
+                &SolutionWitness {
+                    intents: &intents,
+                    batch_root,
+                    batch_timestamp,
+                    solution: &solution,
+                    prices: &prices,
+                },
+                &batch_targets,
+            )
+            .unwrap();
+            let batch_proof = batch_circuit.prove(partial_witness).unwrap();
+
+            let public_inputs = chain
+                .batch_public_inputs(&BatchSolutionPublicInputs::new(
+                    batch_root,
+                    batch_timestamp,
+                    &solution,
+                    &prices,
+                ))
+                .unwrap();
+            assert_eq!(public_inputs, batch_proof.public_inputs);
+            (batch_proof, batch_root)
+        };
+
+        let mut state = ChainState::genesis();
+        let mut nullifier_set = NullifierSet::default();
+        let mut chain_proof = None;
+        for (secret_keys, day) in [((1, 2), 10), ((3, 4), 11)] {
+            let (batch_proof, batch_root) = prove_batch(secret_keys, day);
+            let proof = chain
+                .prove(chain_proof.as_ref(), &batch_proof, &mut nullifier_set)
+                .unwrap();
+            state = state.next(nullifier_set.root(), &batch_proof.public_inputs);
+            assert_eq!(state.batch_root, batch_root);
+            assert_eq!(state.batch_timestamp, date(day).timestamp() as u64);
+
+            chain.verify(proof.clone(), &state).unwrap();
+            chain_proof = Some(proof);
+        }
+        assert_eq!(state.batch_count, 2);
+
+        // batches sealed before the latest one are rejected
+        let (stale_batch_proof, _) = prove_batch((5, 6), 9);
+        assert!(matches!(
+            chain.prove(chain_proof.as_ref(), &stale_batch_proof, &mut nullifier_set),
+            Err(BatchChainError::StaleBatch)
+        ));
+
+        // batches settling intents already settled along the chain are rejected
+        let (replayed_batch_proof, _) = prove_batch((1, 2), 12);
+        assert!(matches!(
+            chain.prove(
+                chain_proof.as_ref(),
+                &replayed_batch_proof,
+                &mut nullifier_set
+            ),
+            Err(BatchChainError::NullifierSetError(
+                NullifierSetError::SpentNullifier(_)
+            ))
+        ));
+        assert_eq!(nullifier_set.root(), state.nullifier_root);
+
+        // the latest chain proof does not verify against an earlier state
+        assert!(matches!(
+            chain.verify(chain_proof.unwrap(), &ChainState::genesis()),
+            Err(ProofVerificationError::PublicInputMismatch(_))
+        ));
+    }
+}
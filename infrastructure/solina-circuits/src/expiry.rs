@@ -0,0 +1,75 @@
+// EXPIRY_DESIGN:
+//
+// 1. Each batch is sealed at a given timestamp, which is a public input of the batch proof.
+// 2. Every matched intent must have an expiry date strictly later than the batch timestamp,
+//    otherwise solvers could settle orders that users consider dead.
+// 3. Timestamps are unix seconds, represented as two little endian u32 limbs. Timestamps
+//    prior to the unix epoch are rejected when setting the witness.
+use chrono::NaiveDateTime;
+use plonky2::{
+    field::extension::Extendable, hash::hash_types::RichField, iop::witness::PartialWitness,
+    plonk::circuit_builder::CircuitBuilder,
+};
+use plonky2_u32::{
+    gadgets::{
+        arithmetic_u32::{CircuitBuilderU32, U32Target},
+        multiple_comparison::list_le_u32_circuit,
+    },
+    witness::WitnessU32,
+};
+
+pub type TimestampTargets = [U32Target; 2];
+
+/// The timestamp is prior to the unix epoch.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TimestampOutOfRange;
+
+pub fn add_virtual_timestamp_targets<F, const D: usize>(
+    circuit_builder: &mut CircuitBuilder<F, D>,
+) -> TimestampTargets
+where
+    F: RichField + Extendable<D>,
+{
+    let targets = circuit_builder.add_virtual_u32_targets(2);
+    [targets[0], targets[1]]
+}
+
+/// Registers the batch timestamp targets as public inputs of the circuit.
+pub fn register_batch_timestamp<F, const D: usize>(
+    circuit_builder: &mut CircuitBuilder<F, D>,
+    batch_timestamp_targets: &TimestampTargets,
+) where
+    F: RichField + Extendable<D>,
+{
+    batch_timestamp_targets
+        .iter()
+        .for_each(|t| circuit_builder.register_public_input(t.0));
+}
+
+/// Constrains `expiry_targets` to be strictly later than `batch_timestamp_targets`.
+pub fn assert_not_expired<F, const D: usize>(
+    circuit_builder: &mut CircuitBuilder<F, D>,
+    expiry_targets: &TimestampTargets,
+    batch_timestamp_targets: &TimestampTargets,
+) where
+    F: RichField + Extendable<D>,
+{
+    // expiry > batch_timestamp <=> !(expiry <= batch_timestamp)
+    let is_expired = list_le_u32_circuit(
+        circuit_builder,
+        expiry_targets.to_vec(),
+        batch_timestamp_targets.to_vec(),
+    );
+    circuit_builder.assert_zero(is_expired.target);
+}
+
+pub fn set_timestamp_targets<F: RichField>(
+    partial_witness: &mut PartialWitness<F>,
+    timestamp: &NaiveDateTime,
+    targets: &TimestampTargets,
+) -> Result<(), TimestampOutOfRange> {
+    let timestamp = u64::try_from(timestamp.timestamp()).map_err(|_| TimestampOutOfRange)?;
+    partial_witness.set_u32_target(targets[0], timestamp as u32);
+    partial_witness.set_u32_target(targets[1], (timestamp >> 32) as u32);
+    Ok(())
+}
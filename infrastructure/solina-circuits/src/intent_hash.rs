@@ -0,0 +1,334 @@
+// INTENT_HASH_DESIGN:
+//
+// 1. The circuits compute the Poseidon structured hash of every intent from its contents,
+//    matching `Intent::structured_hash_with::<Poseidon>` natively. Signatures and nullifiers
+//    are computed over this hash, which binds them to the tokens and amounts constrained by
+//    the circuit.
+// 2. Bytes are packed into big endian u32 limbs, zero padded on the right, as in
+//    `Poseidon::hash_bytes`. Token addresses are eight u32 limbs, while amounts are the 32
+//    big endian bytes of `encode_amount`, so that the circuit does not depend on the intent.
+// 3. Digests are hashed again through their bytes (four big endian u64), as in
+//    `Poseidon::digest_to_bytes`, that is, as eight u32 limbs.
+// 4. Type hashes do not depend on the intent, and are circuit constants.
+// 5. The expiry date is hashed as the 8 big endian bytes of its unix seconds, that is, as
+//    the high and low u32 limbs of the timestamp targets of `expiry`. The expiry targets
+//    compared to the batch timestamp are then the signed ones.
+use crate::expiry::{
+    add_virtual_timestamp_targets, set_timestamp_targets, TimestampOutOfRange, TimestampTargets,
+};
+use plonky2::{
+    field::{extension::Extendable, types::PrimeField64},
+    hash::{
+        hash_types::{HashOut, HashOutTarget, RichField},
+        poseidon::PoseidonHash,
+    },
+    iop::{
+        target::{BoolTarget, Target},
+        witness::{PartialWitness, WitnessWrite},
+    },
+    plonk::circuit_builder::CircuitBuilder,
+};
+use solina::{
+    intent::{encode_amount, Intent, IntentConstraints, IntentInputs, AMOUNT_ENCODING_LEN},
+    structured_hash::{HashBackend, Poseidon, PoseidonDigest, StructuredHashInterface},
+};
+
+pub const TOKEN_ADDRESS_LIMBS: usize = 8;
+
+/// Big endian u32 limbs of a token address.
+pub type TokenAddressTargets = [Target; TOKEN_ADDRESS_LIMBS];
+
+pub struct IntentHashTargets {
+    pub quote_token: TokenAddressTargets,
+    pub base_token: TokenAddressTargets,
+    /// Big endian bytes of the quote amount.
+    pub quote_amount: Vec<Target>,
+    pub direction: BoolTarget,
+    /// Big endian bytes of the minimum base token amount.
+    pub min_base_token_amount: Vec<Target>,
+    pub expiry_date: TimestampTargets,
+    pub structured_hash: HashOutTarget,
+}
+
+/// Converts a native Poseidon digest to a digest over `F`.
+pub fn to_hash_out<F: RichField>(digest: &PoseidonDigest) -> HashOut<F> {
+    HashOut::from_vec(
+        digest
+            .elements
+            .iter()
+            .map(|e| F::from_canonical_u64(e.to_canonical_u64()))
+            .collect(),
+    )
+}
+
+/// Adds `n` virtual targets, range checked to `bits` bits.
+pub fn add_virtual_limbs<F, const D: usize>(
+    circuit_builder: &mut CircuitBuilder<F, D>,
+    n: usize,
+    bits: usize,
+) -> Vec<Target>
+where
+    F: RichField + Extendable<D>,
+{
+    let limbs = circuit_builder.add_virtual_targets(n);
+    limbs
+        .iter()
+        .for_each(|limb| circuit_builder.range_check(*limb, bits));
+    limbs
+}
+
+/// Packs big endian bytes into big endian u32 limbs, zero padded on the right.
+fn bytes_to_limbs<F, const D: usize>(
+    circuit_builder: &mut CircuitBuilder<F, D>,
+    bytes: &[Target],
+) -> Vec<Target>
+where
+    F: RichField + Extendable<D>,
+{
+    let mut limbs = vec![];
+    for chunk in bytes.chunks(4) {
+        let mut limb = circuit_builder.zero();
+        for (i, byte) in chunk.iter().enumerate() {
+            let shift = F::from_canonical_u32(1 << (8 * (3 - i)));
+            limb = circuit_builder.mul_const_add(shift, *byte, limb);
+        }
+        limbs.push(limb);
+    }
+    limbs
+}
+
+/// Big endian u32 limbs of the bytes of `digest`, as in `Poseidon::digest_to_bytes`.
+pub(crate) fn digest_to_limbs<F, const D: usize>(
+    circuit_builder: &mut CircuitBuilder<F, D>,
+    digest: HashOutTarget,
+) -> Vec<Target>
+where
+    F: RichField + Extendable<D>,
+{
+    digest
+        .elements
+        .iter()
+        .flat_map(|element| {
+            let (low, high) = circuit_builder.split_low_high(*element, 32, 64);
+            [high, low]
+        })
+        .collect()
+}
+
+/// Structured hash of a type, given the limbs of its data encoding.
+fn structured_hash_circuit<S, F, const D: usize>(
+    circuit_builder: &mut CircuitBuilder<F, D>,
+    data_encoding: Vec<Target>,
+) -> HashOutTarget
+where
+    S: StructuredHashInterface,
+    F: RichField + Extendable<D>,
+{
+    let type_encoding = circuit_builder.constant_hash(to_hash_out(&Poseidon::hash_bytes(
+        S::type_encode().as_bytes(),
+    )));
+    let data_encoding = circuit_builder.hash_n_to_hash_no_pad::<PoseidonHash>(data_encoding);
+    two_to_one_circuit(circuit_builder, type_encoding, data_encoding)
+}
+
+/// Poseidon's two-to-one compression, which is hashing eight elements without padding.
+pub fn two_to_one_circuit<F, const D: usize>(
+    circuit_builder: &mut CircuitBuilder<F, D>,
+    left: HashOutTarget,
+    right: HashOutTarget,
+) -> HashOutTarget
+where
+    F: RichField + Extendable<D>,
+{
+    circuit_builder.hash_n_to_hash_no_pad::<PoseidonHash>([left.elements, right.elements].concat())
+}
+
+/// Computes the Poseidon structured hash of an intent from its contents.
+pub fn add_intent_hash_targets<F, const D: usize>(
+    circuit_builder: &mut CircuitBuilder<F, D>,
+) -> IntentHashTargets
+where
+    F: RichField + Extendable<D>,
+{
+    let quote_token = add_virtual_limbs(circuit_builder, TOKEN_ADDRESS_LIMBS, 32);
+    let base_token = add_virtual_limbs(circuit_builder, TOKEN_ADDRESS_LIMBS, 32);
+    let quote_amount = add_virtual_limbs(circuit_builder, AMOUNT_ENCODING_LEN, 8);
+    let direction = circuit_builder.add_virtual_bool_target_safe();
+    let min_base_token_amount = add_virtual_limbs(circuit_builder, AMOUNT_ENCODING_LEN, 8);
+    let expiry_date = add_virtual_timestamp_targets(circuit_builder);
+
+    let direction_limb =
+        circuit_builder.mul_const(F::from_canonical_u32(1 << 24), direction.target);
+    let inputs_data_encoding = [
+        quote_token.clone(),
+        base_token.clone(),
+        bytes_to_limbs(circuit_builder, &quote_amount),
+        vec![direction_limb],
+    ]
+    .into_iter()
+    .flat_map(|limbs| {
+        let hash = circuit_builder.hash_n_to_hash_no_pad::<PoseidonHash>(limbs);
+        digest_to_limbs(circuit_builder, hash)
+    })
+    .collect();
+    let inputs_hash =
+        structured_hash_circuit::<IntentInputs, F, D>(circuit_builder, inputs_data_encoding);
+
+    let min_base_token_amount_limbs = bytes_to_limbs(circuit_builder, &min_base_token_amount);
+    let min_base_token_amount_hash =
+        circuit_builder.hash_n_to_hash_no_pad::<PoseidonHash>(min_base_token_amount_limbs);
+    let constraints_data_encoding = digest_to_limbs(circuit_builder, min_base_token_amount_hash);
+    let constraints_hash = structured_hash_circuit::<IntentConstraints, F, D>(
+        circuit_builder,
+        constraints_data_encoding,
+    );
+
+    // big endian bytes of the timestamp, that is, its high limb first
+    let expiry_date_hash = circuit_builder
+        .hash_n_to_hash_no_pad::<PoseidonHash>(vec![expiry_date[1].0, expiry_date[0].0]);
+
+    let intent_data_encoding = [
+        digest_to_limbs(circuit_builder, inputs_hash),
+        digest_to_limbs(circuit_builder, constraints_hash),
+        digest_to_limbs(circuit_builder, expiry_date_hash),
+    ]
+    .concat();
+    let structured_hash =
+        structured_hash_circuit::<Intent, F, D>(circuit_builder, intent_data_encoding);
+
+    IntentHashTargets {
+        quote_token: quote_token
+            .try_into()
+            .expect("Token addresses are eight limbs long"),
+        base_token: base_token
+            .try_into()
+            .expect("Token addresses are eight limbs long"),
+        quote_amount,
+        direction,
+        min_base_token_amount,
+        expiry_date,
+        structured_hash,
+    }
+}
+
+/// Sets big endian u32 limbs of `bytes` to `targets`.
+pub fn set_limb_targets<F: RichField>(
+    partial_witness: &mut PartialWitness<F>,
+    bytes: &[u8],
+    targets: &[Target],
+) {
+    bytes.chunks(4).zip(targets).for_each(|(limb, target)| {
+        partial_witness.set_target(
+            *target,
+            F::from_canonical_u32(u32::from_be_bytes(
+                limb.try_into().expect("Limbs are 4 bytes long"),
+            )),
+        )
+    });
+}
+
+pub fn set_intent_hash_targets<F: RichField>(
+    partial_witness: &mut PartialWitness<F>,
+    intent: &Intent,
+    targets: &IntentHashTargets,
+) -> Result<(), TimestampOutOfRange> {
+    let inputs = &intent.inputs;
+    set_limb_targets(partial_witness, &inputs.quote_token, &targets.quote_token);
+    set_limb_targets(partial_witness, &inputs.base_token, &targets.base_token);
+    for (bytes, byte_targets) in [
+        (encode_amount(&inputs.quote_amount), &targets.quote_amount),
+        (
+            encode_amount(&intent.constraints.min_base_token_amount),
+            &targets.min_base_token_amount,
+        ),
+    ] {
+        bytes.iter().zip(byte_targets).for_each(|(byte, target)| {
+            partial_witness.set_target(*target, F::from_canonical_u8(*byte))
+        });
+    }
+    partial_witness.set_bool_target(targets.direction, inputs.direction.to_bool());
+    set_timestamp_targets(partial_witness, intent.expiry_date(), &targets.expiry_date)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use num_bigint::BigUint;
+    use plonky2::plonk::{
+        circuit_data::CircuitConfig,
+        config::{GenericConfig, PoseidonGoldilocksConfig},
+    };
+    use solina::{intent::TradeDirection, Signature};
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    fn intent(quote_amount: u64, min_base_token_amount: u64, direction: TradeDirection) -> Intent {
+        let mut quote_token = [0u8; 32];
+        quote_token[0] = 255;
+        let mut base_token = [0u8; 32];
+        base_token[31] = 64;
+
+        Intent::new(
+            [7u8; 32],
+            IntentInputs::new(
+                quote_token,
+                base_token,
+                BigUint::from(quote_amount),
+                direction,
+            ),
+            IntentConstraints::new(BigUint::from(min_base_token_amount)),
+            Signature([0u8; 64]),
+            NaiveDate::from_ymd_opt(2023, 11, 10)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap(),
+        )
+    }
+
+    /// Proves the structured hash of `intent` in circuit, exposed as a public input.
+    fn prove_structured_hash(intent: &Intent) -> Vec<F> {
+        let mut circuit_builder =
+            CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let targets = add_intent_hash_targets(&mut circuit_builder);
+        circuit_builder.register_public_inputs(&targets.structured_hash.elements);
+        let circuit_data = circuit_builder.build::<C>();
+
+        let mut partial_witness = PartialWitness::new();
+        set_intent_hash_targets(&mut partial_witness, intent, &targets).unwrap();
+        let proof = circuit_data.prove(partial_witness).unwrap();
+        circuit_data.verify(proof.clone()).unwrap();
+        proof.public_inputs
+    }
+
+    #[test]
+    fn it_works_native_and_circuit_structured_hash_equivalence() {
+        // amounts of 5 and 1 significant bytes, and a zero amount, all 32 bytes long
+        for intent in [
+            intent(1_000_000_000_000, 64, TradeDirection::Buy),
+            intent(7, 0, TradeDirection::Sell),
+        ] {
+            assert_eq!(
+                prove_structured_hash(&intent),
+                to_hash_out::<F>(&intent.structured_hash_with::<Poseidon>()).elements
+            );
+        }
+    }
+
+    #[test]
+    fn it_binds_the_structured_hash_to_the_intent() {
+        let intent = intent(1_000, 64, TradeDirection::Buy);
+        let mut other_intent = intent.clone();
+        other_intent.inputs.quote_amount = BigUint::from(1_001_u64);
+        let mut extended_intent = intent.clone();
+        extended_intent.expiry_date += chrono::Duration::seconds(1);
+        for other_intent in [other_intent, extended_intent] {
+            assert_ne!(
+                prove_structured_hash(&other_intent),
+                to_hash_out::<F>(&intent.structured_hash_with::<Poseidon>()).elements
+            );
+        }
+    }
+}
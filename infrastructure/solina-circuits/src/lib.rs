@@ -1,2 +1,13 @@
+pub mod amount;
+pub mod batch_circuit;
+pub mod chain;
+pub mod expiry;
+pub mod intent_hash;
 pub mod match_circuit;
+pub mod nullifier;
+pub mod nullifier_set;
+pub mod prover;
+pub mod registry;
+pub mod signature;
 pub mod solver_circuit;
+pub mod verifier;
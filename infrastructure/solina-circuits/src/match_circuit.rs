@@ -1,18 +1,54 @@
+use chrono::NaiveDateTime;
 use plonky2::{
     field::extension::Extendable,
-    hash::hash_types::RichField,
-    plonk::{circuit_builder::CircuitBuilder, circuit_data::CircuitConfig},
+    hash::hash_types::{HashOutTarget, RichField},
+    iop::{target::Target, witness::PartialWitness},
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::{CircuitConfig, CircuitData},
+        config::GenericConfig,
+    },
 };
-use plonky2_u32::gadgets::arithmetic_u32::{CircuitBuilderU32, U32Target};
 use solina::solver::Match;
 
-pub type TokenAddressTargets = [U32Target; 4];
+use crate::{
+    amount::{
+        add_virtual_amount_targets, amount_from_be_bytes, assert_amount_le, set_amount_targets,
+        AmountOutOfRange, AmountTargets,
+    },
+    expiry::{
+        add_virtual_timestamp_targets, assert_not_expired, register_batch_timestamp,
+        set_timestamp_targets, TimestampOutOfRange, TimestampTargets,
+    },
+    intent_hash::{
+        add_intent_hash_targets, set_intent_hash_targets, IntentHashTargets, TokenAddressTargets,
+        TOKEN_ADDRESS_LIMBS,
+    },
+    nullifier::{
+        add_nullifier_targets, add_public_key_targets, set_public_key_targets, NullifierTargets,
+    },
+    signature::{
+        add_intent_signature_verification, set_intent_signature_targets, IntentSignatureTargets,
+        SignatureCircuitError,
+    },
+};
 
 pub struct MatchTargets {
+    intent_a_hash_targets: IntentHashTargets,
+    intent_b_hash_targets: IntentHashTargets,
+    intent_a_public_key_targets: Vec<Target>,
+    intent_b_public_key_targets: Vec<Target>,
     intent_a_quote_token_targets: TokenAddressTargets,
     intent_b_base_token_targets: TokenAddressTargets,
     intent_a_base_token_targets: TokenAddressTargets,
     intent_b_quote_token_targets: TokenAddressTargets,
+    token_a_amount_targets: AmountTargets,
+    token_b_amount_targets: AmountTargets,
+    intent_a_signature_targets: IntentSignatureTargets,
+    intent_b_signature_targets: IntentSignatureTargets,
+    batch_timestamp_targets: TimestampTargets,
+    intent_a_nullifier_targets: NullifierTargets,
+    intent_b_nullifier_targets: NullifierTargets,
 }
 
 pub struct MatchCircuitData<F: RichField + Extendable<D>, const D: usize> {
@@ -20,65 +56,461 @@ pub struct MatchCircuitData<F: RichField + Extendable<D>, const D: usize> {
     targets: MatchTargets,
 }
 
-pub fn generate_match_circuit<F, const D: usize>(match_instance: Match) -> MatchCircuitData<F, D>
+impl<F: RichField + Extendable<D>, const D: usize> MatchCircuitData<F, D> {
+    /// Builds the match circuit, together with the targets its witness is set on.
+    pub fn build<C: GenericConfig<D, F = F>>(self) -> (CircuitData<F, C, D>, MatchTargets) {
+        (self.circuit_builder.build::<C>(), self.targets)
+    }
+}
+
+impl MatchTargets {
+    /// Poseidon structured hashes of intents A and B, computed from their contents.
+    pub fn structured_hashes(&self) -> [HashOutTarget; 2] {
+        [
+            self.intent_a_hash_targets.structured_hash,
+            self.intent_b_hash_targets.structured_hash,
+        ]
+    }
+
+    /// Nullifiers of intents A and B.
+    pub fn nullifiers(&self) -> [HashOutTarget; 2] {
+        [
+            self.intent_a_nullifier_targets.nullifier,
+            self.intent_b_nullifier_targets.nullifier,
+        ]
+    }
+
+    /// Poseidon hashes of the public keys of intents A and B.
+    pub fn public_key_hashes(&self) -> [HashOutTarget; 2] {
+        [
+            self.intent_a_nullifier_targets.public_key_hash,
+            self.intent_b_nullifier_targets.public_key_hash,
+        ]
+    }
+
+    /// Amount swapped by intent A, in token A.
+    pub fn token_a_amount(&self) -> &AmountTargets {
+        &self.token_a_amount_targets
+    }
+
+    /// Amount swapped by intent B, in token B, for the token A amount of intent A.
+    pub fn token_b_amount(&self) -> &AmountTargets {
+        &self.token_b_amount_targets
+    }
+}
+
+/// Builds the circuit proving a single match, whose public inputs are the batch timestamp,
+/// followed by the nullifiers of intents A and B.
+pub fn generate_match_circuit<F, const D: usize>() -> MatchCircuitData<F, D>
 where
     F: RichField + Extendable<D>,
 {
     let mut circuit_builder =
         CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_zk_config());
 
+    let batch_timestamp_targets = add_virtual_timestamp_targets(&mut circuit_builder);
+    register_batch_timestamp(&mut circuit_builder, &batch_timestamp_targets);
+    let targets = add_match_targets(&mut circuit_builder, &batch_timestamp_targets);
+    targets
+        .nullifiers()
+        .iter()
+        .for_each(|nullifier| circuit_builder.register_public_inputs(&nullifier.elements));
+
+    MatchCircuitData {
+        circuit_builder,
+        targets,
+    }
+}
+
+/// Adds the constraints of a match of two intents, in a batch sealed at
+/// `batch_timestamp_targets`, without registering any public input.
+pub fn add_match_targets<F, const D: usize>(
+    circuit_builder: &mut CircuitBuilder<F, D>,
+    batch_timestamp_targets: &TimestampTargets,
+) -> MatchTargets
+where
+    F: RichField + Extendable<D>,
+{
+    // 0. Hash both intents from their contents, which signatures and nullifiers are
+    //    computed over (see `intent_hash`).
+    let intent_a_hash_targets = add_intent_hash_targets(circuit_builder);
+    let intent_b_hash_targets = add_intent_hash_targets(circuit_builder);
+    let intent_a_public_key_targets = add_public_key_targets(circuit_builder);
+    let intent_b_public_key_targets = add_public_key_targets(circuit_builder);
+
     // 1. Verify that both intents have appropriate token addresses
-    let intent_a_quote_token_targets = circuit_builder.add_virtual_u32_targets(4);
-    let intent_b_base_token_targets = circuit_builder.add_virtual_u32_targets(4);
+    let intent_a_quote_token_targets = intent_a_hash_targets.quote_token;
+    let intent_b_base_token_targets = intent_b_hash_targets.base_token;
 
-    (0..4).for_each(|i| {
-        circuit_builder.connect_u32(
+    (0..TOKEN_ADDRESS_LIMBS).for_each(|i| {
+        circuit_builder.connect(
             intent_a_quote_token_targets[i],
             intent_b_base_token_targets[i],
         )
     });
 
-    let intent_a_base_token_targets = circuit_builder.add_virtual_u32_targets(4);
-    let intent_b_quote_token_targets = circuit_builder.add_virtual_u32_targets(4);
+    let intent_a_base_token_targets = intent_a_hash_targets.base_token;
+    let intent_b_quote_token_targets = intent_b_hash_targets.quote_token;
 
     intent_a_base_token_targets
         .iter()
         .zip(intent_b_quote_token_targets)
-        .for_each(|(a, b)| circuit_builder.connect_u32(*a, b));
-
-    // 2. Verify that the amount being swapped does not exceed the desired one, for each intent.
-    let intent_a_quote_amount_targets = circuit_builder.add_virtual_u32_targets(
-        match_instance
-            .intent_a()
-            .inputs
-            .quote_amount
-            .to_u32_digits()
-            .len(),
+        .for_each(|(a, b)| circuit_builder.connect(*a, b));
+
+    // 2. Verify that the amount being swapped does not exceed the desired one, for each intent,
+    //    and that each intent receives at least its minimum base token amount. Intent A swaps
+    //    the token A amount for the token B amount, and conversely for intent B.
+    let token_a_amount_targets = add_virtual_amount_targets(circuit_builder);
+    let token_b_amount_targets = add_virtual_amount_targets(circuit_builder);
+
+    for (hash_targets, swapped_amount_targets, received_amount_targets) in [
+        (
+            &intent_a_hash_targets,
+            &token_a_amount_targets,
+            &token_b_amount_targets,
+        ),
+        (
+            &intent_b_hash_targets,
+            &token_b_amount_targets,
+            &token_a_amount_targets,
+        ),
+    ] {
+        let quote_amount_targets =
+            amount_from_be_bytes(circuit_builder, &hash_targets.quote_amount);
+        assert_amount_le(
+            circuit_builder,
+            swapped_amount_targets,
+            &quote_amount_targets,
+        );
+        let min_base_token_amount_targets =
+            amount_from_be_bytes(circuit_builder, &hash_targets.min_base_token_amount);
+        assert_amount_le(
+            circuit_builder,
+            &min_base_token_amount_targets,
+            received_amount_targets,
+        );
+    }
+
+    // 3. Verify that both intents have been signed by their owners, over their structured hash.
+    let intent_a_signature_targets = add_intent_signature_verification(
+        circuit_builder,
+        intent_a_hash_targets.structured_hash,
+        &intent_a_public_key_targets,
     );
-    let intent_b_quote_amount_targets = circuit_builder.add_virtual_u32_targets(
-        match_instance
-            .intent_b()
-            .inputs
-            .quote_amount
-            .to_u32_digits()
-            .len(),
+    let intent_b_signature_targets = add_intent_signature_verification(
+        circuit_builder,
+        intent_b_hash_targets.structured_hash,
+        &intent_b_public_key_targets,
     );
 
-    let match_intent_a_amount_targets = circuit_builder.add_virtual_u32_targets(
-        match_instance
-            .swapped_amount()
-            .token_a_amount()
-            .to_u32_digits()
-            .len(),
+    // 4. Verify that both intents expire strictly after the batch was sealed. The batch
+    //    timestamp is a public input, so verifiers can check it against the sealed batch.
+    //    Expiry dates are part of the signed structured hashes.
+    assert_not_expired(
+        circuit_builder,
+        &intent_a_hash_targets.expiry_date,
+        batch_timestamp_targets,
     );
-    let match_intent_b_amount_targets = circuit_builder.add_virtual_u32_targets(
-        match_instance
-            .swapped_amount()
-            .token_b_amount()
-            .to_u32_digits()
-            .len(),
+    assert_not_expired(
+        circuit_builder,
+        &intent_b_hash_targets.expiry_date,
+        batch_timestamp_targets,
     );
-    // TODO: check for the inequalities
 
-    todo!()
+    // 5. Compute the nullifiers of both intents, output by the circuits, so that the service
+    //    can reject any intent that was already settled in a previous batch. Nullifiers are
+    //    computed over the structured hash of the intent contents, and over the signer
+    //    public key.
+    let intent_a_nullifier_targets = add_nullifier_targets(
+        circuit_builder,
+        intent_a_hash_targets.structured_hash,
+        &intent_a_public_key_targets,
+    );
+    let intent_b_nullifier_targets = add_nullifier_targets(
+        circuit_builder,
+        intent_b_hash_targets.structured_hash,
+        &intent_b_public_key_targets,
+    );
+
+    MatchTargets {
+        intent_a_hash_targets,
+        intent_b_hash_targets,
+        intent_a_public_key_targets,
+        intent_b_public_key_targets,
+        intent_a_quote_token_targets,
+        intent_b_base_token_targets,
+        intent_a_base_token_targets,
+        intent_b_quote_token_targets,
+        token_a_amount_targets,
+        token_b_amount_targets,
+        intent_a_signature_targets,
+        intent_b_signature_targets,
+        batch_timestamp_targets: *batch_timestamp_targets,
+        intent_a_nullifier_targets,
+        intent_b_nullifier_targets,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MatchCircuitError {
+    /// A swapped amount does not fit in `AMOUNT_LIMBS` limbs
+    AmountOutOfRange,
+    /// The batch timestamp, or an expiry date, is prior to the unix epoch
+    TimestampOutOfRange,
+    Signature(SignatureCircuitError),
+}
+
+impl From<AmountOutOfRange> for MatchCircuitError {
+    fn from(_: AmountOutOfRange) -> Self {
+        Self::AmountOutOfRange
+    }
+}
+
+impl From<TimestampOutOfRange> for MatchCircuitError {
+    fn from(_: TimestampOutOfRange) -> Self {
+        Self::TimestampOutOfRange
+    }
+}
+
+impl From<SignatureCircuitError> for MatchCircuitError {
+    fn from(e: SignatureCircuitError) -> Self {
+        Self::Signature(e)
+    }
+}
+
+/// Sets the witness of the match circuit targets, for `match_instance` in a batch sealed at
+/// `batch_timestamp`. Token addresses, expiry dates, messages and nullifiers are derived from
+/// the intents.
+pub fn set_match_targets<F, const D: usize>(
+    partial_witness: &mut PartialWitness<F>,
+    match_instance: &Match,
+    batch_timestamp: &NaiveDateTime,
+    targets: &MatchTargets,
+) -> Result<(), MatchCircuitError>
+where
+    F: RichField + Extendable<D>,
+{
+    set_timestamp_targets(
+        partial_witness,
+        batch_timestamp,
+        &targets.batch_timestamp_targets,
+    )?;
+    let swapped_amount = match_instance.swapped_amount();
+    set_amount_targets(
+        partial_witness,
+        swapped_amount.token_a_amount(),
+        &targets.token_a_amount_targets,
+    )?;
+    set_amount_targets(
+        partial_witness,
+        swapped_amount.token_b_amount(),
+        &targets.token_b_amount_targets,
+    )?;
+    for (intent, hash_targets, public_key_targets, signature_targets) in [
+        (
+            match_instance.intent_a(),
+            &targets.intent_a_hash_targets,
+            &targets.intent_a_public_key_targets,
+            &targets.intent_a_signature_targets,
+        ),
+        (
+            match_instance.intent_b(),
+            &targets.intent_b_hash_targets,
+            &targets.intent_b_public_key_targets,
+            &targets.intent_b_signature_targets,
+        ),
+    ] {
+        set_intent_hash_targets(partial_witness, intent, hash_targets)?;
+        set_public_key_targets(partial_witness, intent, public_key_targets);
+        set_intent_signature_targets::<F, D>(partial_witness, intent, signature_targets)?;
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "ecdsa"))]
+mod tests {
+    use super::*;
+    use crate::signature::sign_intent;
+    use chrono::NaiveDate;
+    use num_bigint::BigUint;
+    use plonky2::{
+        field::{secp256k1_scalar::Secp256K1Scalar, types::Field},
+        plonk::config::PoseidonGoldilocksConfig,
+    };
+    use plonky2_ecdsa::curve::ecdsa::ECDSASecretKey;
+    use solina::{
+        intent::{Intent, IntentConstraints, IntentInputs, TradeDirection},
+        solver::SwappedAmount,
+        Signature,
+    };
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    fn date(day: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2023, 11, day)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+    }
+
+    fn signed_intent(secret_key: u64, tokens: (u8, u8), expiry_day: u32) -> Intent {
+        let mut intent = Intent::new(
+            [0u8; 32],
+            IntentInputs::new(
+                [tokens.0; 32],
+                [tokens.1; 32],
+                BigUint::from(1_000_u64),
+                TradeDirection::Sell,
+            ),
+            IntentConstraints::new(BigUint::from(900_u64)),
+            Signature([0u8; 64]),
+            date(expiry_day),
+        );
+        sign_intent(
+            &mut intent,
+            ECDSASecretKey(Secp256K1Scalar::from_canonical_u64(secret_key)),
+        );
+        intent
+    }
+
+    /// A match of two intents quoting 1000 tokens, for at least 900 base tokens.
+    fn match_instance(intent_a_expiry_day: u32, swapped_amount: (u64, u64)) -> Match {
+        Match::new(
+            signed_intent(1, (1, 2), intent_a_expiry_day),
+            signed_intent(2, (2, 1), 28),
+            SwappedAmount::new(
+                BigUint::from(swapped_amount.0),
+                BigUint::from(swapped_amount.1),
+            ),
+        )
+    }
+
+    /// Proves `match_instance`, in a batch sealed at `batch_timestamp`.
+    fn prove_match(match_instance: Match, batch_timestamp: NaiveDateTime) {
+        let (circuit_data, targets) = generate_match_circuit::<F, D>().build::<C>();
+
+        let mut partial_witness = PartialWitness::new();
+        set_match_targets::<F, D>(
+            &mut partial_witness,
+            &match_instance,
+            &batch_timestamp,
+            &targets,
+        )
+        .unwrap();
+        let proof = circuit_data.prove(partial_witness).unwrap();
+        circuit_data.verify(proof).unwrap();
+    }
+
+    #[test]
+    fn it_works_match_circuit() {
+        prove_match(match_instance(20, (1_000, 1_000)), date(10));
+        prove_match(match_instance(20, (950, 900)), date(10));
+    }
+
+    #[test]
+    #[should_panic]
+    fn it_rejects_swapped_amounts_exceeding_the_quote_amount() {
+        prove_match(match_instance(20, (1_001, 1_000)), date(10));
+    }
+
+    #[test]
+    #[should_panic]
+    fn it_rejects_received_amounts_below_the_minimum_base_token_amount() {
+        prove_match(match_instance(20, (1_000, 899)), date(10));
+    }
+
+    #[test]
+    #[should_panic]
+    fn it_rejects_expired_intents() {
+        prove_match(match_instance(20, (1_000, 1_000)), date(20));
+    }
+
+    #[test]
+    fn it_rejects_timestamps_prior_to_the_unix_epoch() {
+        let (_, targets) = generate_match_circuit::<F, D>().build::<C>();
+        let pre_epoch = NaiveDate::from_ymd_opt(1969, 12, 31)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+        assert_eq!(
+            set_match_targets::<F, D>(
+                &mut PartialWitness::new(),
+                &match_instance(20, (1_000, 1_000)),
+                &pre_epoch,
+                &targets,
+            ),
+            Err(MatchCircuitError::TimestampOutOfRange)
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn it_rejects_expiry_dates_other_than_the_signed_one() {
+        // intent A was signed to expire before the batch was sealed
+        let match_instance = match_instance(20, (1_000, 1_000));
+        let batch_timestamp = date(25);
+        let (circuit_data, targets) = generate_match_circuit::<F, D>().build::<C>();
+
+        // the prover witnesses a later expiry date, along with the signature of intent A
+        let mut extended_intent = match_instance.intent_a().clone();
+        extended_intent.expiry_date = date(30);
+        let mut partial_witness = PartialWitness::new();
+        set_timestamp_targets(
+            &mut partial_witness,
+            &batch_timestamp,
+            &targets.batch_timestamp_targets,
+        )
+        .unwrap();
+        set_intent_hash_targets(
+            &mut partial_witness,
+            &extended_intent,
+            &targets.intent_a_hash_targets,
+        )
+        .unwrap();
+        set_public_key_targets(
+            &mut partial_witness,
+            &extended_intent,
+            &targets.intent_a_public_key_targets,
+        );
+        set_intent_signature_targets::<F, D>(
+            &mut partial_witness,
+            match_instance.intent_a(),
+            &targets.intent_a_signature_targets,
+        )
+        .unwrap();
+        set_amount_targets(
+            &mut partial_witness,
+            match_instance.swapped_amount().token_a_amount(),
+            &targets.token_a_amount_targets,
+        )
+        .unwrap();
+        set_amount_targets(
+            &mut partial_witness,
+            match_instance.swapped_amount().token_b_amount(),
+            &targets.token_b_amount_targets,
+        )
+        .unwrap();
+        let intent_b = match_instance.intent_b();
+        set_intent_hash_targets(
+            &mut partial_witness,
+            intent_b,
+            &targets.intent_b_hash_targets,
+        )
+        .unwrap();
+        set_public_key_targets(
+            &mut partial_witness,
+            intent_b,
+            &targets.intent_b_public_key_targets,
+        );
+        set_intent_signature_targets::<F, D>(
+            &mut partial_witness,
+            intent_b,
+            &targets.intent_b_signature_targets,
+        )
+        .unwrap();
+
+        circuit_data.prove(partial_witness).unwrap();
+    }
 }
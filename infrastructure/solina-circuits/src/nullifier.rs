@@ -0,0 +1,136 @@
+// NULLIFIER_DESIGN:
+//
+// 1. The nullifier of an intent is the Poseidon hash of its Poseidon structured hash and of
+//    its signer public key, matching `Intent::nullifier` natively.
+// 2. Nullifiers are public outputs of the circuits, registered by each circuit in its own
+//    layout (see `match_circuit` and `batch_circuit`). The Solina service keeps the set of
+//    spent nullifiers, and rejects any proof settling an intent whose nullifier was already
+//    spent. This enforces the uniqueness of intents across batches.
+// 3. The structured hash is computed in circuit from the intent contents (see `intent_hash`),
+//    and the public key limbs are those the intent signature is verified against (see
+//    `signature`), so that nullifiers are bound to the signed intent.
+// 4. Public keys are laid out as eight big endian u32 limbs, as in `Poseidon::hash_bytes`.
+use crate::intent_hash::{add_virtual_limbs, set_limb_targets, two_to_one_circuit};
+use plonky2::{
+    field::extension::Extendable,
+    hash::{
+        hash_types::{HashOutTarget, RichField},
+        poseidon::PoseidonHash,
+    },
+    iop::{target::Target, witness::PartialWitness},
+    plonk::circuit_builder::CircuitBuilder,
+};
+use solina::intent::Intent;
+
+pub const PUBLIC_KEY_LIMBS: usize = 8;
+
+pub struct NullifierTargets {
+    pub nullifier: HashOutTarget,
+    /// Poseidon hash of the public key limbs, as in `Poseidon::hash_bytes`.
+    pub public_key_hash: HashOutTarget,
+}
+
+/// Adds the (range checked) limbs of an intent public key.
+pub fn add_public_key_targets<F, const D: usize>(
+    circuit_builder: &mut CircuitBuilder<F, D>,
+) -> Vec<Target>
+where
+    F: RichField + Extendable<D>,
+{
+    add_virtual_limbs(circuit_builder, PUBLIC_KEY_LIMBS, 32)
+}
+
+pub fn set_public_key_targets<F: RichField>(
+    partial_witness: &mut PartialWitness<F>,
+    intent: &Intent,
+    targets: &[Target],
+) {
+    set_limb_targets(partial_witness, &intent.public_key(), targets)
+}
+
+/// Computes the nullifier of the intent with `structured_hash`, signed by `public_key`, in
+/// circuit.
+pub fn add_nullifier_targets<F, const D: usize>(
+    circuit_builder: &mut CircuitBuilder<F, D>,
+    structured_hash: HashOutTarget,
+    public_key: &[Target],
+) -> NullifierTargets
+where
+    F: RichField + Extendable<D>,
+{
+    let public_key_hash =
+        circuit_builder.hash_n_to_hash_no_pad::<PoseidonHash>(public_key.to_vec());
+    let nullifier = two_to_one_circuit(circuit_builder, structured_hash, public_key_hash);
+
+    NullifierTargets {
+        nullifier,
+        public_key_hash,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intent_hash::{add_intent_hash_targets, set_intent_hash_targets};
+    use chrono::NaiveDate;
+    use num_bigint::BigUint;
+    use plonky2::{
+        field::types::Field,
+        plonk::{
+            circuit_data::CircuitConfig,
+            config::{GenericConfig, PoseidonGoldilocksConfig},
+        },
+    };
+    use solina::{
+        intent::{IntentConstraints, IntentInputs, TradeDirection},
+        Signature,
+    };
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    #[test]
+    fn it_works_native_and_circuit_nullifier_equivalence() {
+        let intent = Intent::new(
+            [3u8; 32],
+            IntentInputs::new(
+                [1u8; 32],
+                [2u8; 32],
+                BigUint::from(1_000_u64),
+                TradeDirection::Sell,
+            ),
+            IntentConstraints::new(BigUint::from(64_u8)),
+            Signature([0u8; 64]),
+            NaiveDate::from_ymd_opt(2023, 11, 10)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap(),
+        );
+
+        let mut circuit_builder =
+            CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let hash_targets = add_intent_hash_targets(&mut circuit_builder);
+        let public_key_targets = add_public_key_targets(&mut circuit_builder);
+        let nullifier_targets = add_nullifier_targets(
+            &mut circuit_builder,
+            hash_targets.structured_hash,
+            &public_key_targets,
+        );
+        circuit_builder.register_public_inputs(&nullifier_targets.nullifier.elements);
+        let circuit_data = circuit_builder.build::<C>();
+
+        let mut partial_witness = PartialWitness::new();
+        set_intent_hash_targets(&mut partial_witness, &intent, &hash_targets).unwrap();
+        set_public_key_targets(&mut partial_witness, &intent, &public_key_targets);
+        let proof = circuit_data.prove(partial_witness).unwrap();
+        circuit_data.verify(proof.clone()).unwrap();
+
+        let nullifier = intent
+            .nullifier()
+            .chunks(8)
+            .map(|limb| F::from_canonical_u64(u64::from_be_bytes(limb.try_into().unwrap())))
+            .collect::<Vec<_>>();
+        assert_eq!(proof.public_inputs, nullifier);
+    }
+}
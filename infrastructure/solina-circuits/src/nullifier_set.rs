@@ -0,0 +1,328 @@
+// NULLIFIER_SET_DESIGN:
+//
+// 1. The set of spent nullifiers is a sparse Merkle tree of depth `NULLIFIER_SET_DEPTH`. The
+//    leaf of a nullifier is indexed by its first element, in little endian bit order from the
+//    leaves up, and holds either the nullifier or, when empty, the zero hash.
+// 2. Inner nodes are the Poseidon hash of their two children, so the root of the empty set is
+//    that of a tree of zero hashes (see `NullifierSet::empty_root`).
+// 3. Inserting a nullifier proves, from the siblings of its leaf, that the leaf is empty
+//    under the current root, then recomputes the root with the leaf set to the nullifier.
+//    Spending a nullifier twice, or two nullifiers sharing a leaf, fails the first check.
+// 4. A nullifier element is less than the field order but its 64 bits are only constrained
+//    modulo the field order by `split_le`. The bits are also constrained to be those of the
+//    canonical value, otherwise a nullifier could be inserted a second time at another leaf.
+// 5. Zero nullifiers pad the unused match slots of a batch, and leave the set unchanged.
+use crate::verifier::F;
+use plonky2::{
+    field::{
+        extension::Extendable,
+        types::{Field, PrimeField64},
+    },
+    hash::{
+        hash_types::{HashOut, HashOutTarget, RichField},
+        poseidon::PoseidonHash,
+    },
+    iop::{
+        target::{BoolTarget, Target},
+        witness::{PartialWitness, WitnessWrite},
+    },
+    plonk::{circuit_builder::CircuitBuilder, config::Hasher},
+};
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Depth of the nullifier set, one level per bit of a nullifier first element.
+pub const NULLIFIER_SET_DEPTH: usize = 64;
+
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum NullifierSetError {
+    #[error("Spent nullifier: `{0:?}`")]
+    SpentNullifier(HashOut<F>),
+}
+
+/// The set of spent nullifiers, as a sparse Merkle tree.
+#[derive(Clone, Debug)]
+pub struct NullifierSet {
+    /// Non empty nodes, by level (0 for the leaves) and index in their level.
+    nodes: HashMap<(usize, u64), HashOut<F>>,
+    /// Root of an empty subtree, by level.
+    empty_nodes: Vec<HashOut<F>>,
+}
+
+impl Default for NullifierSet {
+    fn default() -> Self {
+        let mut empty_nodes = vec![HashOut::ZERO];
+        for level in 0..NULLIFIER_SET_DEPTH {
+            empty_nodes.push(hash_nodes(empty_nodes[level], empty_nodes[level]));
+        }
+
+        Self {
+            nodes: HashMap::new(),
+            empty_nodes,
+        }
+    }
+}
+
+impl NullifierSet {
+    pub fn empty_root() -> HashOut<F> {
+        Self::default().root()
+    }
+
+    pub fn root(&self) -> HashOut<F> {
+        self.node(NULLIFIER_SET_DEPTH, 0)
+    }
+
+    pub fn contains(&self, nullifier: &HashOut<F>) -> bool {
+        self.nodes.get(&(0, leaf_index(nullifier))) == Some(nullifier)
+    }
+
+    /// Siblings of the leaf of `nullifier`, from the leaves up.
+    pub fn siblings(&self, nullifier: &HashOut<F>) -> Vec<HashOut<F>> {
+        let mut index = leaf_index(nullifier);
+        (0..NULLIFIER_SET_DEPTH)
+            .map(|level| {
+                let sibling = self.node(level, index ^ 1);
+                index >>= 1;
+                sibling
+            })
+            .collect()
+    }
+
+    /// Inserts `nullifier`, returning the siblings of its (empty) leaf before the insertion,
+    /// which witness that it was not spent.
+    pub fn insert(&mut self, nullifier: HashOut<F>) -> Result<Vec<HashOut<F>>, NullifierSetError> {
+        let mut index = leaf_index(&nullifier);
+        if self.nodes.contains_key(&(0, index)) {
+            return Err(NullifierSetError::SpentNullifier(nullifier));
+        }
+
+        let siblings = self.siblings(&nullifier);
+        let mut node = nullifier;
+        for (level, sibling) in siblings.iter().enumerate() {
+            self.nodes.insert((level, index), node);
+            node = if index & 1 == 0 {
+                hash_nodes(node, *sibling)
+            } else {
+                hash_nodes(*sibling, node)
+            };
+            index >>= 1;
+        }
+        self.nodes.insert((NULLIFIER_SET_DEPTH, 0), node);
+
+        Ok(siblings)
+    }
+
+    fn node(&self, level: usize, index: u64) -> HashOut<F> {
+        self.nodes
+            .get(&(level, index))
+            .copied()
+            .unwrap_or(self.empty_nodes[level])
+    }
+}
+
+fn leaf_index(nullifier: &HashOut<F>) -> u64 {
+    nullifier.elements[0].to_canonical_u64()
+}
+
+fn hash_nodes(left: HashOut<F>, right: HashOut<F>) -> HashOut<F> {
+    PoseidonHash::hash_no_pad(&[left.elements, right.elements].concat())
+}
+
+pub struct NullifierInsertionTargets {
+    pub siblings: Vec<HashOutTarget>,
+    /// Root of the set once the nullifier is inserted.
+    pub new_root: HashOutTarget,
+}
+
+/// Inserts `nullifier` into the set with root `root` in circuit, constraining its leaf to be
+/// empty, unless `nullifier` is zero.
+pub fn add_nullifier_insertion_targets<F, const D: usize>(
+    circuit_builder: &mut CircuitBuilder<F, D>,
+    root: HashOutTarget,
+    nullifier: HashOutTarget,
+) -> NullifierInsertionTargets
+where
+    F: RichField + Extendable<D>,
+{
+    let siblings = (0..NULLIFIER_SET_DEPTH)
+        .map(|_| circuit_builder.add_virtual_hash())
+        .collect::<Vec<_>>();
+    let index_bits = add_leaf_index_bits(circuit_builder, nullifier.elements[0]);
+
+    let empty_leaf = circuit_builder.constant_hash(HashOut::ZERO);
+    let old_root = add_merkle_root_targets(circuit_builder, empty_leaf, &index_bits, &siblings);
+    let new_root = add_merkle_root_targets(circuit_builder, nullifier, &index_bits, &siblings);
+
+    let zero = circuit_builder.zero();
+    let mut is_padding = circuit_builder._true();
+    for element in nullifier.elements {
+        let is_zero = circuit_builder.is_equal(element, zero);
+        is_padding = circuit_builder.and(is_padding, is_zero);
+    }
+    let checked_root = circuit_builder.select_hash(is_padding, root, old_root);
+    circuit_builder.connect_hashes(checked_root, root);
+    let new_root = circuit_builder.select_hash(is_padding, root, new_root);
+
+    NullifierInsertionTargets { siblings, new_root }
+}
+
+pub fn set_nullifier_insertion_targets<F: RichField>(
+    partial_witness: &mut PartialWitness<F>,
+    siblings: &[HashOut<F>],
+    targets: &NullifierInsertionTargets,
+) {
+    targets
+        .siblings
+        .iter()
+        .zip(siblings)
+        .for_each(|(target, sibling)| partial_witness.set_hash_target(*target, *sibling));
+}
+
+/// Little endian bits of the canonical value of `element`.
+fn add_leaf_index_bits<F, const D: usize>(
+    circuit_builder: &mut CircuitBuilder<F, D>,
+    element: Target,
+) -> Vec<BoolTarget>
+where
+    F: RichField + Extendable<D>,
+{
+    let bits = circuit_builder.split_le(element, NULLIFIER_SET_DEPTH);
+
+    // canonical values with their high limb equal to 2^32 - 1 have a zero low limb
+    let low = circuit_builder.le_sum(bits[..32].iter());
+    let high = circuit_builder.le_sum(bits[32..].iter());
+    let max_high = circuit_builder.constant(F::from_canonical_u32(u32::MAX));
+    let is_max_high = circuit_builder.is_equal(high, max_high);
+    let non_canonical = circuit_builder.mul(is_max_high.target, low);
+    circuit_builder.assert_zero(non_canonical);
+
+    bits
+}
+
+fn add_merkle_root_targets<F, const D: usize>(
+    circuit_builder: &mut CircuitBuilder<F, D>,
+    leaf: HashOutTarget,
+    index_bits: &[BoolTarget],
+    siblings: &[HashOutTarget],
+) -> HashOutTarget
+where
+    F: RichField + Extendable<D>,
+{
+    index_bits
+        .iter()
+        .zip(siblings)
+        .fold(leaf, |node, (is_right, sibling)| {
+            let left = circuit_builder.select_hash(*is_right, *sibling, node);
+            let right = circuit_builder.select_hash(*is_right, node, *sibling);
+            circuit_builder
+                .hash_n_to_hash_no_pad::<PoseidonHash>([left.elements, right.elements].concat())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::verifier::{C, D};
+    use plonky2::plonk::circuit_data::CircuitConfig;
+
+    fn nullifier(first_element: F) -> HashOut<F> {
+        HashOut {
+            elements: [first_element, F::ONE, F::TWO, F::from_canonical_u64(3)],
+        }
+    }
+
+    /// Proves the insertion of `nullifier` into `nullifier_set`, followed by a padding
+    /// nullifier, from the witnessed `siblings`.
+    fn prove_insertion(
+        nullifier_set: &NullifierSet,
+        nullifier: HashOut<F>,
+        siblings: &[HashOut<F>],
+    ) -> HashOut<F> {
+        let mut circuit_builder =
+            CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let root = circuit_builder.add_virtual_hash();
+        let nullifier_target = circuit_builder.add_virtual_hash();
+        let padding_target = circuit_builder.add_virtual_hash();
+        let insertion =
+            add_nullifier_insertion_targets(&mut circuit_builder, root, nullifier_target);
+        let padding = add_nullifier_insertion_targets(
+            &mut circuit_builder,
+            insertion.new_root,
+            padding_target,
+        );
+        circuit_builder.register_public_inputs(&padding.new_root.elements);
+        let circuit_data = circuit_builder.build::<C>();
+
+        let mut partial_witness = PartialWitness::new();
+        partial_witness.set_hash_target(root, nullifier_set.root());
+        partial_witness.set_hash_target(nullifier_target, nullifier);
+        partial_witness.set_hash_target(padding_target, HashOut::ZERO);
+        set_nullifier_insertion_targets(&mut partial_witness, siblings, &insertion);
+        set_nullifier_insertion_targets(
+            &mut partial_witness,
+            &[HashOut::ZERO; NULLIFIER_SET_DEPTH],
+            &padding,
+        );
+        let proof = circuit_data.prove(partial_witness).unwrap();
+        let new_root = HashOut::from_vec(proof.public_inputs.clone());
+        circuit_data.verify(proof).unwrap();
+        new_root
+    }
+
+    #[test]
+    fn it_works_nullifier_set() {
+        let (four, five) = (
+            nullifier(F::from_canonical_u64(4)),
+            nullifier(F::from_canonical_u64(5)),
+        );
+        let mut nullifier_set = NullifierSet::default();
+        assert_eq!(nullifier_set.root(), NullifierSet::empty_root());
+
+        let siblings = nullifier_set.insert(five).unwrap();
+        assert_eq!(siblings.len(), NULLIFIER_SET_DEPTH);
+        assert!(nullifier_set.contains(&five));
+        assert!(!nullifier_set.contains(&four));
+        let root = nullifier_set.root();
+        assert_ne!(root, NullifierSet::empty_root());
+
+        nullifier_set.insert(four).unwrap();
+        assert_eq!(
+            nullifier_set.insert(five),
+            Err(NullifierSetError::SpentNullifier(five))
+        );
+
+        // the root does not depend on the insertion order
+        let mut other_set = NullifierSet::default();
+        other_set.insert(four).unwrap();
+        assert_ne!(other_set.root(), root);
+        other_set.insert(five).unwrap();
+        assert_eq!(other_set.root(), nullifier_set.root());
+    }
+
+    #[test]
+    fn it_works_nullifier_insertion_circuit() {
+        let mut nullifier_set = NullifierSet::default();
+        nullifier_set.insert(nullifier(F::ONE)).unwrap();
+        let previous_set = nullifier_set.clone();
+        // the largest canonical element, whose high limb is 2^32 - 1
+        let siblings = nullifier_set.insert(nullifier(F::NEG_ONE)).unwrap();
+
+        assert_eq!(
+            prove_insertion(&previous_set, nullifier(F::NEG_ONE), &siblings),
+            nullifier_set.root()
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn it_fails_nullifier_insertion_circuit_spent_nullifier() {
+        let mut nullifier_set = NullifierSet::default();
+        nullifier_set.insert(nullifier(F::NEG_ONE)).unwrap();
+
+        prove_insertion(
+            &nullifier_set,
+            nullifier(F::NEG_ONE),
+            &nullifier_set.siblings(&nullifier(F::NEG_ONE)),
+        );
+    }
+}
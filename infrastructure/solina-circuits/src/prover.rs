@@ -0,0 +1,269 @@
+// PROVER_DESIGN:
+//
+// 1. A batch solution can be proven with two stacks: hand written plonky2 circuits (fast
+//    proving, small proofs), or a RISC Zero guest running the `solina` validation code
+//    (slower, but much easier to audit).
+// 2. Both backends implement `SolutionProver`. Proofs are tagged with the backend that
+//    generated them, and are verified through the same interface, against the same
+//    `BatchSolutionPublicInputs`.
+// 3. The plonky2 backend proves a circuit obtained from the `registry`, together with a
+//    witness generator filling its targets from the batch and the solution (see
+//    `batch_circuit::batch_solution_prover`).
+// 4. The RISC Zero backend runs the batch validation guest, which reads a JSON encoded
+//    `BatchValidationInputs` and commits a JSON encoded `BatchValidationJournal`. The
+//    proof is the JSON encoded receipt, and the public inputs are read from its journal.
+use crate::verifier::{
+    verify_batch_solution_proof_with, BatchSolutionPublicInputs, ProofVerificationError, C, D, F,
+};
+use chrono::NaiveDateTime;
+use plonky2::{iop::witness::PartialWitness, plonk::circuit_data::CircuitData};
+use serde::{Deserialize, Serialize};
+use solina::{
+    intent::Intent, price_oracle::PriceSnapshot, solver::BatchSolution,
+    structured_hash::StructuredHash,
+};
+use std::sync::Arc;
+use thiserror::Error;
+
+#[cfg(feature = "risc0")]
+pub use risc0::Risc0Prover;
+
+#[derive(Debug, Error)]
+pub enum SolutionProverError {
+    #[error("Witness Error: `{0}`")]
+    WitnessError(String),
+    #[error("Prover Error: `{0}`")]
+    ProverError(String),
+    #[error("Serialization Error: `{0}`")]
+    SerializationError(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProverBackend {
+    Plonky2,
+    Risc0,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SolutionProof {
+    pub backend: ProverBackend,
+    pub proof: Vec<u8>,
+}
+
+/// Everything a solution proof is generated from.
+pub struct SolutionWitness<'a> {
+    pub intents: &'a [Intent],
+    pub batch_root: StructuredHash,
+    pub batch_timestamp: NaiveDateTime,
+    pub solution: &'a BatchSolution,
+    /// Prices the solution total liquidity is computed with.
+    pub prices: &'a PriceSnapshot,
+}
+
+pub trait SolutionProver {
+    fn backend(&self) -> ProverBackend;
+
+    fn prove(&self, witness: &SolutionWitness) -> Result<SolutionProof, SolutionProverError>;
+
+    fn verify(
+        &self,
+        proof: &SolutionProof,
+        expected_public_inputs: &BatchSolutionPublicInputs,
+    ) -> Result<(), ProofVerificationError>;
+
+    fn check_backend(&self, proof: &SolutionProof) -> Result<(), ProofVerificationError> {
+        if proof.backend != self.backend() {
+            return Err(ProofVerificationError::UnexpectedBackend(format!(
+                "expected {:?}, found {:?}",
+                self.backend(),
+                proof.backend
+            )));
+        }
+        Ok(())
+    }
+}
+
+pub type WitnessGenerator =
+    Box<dyn Fn(&SolutionWitness) -> Result<PartialWitness<F>, SolutionProverError> + Send + Sync>;
+
+pub struct Plonky2Prover {
+    circuit_data: Arc<CircuitData<F, C, D>>,
+    witness_generator: WitnessGenerator,
+}
+
+impl Plonky2Prover {
+    pub fn new(
+        circuit_data: Arc<CircuitData<F, C, D>>,
+        witness_generator: WitnessGenerator,
+    ) -> Self {
+        Self {
+            circuit_data,
+            witness_generator,
+        }
+    }
+}
+
+impl SolutionProver for Plonky2Prover {
+    fn backend(&self) -> ProverBackend {
+        ProverBackend::Plonky2
+    }
+
+    fn prove(&self, witness: &SolutionWitness) -> Result<SolutionProof, SolutionProverError> {
+        let partial_witness = (self.witness_generator)(witness)?;
+        let proof = self
+            .circuit_data
+            .prove(partial_witness)
+            .map_err(|e| SolutionProverError::ProverError(e.to_string()))?;
+
+        Ok(SolutionProof {
+            backend: self.backend(),
+            proof: proof.to_bytes(),
+        })
+    }
+
+    fn verify(
+        &self,
+        proof: &SolutionProof,
+        expected_public_inputs: &BatchSolutionPublicInputs,
+    ) -> Result<(), ProofVerificationError> {
+        self.check_backend(proof)?;
+        verify_batch_solution_proof_with(
+            &self.circuit_data.verifier_data(),
+            &proof.proof,
+            expected_public_inputs,
+        )
+    }
+}
+
+#[cfg(feature = "risc0")]
+mod risc0 {
+    use super::*;
+    use risc0_zkvm::{
+        serde::{from_slice, to_vec},
+        MethodId, Prover, Receipt,
+    };
+    use solina::batch::{BatchValidationInputs, BatchValidationJournal};
+
+    pub struct Risc0Prover {
+        guest_elf: Vec<u8>,
+        method_id: Vec<u8>,
+    }
+
+    impl Risc0Prover {
+        pub fn new(guest_elf: Vec<u8>) -> Result<Self, SolutionProverError> {
+            let method_id = MethodId::compute(&guest_elf)
+                .map_err(|e| SolutionProverError::ProverError(e.to_string()))?
+                .as_slice()
+                .to_vec();
+            Ok(Self {
+                guest_elf,
+                method_id,
+            })
+        }
+
+        pub fn method_id(&self) -> &[u8] {
+            &self.method_id
+        }
+
+        fn journal(receipt: &Receipt) -> Result<BatchValidationJournal, ProofVerificationError> {
+            let journal: String = from_slice(&receipt.journal)
+                .map_err(|e| ProofVerificationError::MalformedProof(e.to_string()))?;
+            serde_json::from_str(&journal)
+                .map_err(|e| ProofVerificationError::MalformedProof(e.to_string()))
+        }
+    }
+
+    impl SolutionProver for Risc0Prover {
+        fn backend(&self) -> ProverBackend {
+            ProverBackend::Risc0
+        }
+
+        fn prove(&self, witness: &SolutionWitness) -> Result<SolutionProof, SolutionProverError> {
+            let inputs = serde_json::to_string(&BatchValidationInputs {
+                intents: witness.intents.to_vec(),
+                batch_root: witness.batch_root,
+                batch_timestamp: witness.batch_timestamp,
+                solution: witness.solution.clone(),
+                prices: witness.prices.clone(),
+            })
+            .map_err(|e| SolutionProverError::SerializationError(e.to_string()))?;
+
+            let mut prover = Prover::new(&self.guest_elf, &self.method_id)
+                .map_err(|e| SolutionProverError::ProverError(e.to_string()))?;
+            prover.add_input_u32_slice(
+                &to_vec(&inputs)
+                    .map_err(|e| SolutionProverError::SerializationError(e.to_string()))?,
+            );
+            let receipt = prover
+                .run()
+                .map_err(|e| SolutionProverError::ProverError(e.to_string()))?;
+
+            Ok(SolutionProof {
+                backend: self.backend(),
+                proof: serde_json::to_vec(&receipt)
+                    .map_err(|e| SolutionProverError::SerializationError(e.to_string()))?,
+            })
+        }
+
+        fn verify(
+            &self,
+            proof: &SolutionProof,
+            expected_public_inputs: &BatchSolutionPublicInputs,
+        ) -> Result<(), ProofVerificationError> {
+            self.check_backend(proof)?;
+            let receipt: Receipt = serde_json::from_slice(&proof.proof)
+                .map_err(|e| ProofVerificationError::MalformedProof(e.to_string()))?;
+            receipt
+                .verify(&self.method_id)
+                .map_err(|e| ProofVerificationError::InvalidProof(e.to_string()))?;
+
+            let journal = Self::journal(&receipt)?;
+            if !journal.is_valid {
+                return Err(ProofVerificationError::InvalidProof(
+                    "the guest rejected the solution".to_string(),
+                ));
+            }
+            for (name, matches) in [
+                (
+                    "batch root",
+                    journal.batch_root == expected_public_inputs.batch_root,
+                ),
+                (
+                    "batch timestamp",
+                    journal.batch_timestamp == expected_public_inputs.batch_timestamp,
+                ),
+                ("score", journal.score == expected_public_inputs.score),
+                (
+                    "settlements",
+                    journal.settlements == expected_public_inputs.settlements,
+                ),
+                (
+                    "nullifiers",
+                    journal.nullifiers == expected_public_inputs.nullifiers,
+                ),
+            ] {
+                if !matches {
+                    return Err(ProofVerificationError::PublicInputMismatch(
+                        name.to_string(),
+                    ));
+                }
+            }
+
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn it_rejects_invalid_guest_elfs() {
+            assert!(matches!(
+                Risc0Prover::new(vec![0u8; 64]),
+                Err(SolutionProverError::ProverError(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,544 @@
+// REGISTRY_DESIGN:
+//
+// 1. Circuit generation is expensive, and solvers and verifiers must agree on the exact same
+//    circuit. The registry builds each circuit once per shape, and caches it in memory and
+//    on disk.
+// 2. A shape is everything the structure of a circuit depends on: its name, its parameters
+//    (number of limbs, of matches, ...) and the version of this crate.
+// 3. Artifacts are stored under `<artifacts_dir>/<shape id>/`:
+//      - `common_data.bin`: serialized `CommonCircuitData`,
+//      - `verifier_only_data.bin`: serialized `VerifierOnlyCircuitData`,
+//      - `prover_only_data.bin`: serialized `ProverOnlyCircuitData`, if the witness
+//        generators of the circuit are supported by the generator serializer (the u32 and
+//        ECDSA gadgets are not). Otherwise, artifacts are rewritten whenever the circuit is
+//        built, as they cannot be loaded back,
+//      - `verifier_data.bin`: serialized `VerifierCircuitData`, as served by the Solina service,
+//      - `manifest.json`: the shape and its version hash.
+// 4. The version hash is the hex encoded plonky2 circuit digest. Artifacts on disk are
+//    loaded first, provided their manifest is that of the requested shape and the digest of
+//    their verifier data matches the manifest version hash. The circuit is only built, and
+//    its artifacts (re)written, when they are missing or inconsistent, since building is the
+//    expensive part. Shape ids include the crate version, which must be bumped whenever the
+//    circuit code changes, so that artifacts of an older circuit are never loaded.
+// 5. Artifacts are exported to the artifacts directory of the Solina service with `export`,
+//    and read back by the service with `read_verifier_artifacts`, which only needs the
+//    manifest and the serialized verifier data.
+use plonky2::{
+    field::extension::Extendable,
+    hash::hash_types::RichField,
+    plonk::{
+        circuit_data::{
+            CircuitData, CommonCircuitData, ProverOnlyCircuitData, VerifierCircuitData,
+            VerifierOnlyCircuitData,
+        },
+        config::{GenericConfig, GenericHashOut},
+    },
+    util::serialization::{GateSerializer, WitnessGeneratorSerializer},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use thiserror::Error;
+
+pub const CIRCUITS_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+pub const COMMON_DATA_FILE: &str = "common_data.bin";
+pub const VERIFIER_ONLY_DATA_FILE: &str = "verifier_only_data.bin";
+pub const PROVER_ONLY_DATA_FILE: &str = "prover_only_data.bin";
+pub const VERIFIER_DATA_FILE: &str = "verifier_data.bin";
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+#[derive(Debug, Error)]
+pub enum CircuitRegistryError {
+    #[error("Io Error: `{0}`")]
+    IoError(#[from] std::io::Error),
+    #[error("Serialization Error: `{0}`")]
+    SerializationError(String),
+    #[error("Version mismatch for circuit `{0}`: expected `{1}`, found `{2}`")]
+    VersionMismatch(String, String, String),
+    #[error("Shape mismatch for circuit `{0}`: found `{1}`")]
+    ShapeMismatch(String, String),
+    #[error("Invalid circuit id: `{0}`")]
+    InvalidCircuitId(String),
+    #[error("Unknown circuit: `{0}`")]
+    UnknownCircuit(String),
+}
+
+/// Everything the structure of a circuit depends on.
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Deserialize, Serialize)]
+pub struct CircuitShape {
+    pub name: String,
+    pub parameters: Vec<usize>,
+}
+
+impl CircuitShape {
+    pub fn new(name: &str, parameters: Vec<usize>) -> Self {
+        Self {
+            name: name.to_string(),
+            parameters,
+        }
+    }
+
+    /// A unique identifier of the shape, used to name its artifacts directory.
+    pub fn id(&self) -> String {
+        let parameters = self
+            .parameters
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<_>>();
+        [
+            self.name.clone(),
+            format!("v{}", CIRCUITS_VERSION),
+            parameters.join("-"),
+        ]
+        .join("_")
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct CircuitManifest {
+    pub shape: CircuitShape,
+    pub version_hash: String,
+}
+
+pub struct CircuitRegistry<F, C, const D: usize>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    artifacts_dir: PathBuf,
+    circuits: HashMap<CircuitShape, Arc<CircuitData<F, C, D>>>,
+    gate_serializer: Box<dyn GateSerializer<F, D>>,
+    generator_serializer: Box<dyn WitnessGeneratorSerializer<F, D>>,
+}
+
+impl<F, C, const D: usize> CircuitRegistry<F, C, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    pub fn new<P: AsRef<Path>>(
+        artifacts_dir: P,
+        gate_serializer: Box<dyn GateSerializer<F, D>>,
+        generator_serializer: Box<dyn WitnessGeneratorSerializer<F, D>>,
+    ) -> Self {
+        Self {
+            artifacts_dir: artifacts_dir.as_ref().to_path_buf(),
+            circuits: HashMap::new(),
+            gate_serializer,
+            generator_serializer,
+        }
+    }
+
+    pub fn artifacts_dir(&self) -> &PathBuf {
+        &self.artifacts_dir
+    }
+
+    /// Returns the circuit for `shape`, from memory, from its artifacts on disk, or built with
+    /// `build`, in which case its artifacts are (re)written.
+    pub fn get_or_build(
+        &mut self,
+        shape: &CircuitShape,
+        build: impl FnOnce() -> CircuitData<F, C, D>,
+    ) -> Result<Arc<CircuitData<F, C, D>>, CircuitRegistryError> {
+        if let Some(circuit_data) = self.circuits.get(shape) {
+            return Ok(circuit_data.clone());
+        }
+
+        let circuit_data = match self.load(shape) {
+            Ok(circuit_data) => circuit_data,
+            Err(_) => {
+                let circuit_data = build();
+                self.store(shape, &circuit_data)?;
+                circuit_data
+            }
+        };
+
+        let circuit_data = Arc::new(circuit_data);
+        self.circuits.insert(shape.clone(), circuit_data.clone());
+        Ok(circuit_data)
+    }
+
+    pub fn verifier_data(&self, shape: &CircuitShape) -> Option<VerifierCircuitData<F, C, D>> {
+        self.circuits.get(shape).map(|c| c.verifier_data())
+    }
+
+    pub fn version_hash(&self, shape: &CircuitShape) -> Option<String> {
+        self.circuits
+            .get(shape)
+            .map(|c| version_hash::<F, C, D>(&c.verifier_only))
+    }
+
+    /// Writes the artifacts of the (already built) circuit for `shape` under `artifacts_dir`,
+    /// and returns the directory they were written to.
+    pub fn export<P: AsRef<Path>>(
+        &self,
+        shape: &CircuitShape,
+        artifacts_dir: P,
+    ) -> Result<PathBuf, CircuitRegistryError> {
+        let circuit_data = self
+            .circuits
+            .get(shape)
+            .ok_or_else(|| CircuitRegistryError::UnknownCircuit(shape.id()))?;
+        let shape_dir = artifacts_dir.as_ref().join(shape.id());
+        self.store_in(&shape_dir, shape, circuit_data)?;
+        Ok(shape_dir)
+    }
+
+    fn shape_dir(&self, shape: &CircuitShape) -> PathBuf {
+        self.artifacts_dir.join(shape.id())
+    }
+
+    /// Loads the artifacts of `shape`, provided they match their manifest.
+    fn load(&self, shape: &CircuitShape) -> Result<CircuitData<F, C, D>, CircuitRegistryError> {
+        let shape_dir = self.shape_dir(shape);
+
+        let manifest = read_manifest(&shape_dir)?;
+        if manifest.shape != *shape {
+            return Err(CircuitRegistryError::ShapeMismatch(
+                shape.id(),
+                manifest.shape.id(),
+            ));
+        }
+
+        let common = CommonCircuitData::from_bytes(
+            fs::read(shape_dir.join(COMMON_DATA_FILE))?,
+            self.gate_serializer.as_ref(),
+        )
+        .map_err(|e| CircuitRegistryError::SerializationError(format!("{:?}", e)))?;
+        let verifier_only =
+            VerifierOnlyCircuitData::from_bytes(fs::read(shape_dir.join(VERIFIER_ONLY_DATA_FILE))?)
+                .map_err(|e| CircuitRegistryError::SerializationError(format!("{:?}", e)))?;
+        let prover_only = ProverOnlyCircuitData::from_bytes(
+            &fs::read(shape_dir.join(PROVER_ONLY_DATA_FILE))?,
+            self.generator_serializer.as_ref(),
+            &common,
+        )
+        .map_err(|e| CircuitRegistryError::SerializationError(format!("{:?}", e)))?;
+
+        let found_version_hash = version_hash::<F, C, D>(&verifier_only);
+        if found_version_hash != manifest.version_hash {
+            return Err(CircuitRegistryError::VersionMismatch(
+                shape.id(),
+                manifest.version_hash,
+                found_version_hash,
+            ));
+        }
+
+        Ok(CircuitData {
+            prover_only,
+            verifier_only,
+            common,
+        })
+    }
+
+    fn store(
+        &self,
+        shape: &CircuitShape,
+        circuit_data: &CircuitData<F, C, D>,
+    ) -> Result<(), CircuitRegistryError> {
+        self.store_in(&self.shape_dir(shape), shape, circuit_data)
+    }
+
+    fn store_in(
+        &self,
+        shape_dir: &Path,
+        shape: &CircuitShape,
+        circuit_data: &CircuitData<F, C, D>,
+    ) -> Result<(), CircuitRegistryError> {
+        fs::create_dir_all(shape_dir)?;
+
+        let common_bytes = circuit_data
+            .common
+            .to_bytes(self.gate_serializer.as_ref())
+            .map_err(|e| CircuitRegistryError::SerializationError(format!("{:?}", e)))?;
+        let verifier_only_bytes = circuit_data
+            .verifier_only
+            .to_bytes()
+            .map_err(|e| CircuitRegistryError::SerializationError(format!("{:?}", e)))?;
+        let prover_only_bytes = circuit_data
+            .prover_only
+            .to_bytes(self.generator_serializer.as_ref(), &circuit_data.common)
+            .ok();
+        let verifier_data_bytes = circuit_data
+            .verifier_data()
+            .to_bytes(self.gate_serializer.as_ref())
+            .map_err(|e| CircuitRegistryError::SerializationError(format!("{:?}", e)))?;
+
+        let manifest = CircuitManifest {
+            shape: shape.clone(),
+            version_hash: version_hash::<F, C, D>(&circuit_data.verifier_only),
+        };
+        let manifest_bytes = serde_json::to_vec_pretty(&manifest)
+            .map_err(|e| CircuitRegistryError::SerializationError(e.to_string()))?;
+
+        fs::write(shape_dir.join(COMMON_DATA_FILE), common_bytes)?;
+        fs::write(shape_dir.join(VERIFIER_ONLY_DATA_FILE), verifier_only_bytes)?;
+        match prover_only_bytes {
+            Some(prover_only_bytes) => {
+                fs::write(shape_dir.join(PROVER_ONLY_DATA_FILE), prover_only_bytes)?
+            }
+            None => match fs::remove_file(shape_dir.join(PROVER_ONLY_DATA_FILE)) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            },
+        }
+        fs::write(shape_dir.join(VERIFIER_DATA_FILE), verifier_data_bytes)?;
+        // the manifest is written last, so that partially written artifacts are never loaded
+        fs::write(shape_dir.join(MANIFEST_FILE), manifest_bytes)?;
+
+        Ok(())
+    }
+}
+
+/// Circuit ids name a directory, which must live directly under the artifacts directory.
+pub fn is_valid_circuit_id(circuit_id: &str) -> bool {
+    !circuit_id.is_empty()
+        && !circuit_id.starts_with('.')
+        && circuit_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+}
+
+fn read_manifest(shape_dir: &Path) -> Result<CircuitManifest, CircuitRegistryError> {
+    serde_json::from_slice(&fs::read(shape_dir.join(MANIFEST_FILE))?)
+        .map_err(|e| CircuitRegistryError::SerializationError(e.to_string()))
+}
+
+/// Reads the manifest and the serialized `VerifierCircuitData` of the circuit `circuit_id`,
+/// exported under `artifacts_dir`.
+pub fn read_verifier_artifacts<P: AsRef<Path>>(
+    artifacts_dir: P,
+    circuit_id: &str,
+) -> Result<(CircuitManifest, Vec<u8>), CircuitRegistryError> {
+    if !is_valid_circuit_id(circuit_id) {
+        return Err(CircuitRegistryError::InvalidCircuitId(
+            circuit_id.to_string(),
+        ));
+    }
+    let shape_dir = artifacts_dir.as_ref().join(circuit_id);
+    let manifest = read_manifest(&shape_dir).map_err(|e| match e {
+        CircuitRegistryError::IoError(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            CircuitRegistryError::UnknownCircuit(circuit_id.to_string())
+        }
+        e => e,
+    })?;
+    let verifier_data = fs::read(shape_dir.join(VERIFIER_DATA_FILE))?;
+    Ok((manifest, verifier_data))
+}
+
+/// Hex encoded circuit digest, which uniquely identifies a circuit.
+pub fn version_hash<F, C, const D: usize>(verifier_only: &VerifierOnlyCircuitData<C, D>) -> String
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    hex::encode(verifier_only.circuit_digest.to_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plonky2::{
+        field::types::Field,
+        plonk::{
+            circuit_builder::CircuitBuilder, circuit_data::CircuitConfig,
+            config::PoseidonGoldilocksConfig,
+        },
+        util::serialization::{DefaultGateSerializer, DefaultGeneratorSerializer},
+    };
+    use std::marker::PhantomData;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    /// Artifacts directory of a test, removed once dropped.
+    struct TestArtifactsDir(PathBuf);
+
+    impl TestArtifactsDir {
+        fn new(name: &str) -> Self {
+            let dir = Self(std::env::temp_dir().join(format!(
+                "solina-registry-{}-{}",
+                name,
+                std::process::id()
+            )));
+            let _ = fs::remove_dir_all(&dir.0);
+            dir
+        }
+    }
+
+    impl Drop for TestArtifactsDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn registry(artifacts_dir: &Path) -> CircuitRegistry<F, C, D> {
+        CircuitRegistry::new(
+            artifacts_dir,
+            Box::new(DefaultGateSerializer),
+            Box::new(DefaultGeneratorSerializer::<C, D> {
+                _phantom: PhantomData,
+            }),
+        )
+    }
+
+    /// Proves knowledge of `x` such that `x * x + constant` is the public input.
+    fn build(constant: u64) -> CircuitData<F, C, D> {
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let x = builder.add_virtual_target();
+        let x_squared = builder.mul(x, x);
+        let constant = builder.constant(F::from_canonical_u64(constant));
+        let y = builder.add(x_squared, constant);
+        builder.register_public_input(y);
+        builder.build::<C>()
+    }
+
+    fn get_or_build(
+        registry: &mut CircuitRegistry<F, C, D>,
+        shape: &CircuitShape,
+        constant: u64,
+    ) -> Arc<CircuitData<F, C, D>> {
+        registry.get_or_build(shape, || build(constant)).unwrap()
+    }
+
+    #[test]
+    fn it_works_manifest_loading() {
+        let artifacts_dir = TestArtifactsDir::new("manifest");
+        let shape = CircuitShape::new("square", vec![1]);
+
+        let circuit_data = get_or_build(&mut registry(&artifacts_dir.0), &shape, 1);
+        let manifest = read_manifest(&artifacts_dir.0.join(shape.id())).unwrap();
+        assert_eq!(
+            manifest,
+            CircuitManifest {
+                shape: shape.clone(),
+                version_hash: version_hash::<F, C, D>(&circuit_data.verifier_only),
+            }
+        );
+
+        // a new registry loads the artifacts of the circuit from disk, without building it
+        let mut registry = registry(&artifacts_dir.0);
+        let loaded = registry.load(&shape).unwrap();
+        assert_eq!(loaded.verifier_only, circuit_data.verifier_only);
+        assert_eq!(loaded.common, circuit_data.common);
+        registry
+            .get_or_build(&shape, || panic!("The circuit must be loaded from disk"))
+            .unwrap();
+        assert_eq!(registry.version_hash(&shape), Some(manifest.version_hash));
+    }
+
+    #[test]
+    fn it_rebuilds_circuits_on_digest_mismatch() {
+        let artifacts_dir = TestArtifactsDir::new("digest");
+        let shape = CircuitShape::new("square", vec![1]);
+        let circuit_data = get_or_build(&mut registry(&artifacts_dir.0), &shape, 1);
+        let expected_version_hash = version_hash::<F, C, D>(&circuit_data.verifier_only);
+
+        // artifacts of another circuit, under the manifest of the first one
+        let other_shape = CircuitShape::new("square", vec![2]);
+        let mut other_registry = registry(&artifacts_dir.0);
+        get_or_build(&mut other_registry, &other_shape, 2);
+        let shape_dir = artifacts_dir.0.join(shape.id());
+        fs::copy(
+            artifacts_dir
+                .0
+                .join(other_shape.id())
+                .join(VERIFIER_ONLY_DATA_FILE),
+            shape_dir.join(VERIFIER_ONLY_DATA_FILE),
+        )
+        .unwrap();
+
+        let mut registry = registry(&artifacts_dir.0);
+        match registry.load(&shape) {
+            Err(CircuitRegistryError::VersionMismatch(id, expected, _)) => {
+                assert_eq!(id, shape.id());
+                assert_eq!(expected, expected_version_hash);
+            }
+            result => panic!("Unexpected result: {:?}", result.map(|_| ())),
+        }
+        let rebuilt = get_or_build(&mut registry, &shape, 1);
+        assert_eq!(rebuilt.verifier_only, circuit_data.verifier_only);
+        assert!(registry.load(&shape).is_ok());
+    }
+
+    #[test]
+    fn it_rebuilds_circuits_on_shape_mismatch() {
+        let artifacts_dir = TestArtifactsDir::new("shape");
+        let shape = CircuitShape::new("square", vec![1]);
+        let circuit_data = get_or_build(&mut registry(&artifacts_dir.0), &shape, 1);
+
+        // the manifest of another circuit, under the directory of the first one
+        let other_shape = CircuitShape::new("square", vec![2]);
+        get_or_build(&mut registry(&artifacts_dir.0), &other_shape, 2);
+        fs::copy(
+            artifacts_dir.0.join(other_shape.id()).join(MANIFEST_FILE),
+            artifacts_dir.0.join(shape.id()).join(MANIFEST_FILE),
+        )
+        .unwrap();
+
+        let mut registry = registry(&artifacts_dir.0);
+        match registry.load(&shape) {
+            Err(CircuitRegistryError::ShapeMismatch(id, found)) => {
+                assert_eq!(id, shape.id());
+                assert_eq!(found, other_shape.id());
+            }
+            result => panic!("Unexpected result: {:?}", result.map(|_| ())),
+        }
+
+        // the artifacts are overwritten by the rebuilt circuit
+        let rebuilt = get_or_build(&mut registry, &shape, 1);
+        assert_eq!(rebuilt.verifier_only, circuit_data.verifier_only);
+        let manifest = read_manifest(&artifacts_dir.0.join(shape.id())).unwrap();
+        assert_eq!(manifest.shape, shape);
+        assert!(registry.load(&shape).is_ok());
+    }
+
+    #[test]
+    fn it_works_artifacts_export() {
+        let artifacts_dir = TestArtifactsDir::new("export");
+        let service_artifacts_dir = TestArtifactsDir::new("export-service");
+        let shape = CircuitShape::new("square", vec![1]);
+        let mut registry = registry(&artifacts_dir.0);
+
+        assert!(matches!(
+            registry.export(&shape, &service_artifacts_dir.0),
+            Err(CircuitRegistryError::UnknownCircuit(_))
+        ));
+        let circuit_data = get_or_build(&mut registry, &shape, 1);
+        let exported_dir = registry.export(&shape, &service_artifacts_dir.0).unwrap();
+        assert_eq!(exported_dir, service_artifacts_dir.0.join(shape.id()));
+
+        let (manifest, verifier_data) =
+            read_verifier_artifacts(&service_artifacts_dir.0, &shape.id()).unwrap();
+        assert_eq!(Some(manifest.version_hash), registry.version_hash(&shape));
+        let verifier_data =
+            VerifierCircuitData::<F, C, D>::from_bytes(verifier_data, &DefaultGateSerializer)
+                .unwrap();
+        assert_eq!(verifier_data.verifier_only, circuit_data.verifier_only);
+    }
+
+    #[test]
+    fn it_rejects_missing_and_invalid_circuit_ids() {
+        let artifacts_dir = TestArtifactsDir::new("ids");
+        assert!(matches!(
+            read_verifier_artifacts(&artifacts_dir.0, "missing"),
+            Err(CircuitRegistryError::UnknownCircuit(_))
+        ));
+        for circuit_id in ["", "..", "../missing", ".hidden", "a/b"] {
+            assert!(!is_valid_circuit_id(circuit_id));
+            assert!(matches!(
+                read_verifier_artifacts(&artifacts_dir.0, circuit_id),
+                Err(CircuitRegistryError::InvalidCircuitId(_))
+            ));
+        }
+        assert!(is_valid_circuit_id(
+            &CircuitShape::new("batch_solution", vec![4, 2]).id()
+        ));
+    }
+}
@@ -0,0 +1,285 @@
+// SIGNATURE_DESIGN:
+//
+// 1. Every intent is signed by its owner over its structured hash. The match
+//    circuit must prove that both intents of a match carry a valid signature,
+//    so that a solution cannot include forged intents, even if the batch data
+//    held by the Solina service was tampered with.
+// 2. The signature scheme is selected at compile time, via the `ecdsa` (default)
+//    or the `eddsa` feature. Exactly one of them should be enabled. Both are forwarded
+//    to `solina`, which verifies the same signatures natively (see `solina::signature`).
+// 3. The signed message is the Poseidon structured hash of the intent, as the 32 bytes
+//    of `Poseidon::digest_to_bytes`. It is computed in circuit from the intent contents
+//    (see `intent_hash`), and the public key is given by the same limbs the nullifier is
+//    computed over (see `nullifier`), so that signatures are bound to the matched intents.
+// 4. With `ecdsa`, we verify a secp256k1 ECDSA signature, over the message reduced modulo
+//    the scalar field order. Intents only carry the 32 byte x-coordinate of the public
+//    key, so its y-coordinate is a witness, of either parity: both points share the
+//    x-coordinate, and only the owner of the secret key can sign for either of them. The
+//    prover picks the one the signature verifies against. The signature bytes are
+//    `r || s`, both big endian.
+// 5. With `eddsa`, we verify an Ed25519 signature, whose 32 byte public key and
+//    64 byte signature match the intent byte layout as is.
+use plonky2::{
+    field::extension::Extendable,
+    hash::hash_types::{HashOutTarget, RichField},
+    iop::{target::Target, witness::PartialWitness},
+    plonk::circuit_builder::CircuitBuilder,
+};
+use solina::{
+    intent::Intent,
+    signature::SignatureError,
+    structured_hash::{HashBackend, Poseidon, StructuredHashInterface},
+};
+
+#[cfg(all(feature = "ecdsa", feature = "eddsa"))]
+compile_error!("Features `ecdsa` and `eddsa` are mutually exclusive");
+
+#[cfg(not(any(feature = "ecdsa", feature = "eddsa")))]
+compile_error!("One of the features `ecdsa` or `eddsa` must be enabled");
+
+#[cfg(feature = "ecdsa")]
+pub use ecdsa::*;
+
+#[cfg(feature = "eddsa")]
+pub use eddsa::*;
+
+/// Adds targets and constraints verifying an intent signature over its in-circuit
+/// `structured_hash`, against the big endian u32 limbs of its `public_key`.
+pub fn add_intent_signature_verification<F, const D: usize>(
+    circuit_builder: &mut CircuitBuilder<F, D>,
+    structured_hash: HashOutTarget,
+    public_key: &[Target],
+) -> IntentSignatureTargets
+where
+    F: RichField + Extendable<D>,
+{
+    add_signature_verification_targets(circuit_builder, structured_hash, public_key)
+}
+
+/// Sets the witness of previously generated intent signature targets. The message and the
+/// public key are set with the intent contents, see `intent_hash` and `nullifier`.
+pub fn set_intent_signature_targets<F, const D: usize>(
+    partial_witness: &mut PartialWitness<F>,
+    intent: &Intent,
+    targets: &IntentSignatureTargets,
+) -> Result<(), SignatureCircuitError>
+where
+    F: RichField + Extendable<D>,
+{
+    let message = Poseidon::digest_to_bytes(&intent.structured_hash_with::<Poseidon>());
+    set_signature_targets::<F, D>(
+        partial_witness,
+        &message,
+        &intent.public_key(),
+        &intent.signature().0,
+        targets,
+    )
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SignatureCircuitError {
+    /// The intent public key is not a valid curve point
+    InvalidPublicKey,
+    /// The intent signature cannot be decoded, or does not verify
+    InvalidSignature,
+}
+
+impl From<SignatureError> for SignatureCircuitError {
+    fn from(error: SignatureError) -> Self {
+        match error {
+            SignatureError::InvalidPublicKey => Self::InvalidPublicKey,
+            SignatureError::InvalidSignature => Self::InvalidSignature,
+        }
+    }
+}
+
+#[cfg(feature = "ecdsa")]
+mod ecdsa {
+    use super::SignatureCircuitError;
+    use crate::intent_hash::digest_to_limbs;
+    use plonky2::{
+        field::{extension::Extendable, secp256k1_scalar::Secp256K1Scalar},
+        hash::hash_types::{HashOutTarget, RichField},
+        iop::{target::Target, witness::PartialWitness},
+        plonk::circuit_builder::CircuitBuilder,
+    };
+    use plonky2_ecdsa::{
+        curve::secp256k1::Secp256K1,
+        gadgets::{
+            biguint::{BigUintTarget, WitnessBigUint},
+            curve::CircuitBuilderCurve,
+            ecdsa::{verify_message_circuit, ECDSAPublicKeyTarget, ECDSASignatureTarget},
+            nonnative::{CircuitBuilderNonNative, NonNativeTarget},
+        },
+    };
+    use plonky2_u32::gadgets::arithmetic_u32::U32Target;
+    use solina::signature::{lift_x, reduce_scalar};
+
+    pub use solina::signature::sign_intent;
+
+    pub struct IntentSignatureTargets {
+        pub message: NonNativeTarget<Secp256K1Scalar>,
+        pub public_key: ECDSAPublicKeyTarget<Secp256K1>,
+        pub signature: ECDSASignatureTarget<Secp256K1>,
+    }
+
+    pub(crate) fn add_signature_verification_targets<F, const D: usize>(
+        circuit_builder: &mut CircuitBuilder<F, D>,
+        structured_hash: HashOutTarget,
+        public_key: &[Target],
+    ) -> IntentSignatureTargets
+    where
+        F: RichField + Extendable<D>,
+    {
+        // little endian u32 limbs of the big endian message bytes
+        let message_limbs = digest_to_limbs(circuit_builder, structured_hash)
+            .into_iter()
+            .rev()
+            .map(U32Target)
+            .collect();
+        let message = circuit_builder.reduce::<Secp256K1Scalar>(&BigUintTarget {
+            limbs: message_limbs,
+        });
+
+        let public_key_target =
+            ECDSAPublicKeyTarget(circuit_builder.add_virtual_affine_point_target::<Secp256K1>());
+        // the x-coordinate limbs are little endian, while public key limbs are big endian
+        public_key_target
+            .0
+            .x
+            .value
+            .limbs
+            .iter()
+            .zip(public_key.iter().rev())
+            .for_each(|(x_limb, public_key_limb)| {
+                circuit_builder.connect(x_limb.0, *public_key_limb)
+            });
+
+        let signature = ECDSASignatureTarget {
+            r: circuit_builder.add_virtual_nonnative_target::<Secp256K1Scalar>(),
+            s: circuit_builder.add_virtual_nonnative_target::<Secp256K1Scalar>(),
+        };
+
+        verify_message_circuit(
+            circuit_builder,
+            message.clone(),
+            signature.clone(),
+            public_key_target.clone(),
+        );
+
+        IntentSignatureTargets {
+            message,
+            public_key: public_key_target,
+            signature,
+        }
+    }
+
+    pub(crate) fn set_signature_targets<F, const D: usize>(
+        partial_witness: &mut PartialWitness<F>,
+        message: &[u8],
+        public_key: &[u8],
+        signature: &[u8],
+        targets: &IntentSignatureTargets,
+    ) -> Result<(), SignatureCircuitError>
+    where
+        F: RichField + Extendable<D>,
+    {
+        if signature.len() != 64 {
+            return Err(SignatureCircuitError::InvalidSignature);
+        }
+        let message = reduce_scalar(message);
+        let r = reduce_scalar(&signature[..32]);
+        let s = reduce_scalar(&signature[32..]);
+        let (x, y) = lift_x(public_key, &message, &r, &s)?;
+
+        partial_witness.set_biguint_target(&targets.signature.r.value, &r);
+        partial_witness.set_biguint_target(&targets.signature.s.value, &s);
+        partial_witness.set_biguint_target(&targets.public_key.0.x.value, &x);
+        partial_witness.set_biguint_target(&targets.public_key.0.y.value, &y);
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "eddsa")]
+mod eddsa {
+    use super::SignatureCircuitError;
+    use crate::intent_hash::digest_to_limbs;
+    use plonky2::{
+        field::extension::Extendable,
+        hash::hash_types::{HashOutTarget, RichField},
+        iop::{
+            target::{BoolTarget, Target},
+            witness::PartialWitness,
+        },
+        plonk::circuit_builder::CircuitBuilder,
+    };
+    use plonky2_ed25519::gadgets::eddsa::{fill_circuits, make_verify_circuits, EDDSATargets};
+
+    /// Structured hashes are 32 bytes long
+    const MESSAGE_LENGTH: usize = 32;
+
+    pub struct IntentSignatureTargets {
+        pub eddsa_targets: EDDSATargets,
+    }
+
+    /// Connects big endian u32 limbs to bits, most significant bit of each byte first.
+    fn connect_limbs_to_bits<F, const D: usize>(
+        circuit_builder: &mut CircuitBuilder<F, D>,
+        limbs: &[Target],
+        bits: &[BoolTarget],
+    ) where
+        F: RichField + Extendable<D>,
+    {
+        let limb_bits = limbs
+            .iter()
+            .flat_map(|limb| circuit_builder.split_le(*limb, 32).into_iter().rev())
+            .collect::<Vec<_>>();
+        limb_bits
+            .iter()
+            .zip(bits)
+            .for_each(|(limb_bit, bit)| circuit_builder.connect(limb_bit.target, bit.target));
+    }
+
+    pub(crate) fn add_signature_verification_targets<F, const D: usize>(
+        circuit_builder: &mut CircuitBuilder<F, D>,
+        structured_hash: HashOutTarget,
+        public_key: &[Target],
+    ) -> IntentSignatureTargets
+    where
+        F: RichField + Extendable<D>,
+    {
+        let eddsa_targets = make_verify_circuits(circuit_builder, MESSAGE_LENGTH);
+        let message_limbs = digest_to_limbs(circuit_builder, structured_hash);
+        connect_limbs_to_bits(circuit_builder, &message_limbs, &eddsa_targets.msg);
+        connect_limbs_to_bits(circuit_builder, public_key, &eddsa_targets.pk);
+
+        IntentSignatureTargets { eddsa_targets }
+    }
+
+    pub(crate) fn set_signature_targets<F, const D: usize>(
+        partial_witness: &mut PartialWitness<F>,
+        message: &[u8],
+        public_key: &[u8],
+        signature: &[u8],
+        targets: &IntentSignatureTargets,
+    ) -> Result<(), SignatureCircuitError>
+    where
+        F: RichField + Extendable<D>,
+    {
+        if public_key.len() != 32 {
+            return Err(SignatureCircuitError::InvalidPublicKey);
+        }
+        if signature.len() != 64 {
+            return Err(SignatureCircuitError::InvalidSignature);
+        }
+        fill_circuits::<F, D>(
+            partial_witness,
+            message,
+            signature,
+            public_key,
+            &targets.eddsa_targets,
+        );
+        Ok(())
+    }
+}
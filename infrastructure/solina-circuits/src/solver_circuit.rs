@@ -20,6 +20,10 @@
 //    We can route a single intent, intended for a big trade, with multiple shorter intents,
 //    as an example.
 //    That means, we need to have a global swapped amount for each intent.
+// 8. The batch sealing timestamp is a public input, and every matched intent must expire
+//    strictly after it (see `expiry`).
+// 9. The nullifiers of all matched intents are public outputs (see `nullifier`), so that
+//    intents cannot be settled in more than one batch.
 //
 //
 // Further remarks:
@@ -0,0 +1,3 @@
+// The batch solution proof verifier lives in `solina-verify`, which does not depend on the
+// circuits, so that it can be built for wasm32.
+pub use solina_verify::*;
@@ -2,6 +2,7 @@ use anyhow::anyhow;
 use reqwest::{header, header::HeaderMap, IntoUrl, Url};
 use serde::{de::DeserializeOwned, Serialize};
 use serde_json::{json, Value};
+use solina::IntentId;
 
 #[derive(Clone, Debug)]
 /// An intent client whose purpose is to send http requests to the server.
@@ -17,7 +18,7 @@ impl IntentClient {
         let client = reqwest::Client::builder()
             .default_headers({
                 let mut headers = HeaderMap::with_capacity(1);
-                headers.insert(header::CONTENT_TYPE, "applications/json".parse().unwrap());
+                headers.insert(header::CONTENT_TYPE, "application/json".parse().unwrap());
                 headers
             })
             .build()?;
@@ -55,7 +56,6 @@ impl IntentClient {
             .body(request_json.to_string())
             .send()
             .await?;
-        eprintln!("HELLO WORLD: {:?}", response);
         let value: Value = response.json().await?;
         let json_value = jsonrpc_value(value)?;
         match serde_json::from_value(json_value) {
@@ -63,6 +63,15 @@ impl IntentClient {
             Err(e) => Err(anyhow!("Failed to deserialize response, with error: {}", e)),
         }
     }
+
+    /// Submits the `store_intent` request `params`, returning the id assigned to the intent.
+    pub async fn store_intent(&mut self, params: Value) -> Result<IntentId, anyhow::Error> {
+        let response: Value = self.send_request(params, "store_intent").await?;
+        response
+            .get("intent_id")
+            .and_then(|id| id.as_i64())
+            .ok_or_else(|| anyhow!("Missing intent id in response: {}", response))
+    }
 }
 
 fn jsonrpc_value(value: Value) -> Result<Value, anyhow::Error> {
@@ -75,7 +84,7 @@ fn jsonrpc_value(value: Value) -> Result<Value, anyhow::Error> {
         return Err(anyhow!(
             "Request Failed with status: code = {}, message = {}",
             code,
-            message.to_string()
+            message
         ));
     }
 
@@ -1,7 +1,7 @@
 use reqwest::Url;
+use serde_json::Value;
 use solina_client::intent_client::IntentClient;
 use std::io::stdin;
-use tokio;
 
 #[tokio::main]
 async fn main() {
@@ -21,11 +21,17 @@ async fn main() {
         if input == "exit" {
             break;
         }
-        eprintln!("FLAG: DEBUG {}", serde_json::to_value(message).unwrap());
-        intent_client
-            .send_request::<&str, String>(message, "store")
-            .await
-            .expect("Failed to send request successfully");
+        // each line holds the JSON params of a `store_intent` request
+        match serde_json::from_str::<Value>(message) {
+            Ok(params) => {
+                let intent_id = intent_client
+                    .store_intent(params)
+                    .await
+                    .expect("Failed to send request successfully");
+                println!("Intent stored with id: {}", intent_id);
+            }
+            Err(e) => eprintln!("Invalid JSON params: {}", e),
+        }
         input.clear();
     }
 }
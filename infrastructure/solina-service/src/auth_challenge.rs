@@ -1,8 +1,7 @@
-use axum::Json;
 use ethers::prelude::*;
 use log::{error, info};
 use rand::Rng;
-use serde_json::{json, Value};
+use serde_json::Value;
 use std::str::FromStr;
 
 use crate::error::{Error, Result};
@@ -18,32 +17,35 @@ pub(crate) fn generate_challenge() -> String {
 }
 
 pub(crate) fn extract_address(value: &Value) -> Result<String> {
-    let address = value.get("address").ok_or(Error::InvalidRequest)?;
-    let address = address.as_str().ok_or({
-        error!("Failed to extract address from request, with error");
-        Error::InvalidRequest
-    })?;
-    Ok(address.to_string())
+    extract_str(value, "address")
 }
 
 pub(crate) fn extract_signature(value: &Value) -> Result<String> {
-    let signature = value.get("signature").ok_or(Error::InvalidRequest)?;
-    let signature = signature.as_str().ok_or({
-        error!("Failed to extract signature from request");
-        Error::InvalidRequest
+    extract_str(value, "signature")
+}
+
+fn extract_str(value: &Value, field: &str) -> Result<String> {
+    let field_value = value.get(field).and_then(Value::as_str).ok_or_else(|| {
+        error!("Failed to extract {} from request", field);
+        Error::AuthError(format!("Missing, or invalid, {} field", field))
     })?;
-    Ok(signature.to_string())
+    Ok(field_value.to_string())
 }
 
 pub(crate) fn verify_signature(
     address: String,
     challenge: String,
     signature: String,
-) -> Result<Json<Value>> {
+) -> Result<()> {
     info!("The challenge is: {}", challenge);
 
-    let address: Address = Address::from_str(&address)
-        .unwrap_or_else(|_| panic!("Failed to extract Address from public key, {}", address));
+    let address: Address = Address::from_str(&address).map_err(|e| {
+        error!(
+            "Failed to extract Address from public key, {}, with error: {}",
+            address, e
+        );
+        Error::AuthError(format!("Invalid address: {}", address))
+    })?;
     info!("The address is: {:?}", address);
 
     match Signature::from_str(&signature) {
@@ -52,14 +54,13 @@ pub(crate) fn verify_signature(
                 "Failed to recover user address from signature and message, with error: {}",
                 e
             );
-            Error::AuthError
+            Error::AuthError("Challenge signature does not match the address".to_string())
         })?,
         Err(e) => {
             error!("Failed to obtain signature from request, with error: {}", e);
-            return Err(Error::AuthError);
+            return Err(Error::AuthError(format!("Invalid signature: {}", e)));
         }
     };
 
-    // Send the response back to the user
-    Ok(Json(json!({"status": "successfully verified"})))
+    Ok(())
 }
@@ -1,27 +1,29 @@
 use crate::{
     auth_challenge::{extract_address, extract_signature, verify_signature},
+    error::Error,
     json_rpc_server::AppState,
+    worker::SolinaWorker,
 };
-use axum::body::{boxed, Body, BoxBody};
+use axum::body::{Body, BoxBody};
 use axum::{
-    http::{Request, StatusCode},
-    response::Response,
+    http::Request,
+    response::{IntoResponse, Response},
 };
 use futures_util::future::BoxFuture;
 use hyper::body::to_bytes;
 use log::{error, info};
-use std::sync::{Arc, Mutex};
+use serde_json::Value;
 use tower::{layer::Layer, Service};
 
 #[derive(Clone)]
 pub struct EthereumAuthMiddleware<S> {
-    inner: Arc<Mutex<S>>,
+    inner: S,
     pub(crate) app_state: AppState,
 }
 
 impl<S> Service<Request<Body>> for EthereumAuthMiddleware<S>
 where
-    S: Service<Request<Body>, Response = Response<BoxBody>> + Send + 'static,
+    S: Service<Request<Body>, Response = Response<BoxBody>> + Clone + Send + 'static,
     S::Future: Send + 'static,
 {
     type Response = S::Response;
@@ -33,162 +35,114 @@ where
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Result<(), Self::Error>> {
         // Forward the call to the inner service
-        self.inner.lock().unwrap().poll_ready(cx)
+        self.inner.poll_ready(cx)
     }
 
     fn call(&mut self, req: Request<Body>) -> Self::Future {
-        let inner = self.inner.clone();
+        // the service driven to readiness is the one to call, while a fresh clone takes its
+        // place for the next request
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
         let app_state = self.app_state.clone();
 
         Box::pin(async move {
             match *req.method() {
                 http::Method::GET => {
-                    let future = {
-                        let mut inner_service = inner.lock().unwrap();
-                        info!("Sending request to inner service");
-                        inner_service.call(req)
-                    };
-                    future.await
+                    info!("Sending request to inner service");
+                    inner.call(req).await
                 }
                 http::Method::POST => {
                     let (parts, body) = req.into_parts();
-                    let body_bytes = to_bytes(body).await.expect("Failed to extract body bytes");
-
-                    let json_value = serde_json::from_slice(body_bytes.as_ref())
-                        .expect("Failed to extract JSON from request bytes");
-
-                    info!("The provided JSON value is: {}", json_value);
-
-                    let address =
-                        extract_address(&json_value).expect("Failed to extract signature");
-                    let signature =
-                        extract_signature(&json_value).expect("Failed to extract signature");
-
-                    let credential = {
-                        let mut app_state_write = app_state
-                            .solina_worker
-                            .write()
-                            .expect("Failed to write to worker");
-                        if let Ok(credential) = app_state_write.get_current_credential(&address) {
-                            credential
-                        } else {
-                            let response = Response::builder()
-                        .status(StatusCode::UNAUTHORIZED)
-                        .body(boxed(Body::from(
-                            "InvalidRequest: User does not have a valid challenge in memory to sign",
-                        )))
-                        .expect("Failed to form body");
-                            return Ok(response);
+                    let body_bytes = match to_bytes(body).await {
+                        Ok(body_bytes) => body_bytes,
+                        Err(e) => {
+                            error!("Failed to extract body bytes, with error: {}", e);
+                            let error = Error::InvalidRequest(format!("Unreadable body: {}", e));
+                            return Ok(error.into_response());
                         }
                     };
 
-                    info!(
-                        "Got new credential for address = {} and id = {}, with challenge = {}",
-                        address, credential.id, credential.challenge
-                    );
-
-                    let result = {
-                        let now = chrono::prelude::Utc::now().naive_utc();
-                        let config_auth_timeout = match app_state.solina_worker.read() {
-                            Ok(inner) => inner.config().auth_credential_timeout(),
-                            Err(e) => {
-                                error!("Failed to get read access to worker, with error: {}", e);
-                                let response = Response::builder()
-                        .status(StatusCode::UNAUTHORIZED)
-                        .body(boxed(Body::from(
-                            "InvalidRequest: User does not have a valid challenge in memory to sign",
-                        )))
-                        .expect("Failed to form body");
-                                return Ok(response);
-                            }
-                        };
-                        if credential.is_auth
-                            && now
-                                .signed_duration_since(credential.created_at)
-                                .num_seconds()
-                                <= config_auth_timeout as i64
-                        {
-                            // Reconstruct the request
-                            let req = Request::from_parts(parts, Body::from(body_bytes));
-                            let future = {
-                                let mut inner_service = inner.lock().unwrap();
-                                info!("Sending request to inner service");
-                                inner_service.call(req)
-                            };
-                            return future.await;
-                        } else if now
-                            .signed_duration_since(credential.created_at)
-                            .num_seconds()
-                            <= config_auth_timeout as i64
-                        {
-                            verify_signature(address, credential.challenge.clone(), signature)
-                        } else {
-                            let response = Response::builder()
-                    .status(StatusCode::UNAUTHORIZED)
-                    .body(boxed(Body::from(
-                        "InvalidRequest: User does not have a valid challenge in memory to sign",
-                    )))
-                    .expect("Failed to form body");
-                            let mut app_state_write = match app_state.solina_worker.write() {
-                                Ok(inner) => inner,
-                                Err(e) => {
-                                    error!("Failed to write to worker, with error: {}", e);
-                                    return Ok(response);
-                                }
-                            };
-                            if let Err(e) =
-                                app_state_write.update_is_valid_credential(credential.id)
-                            {
-                                error!("Failed to update credential, with error: {}", e);
-                            }
-                            return Ok(response);
-                        }
-                    };
-                    if let Err(e) = result {
-                        error!("Failed to verify challenge signature, with error: {}", e);
-                        let response = Response::builder()
-                            .status(StatusCode::UNAUTHORIZED)
-                            .body(boxed(Body::from("Invalid challenge signature")))
-                            .expect("Failed to form body");
-                        return Ok(response);
-                    } else {
-                        // otherwise update the authentication in the database
-                        let mut app_state_write = app_state
-                            .solina_worker
-                            .write()
-                            .expect("Failed to write to worker");
-                        if let Err(e) = app_state_write.update_is_auth_credential(credential.id) {
-                            error!("Failed to update credential, with error: {}", e);
-                            let response = Response::builder()
-                                .status(StatusCode::UNAUTHORIZED)
-                                .body(boxed(Body::from("InternalError")))
-                                .expect("Failed to form body");
-                            return Ok(response);
-                        }
+                    if let Err(e) = authenticate_body(&app_state, &body_bytes).await {
+                        error!("Failed to authenticate request, with error: {}", e);
+                        return Ok(e.into_response());
                     }
+
                     // Reconstruct the request
                     let req = Request::from_parts(parts, Body::from(body_bytes));
                     // if signature verification is successful, forward the req call to the inner service
-                    let future = {
-                        let mut inner_service = inner.lock().unwrap();
-                        info!("Sending request to inner service");
-                        inner_service.call(req)
-                    };
-                    future.await
+                    info!("Sending request to inner service");
+                    inner.call(req).await
                 }
                 _ => {
-                    let future = {
-                        let mut inner_service = inner.lock().unwrap();
-                        info!("Sending request to inner service");
-                        inner_service.call(req)
-                    };
-                    future.await
+                    info!("Sending request to inner service");
+                    inner.call(req).await
                 }
             }
         })
     }
 }
 
+/// Authenticates the request with the `address` and `signature` fields of its JSON body.
+async fn authenticate_body(app_state: &AppState, body_bytes: &[u8]) -> crate::error::Result<()> {
+    let json_value = serde_json::from_slice::<Value>(body_bytes).map_err(|e| {
+        error!(
+            "Failed to extract JSON from request bytes, with error: {}",
+            e
+        );
+        Error::InvalidRequest(format!("Invalid JSON body: {}", e))
+    })?;
+    info!("The provided JSON value is: {}", json_value);
+
+    let address = extract_address(&json_value)?;
+    let signature = extract_signature(&json_value)?;
+    authenticate(app_state, address, signature).await
+}
+
+/// Authenticates `address`, with its `signature` of the current challenge. Once signed, a
+/// challenge authenticates its address until it times out.
+pub(crate) async fn authenticate(
+    app_state: &AppState,
+    address: String,
+    signature: String,
+) -> crate::error::Result<()> {
+    app_state
+        .worker
+        .run(|solina_worker| authenticate_credential(solina_worker, address, signature))
+        .await
+}
+
+fn authenticate_credential(
+    solina_worker: &mut SolinaWorker,
+    address: String,
+    signature: String,
+) -> crate::error::Result<()> {
+    let credential = solina_worker.get_current_credential(&address)?;
+    info!(
+        "Got new credential for address = {} and id = {}, with challenge = {}",
+        address, credential.id, credential.challenge
+    );
+
+    let now = chrono::prelude::Utc::now().naive_utc();
+    if now
+        .signed_duration_since(credential.created_at)
+        .num_seconds()
+        > solina_worker.config().auth_credential_timeout() as i64
+    {
+        if let Err(e) = solina_worker.update_is_valid_credential(credential.id) {
+            error!("Failed to update credential, with error: {}", e);
+        }
+        return Err(Error::AuthError("Challenge has timed out".to_string()));
+    }
+
+    if credential.is_auth {
+        return Ok(());
+    }
+
+    verify_signature(address, credential.challenge, signature)?;
+    // otherwise update the authentication in the database
+    solina_worker.update_is_auth_credential(credential.id)
+}
+
 #[derive(Clone)]
 pub struct EthereumAuthMiddlewareLayer {
     pub(crate) app_state: AppState,
@@ -199,7 +153,7 @@ impl<S> Layer<S> for EthereumAuthMiddlewareLayer {
 
     fn layer(&self, inner: S) -> Self::Service {
         EthereumAuthMiddleware {
-            inner: Arc::new(Mutex::new(inner)),
+            inner,
             app_state: self.app_state.clone(),
         }
     }
@@ -207,8 +161,11 @@ impl<S> Layer<S> for EthereumAuthMiddlewareLayer {
 
 #[cfg(test)]
 mod tests {
+    use crate::auth_challenge::{extract_address, verify_signature};
+    use crate::error::Error;
     use ethers::prelude::*;
     use hex::encode;
+    use serde_json::json;
 
     #[tokio::test]
     async fn challenge_auth() {
@@ -233,4 +190,20 @@ mod tests {
 
         assert!(signature.verify(challenge, wallet.address()).is_ok());
     }
+
+    #[test]
+    fn it_rejects_malformed_credentials() {
+        assert!(matches!(
+            extract_address(&json!({"address": 1})),
+            Err(Error::AuthError(_))
+        ));
+        assert!(matches!(
+            verify_signature(
+                "not an address".to_string(),
+                "challenge".to_string(),
+                "00".to_string()
+            ),
+            Err(Error::AuthError(_))
+        ));
+    }
 }
@@ -0,0 +1,23 @@
+use crate::worker::WorkerHandle;
+use chrono::Utc;
+use log::error;
+use std::time::Duration;
+
+/// Period at which batch lifecycle transitions are checked.
+pub const BATCH_LIFECYCLE_TICK: Duration = Duration::from_secs(1);
+
+/// Background task driving batches from collecting intents to settlement. Errors are
+/// logged, and the transitions retried on the next tick.
+pub async fn run_batch_lifecycle(worker: WorkerHandle) {
+    let mut interval = tokio::time::interval(BATCH_LIFECYCLE_TICK);
+    loop {
+        interval.tick().await;
+        let now = Utc::now().naive_utc();
+        let result = worker
+            .run(move |worker| worker.advance_batch_lifecycle(now))
+            .await;
+        if let Err(e) = result {
+            error!("Failed to advance batch lifecycle, with error: {}", e);
+        }
+    }
+}
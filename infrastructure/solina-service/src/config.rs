@@ -1,27 +1,93 @@
+use crate::{mempool::OrderingPolicy, validation::ValidationRule};
+use clap::Parser;
+use hex::decode;
+use serde::{Deserialize, Serialize};
+use solina::{
+    competition::{ScoringMetric, SelectionPolicy},
+    price_oracle::PriceSnapshot,
+};
+use solina_circuits::{
+    batch_circuit::{batch_solution_shape, batch_tree_size},
+    registry::is_valid_circuit_id,
+};
 use std::{
+    fs,
     net::SocketAddr,
     path::{Path, PathBuf},
 };
+use toml::Value;
+
+/// Prefix of the environment variables overriding configuration fields, e.g.
+/// `SOLINA_SOCKET_ADDRESS` overrides `socket_address`.
+pub const ENV_PREFIX: &str = "SOLINA_";
 
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("Failed to read config file {0}: {1}")]
+    Read(PathBuf, std::io::Error),
+    #[error("Failed to parse config: {0}")]
+    Parse(String),
+    #[error("Invalid {0}: {1}")]
+    Invalid(&'static str, String),
+}
+
+/// Configuration of the service, loaded from, by increasing precedence, defaults, a TOML
+/// file, environment variables and command line flags. Fields missing from a layer keep
+/// their value from the previous one.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct SolinaConfig {
     mempool_capacity: usize,
+    max_intents_per_signer: usize,
+    mempool_ordering: OrderingPolicy,
+    intent_validation: Vec<ValidationRule>,
     storage_file_path: PathBuf,
     socket_address: SocketAddr,
     auth_credential_timeout: u64,
+    circuit_artifacts_dir: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    solution_circuit_id: Option<String>,
+    batch_sealing_interval: u64,
+    solving_window: u64,
+    scoring_metric: ScoringMetric,
+    selection_policy: SelectionPolicy,
+    #[serde(with = "decimal_prices")]
+    token_prices: PriceSnapshot,
 }
 
 impl SolinaConfig {
+    #[allow(clippy::too_many_arguments)]
     pub fn new<P: AsRef<Path>>(
         mempool_capacity: usize,
+        max_intents_per_signer: usize,
+        mempool_ordering: OrderingPolicy,
+        intent_validation: Vec<ValidationRule>,
         storage_file_path: P,
         socket_address: SocketAddr,
         auth_credential_timeout: u64,
+        circuit_artifacts_dir: P,
+        solution_circuit_id: Option<String>,
+        batch_sealing_interval: u64,
+        solving_window: u64,
+        scoring_metric: ScoringMetric,
+        selection_policy: SelectionPolicy,
+        token_prices: PriceSnapshot,
     ) -> Self {
         Self {
             mempool_capacity,
+            max_intents_per_signer,
+            mempool_ordering,
+            intent_validation,
             storage_file_path: storage_file_path.as_ref().to_path_buf(),
             socket_address,
             auth_credential_timeout,
+            circuit_artifacts_dir: circuit_artifacts_dir.as_ref().to_path_buf(),
+            solution_circuit_id,
+            batch_sealing_interval,
+            solving_window,
+            scoring_metric,
+            selection_policy,
+            token_prices,
         }
     }
 
@@ -29,6 +95,21 @@ impl SolinaConfig {
         self.mempool_capacity
     }
 
+    /// Maximum number of pending intents of a single signer in the mempool.
+    pub fn max_intents_per_signer(&self) -> usize {
+        self.max_intents_per_signer
+    }
+
+    /// Order in which pending intents are batched.
+    pub fn mempool_ordering(&self) -> OrderingPolicy {
+        self.mempool_ordering
+    }
+
+    /// Rules submitted intents are validated against, in order, before entering the mempool.
+    pub fn intent_validation(&self) -> &[ValidationRule] {
+        &self.intent_validation
+    }
+
     pub fn storage_file_path(&self) -> &PathBuf {
         &self.storage_file_path
     }
@@ -40,15 +121,455 @@ impl SolinaConfig {
     pub fn auth_credential_timeout(&self) -> u64 {
         self.auth_credential_timeout
     }
+
+    pub fn circuit_artifacts_dir(&self) -> &PathBuf {
+        &self.circuit_artifacts_dir
+    }
+
+    /// Id of the batch solution circuit proofs are verified with, whose artifacts must be
+    /// exported under `circuit_artifacts_dir`, e.g. by `export_batch_circuit`. Unless set, the
+    /// circuit for full batches of `mempool_capacity` intents.
+    pub fn solution_circuit_id(&self) -> String {
+        self.solution_circuit_id.clone().unwrap_or_else(|| {
+            batch_solution_shape(self.mempool_capacity, self.mempool_capacity / 2).id()
+        })
+    }
+
+    /// Maximum time, in seconds, a batch collects intents before being sealed. Batches are
+    /// sealed earlier if the mempool reaches its capacity.
+    pub fn batch_sealing_interval(&self) -> u64 {
+        self.batch_sealing_interval
+    }
+
+    /// Time, in seconds, solvers have to submit solutions for a sealed batch.
+    pub fn solving_window(&self) -> u64 {
+        self.solving_window
+    }
+
+    /// Metric competing solutions are scored with, the highest score wins the batch.
+    pub fn scoring_metric(&self) -> ScoringMetric {
+        self.scoring_metric
+    }
+
+    /// Whether batches are settled by their best solution, or by a combination of
+    /// compatible solutions.
+    pub fn selection_policy(&self) -> SelectionPolicy {
+        self.selection_policy
+    }
+
+    /// Reference token prices, denominated in ETH, snapshotted for each batch at sealing
+    /// time, to score its solutions. There is no oracle source yet: prices are only read from
+    /// the config, and are updated by restarting the service with a new config.
+    pub fn token_prices(&self) -> &PriceSnapshot {
+        &self.token_prices
+    }
+
+    /// Default configuration, overridden by the `file`, if any, then by the `SOLINA_`
+    /// prefixed variables of `env`. Variables are parsed as TOML values, or taken as
+    /// strings otherwise.
+    pub fn load(
+        file: Option<&Path>,
+        env: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, ConfigError> {
+        let mut config = match file {
+            Some(path) => {
+                let contents = fs::read_to_string(path)
+                    .map_err(|e| ConfigError::Read(path.to_path_buf(), e))?;
+                toml::from_str::<Value>(&contents)
+                    .map_err(|e| ConfigError::Parse(format!("{}: {}", path.display(), e)))?
+            }
+            None => {
+                Value::try_from(Self::default()).map_err(|e| ConfigError::Parse(e.to_string()))?
+            }
+        };
+        let table = config
+            .as_table_mut()
+            .ok_or_else(|| ConfigError::Parse("config is not a table".to_string()))?;
+        for (name, value) in env {
+            let field = match name.strip_prefix(ENV_PREFIX) {
+                Some(field) => field.to_lowercase(),
+                None => continue,
+            };
+            let value = toml::from_str::<Value>(&format!("value = {}", value))
+                .ok()
+                .and_then(|mut parsed| parsed.as_table_mut()?.remove("value"))
+                .unwrap_or(Value::String(value));
+            table.insert(field, value);
+        }
+        config
+            .try_into()
+            .map_err(|e| ConfigError::Parse(e.to_string()))
+    }
+
+    /// Checks that values are within their bounds, that token addresses are well formed, and
+    /// that the solution circuit, if set, is one for batches of `mempool_capacity` intents.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let positive = [
+            ("mempool_capacity", self.mempool_capacity as u64),
+            ("max_intents_per_signer", self.max_intents_per_signer as u64),
+            ("auth_credential_timeout", self.auth_credential_timeout),
+            ("batch_sealing_interval", self.batch_sealing_interval),
+            ("solving_window", self.solving_window),
+        ];
+        if let Some((field, _)) = positive.iter().find(|(_, value)| *value == 0) {
+            return Err(ConfigError::Invalid(field, "must be positive".to_string()));
+        }
+        if let Some(circuit_id) = &self.solution_circuit_id {
+            // batch solution circuit ids end with their batch tree size and number of matches
+            let batch_tree_size = batch_tree_size(self.mempool_capacity);
+            let circuit_batch_tree_size = circuit_id
+                .rsplit('_')
+                .next()
+                .and_then(|parameters| parameters.split('-').next())
+                .and_then(|size| size.parse::<usize>().ok());
+            if !is_valid_circuit_id(circuit_id) || circuit_batch_tree_size != Some(batch_tree_size)
+            {
+                return Err(ConfigError::Invalid(
+                    "solution_circuit_id",
+                    format!(
+                        "{} is not a batch solution circuit for batches of {} intents",
+                        circuit_id, self.mempool_capacity
+                    ),
+                ));
+            }
+        }
+        if let Some(token) = self
+            .token_prices
+            .prices
+            .keys()
+            .find(|token| !is_token_address(token))
+        {
+            return Err(ConfigError::Invalid(
+                "token_prices",
+                format!("{} is not a token address", token),
+            ));
+        }
+        self.intent_validation.iter().try_for_each(|rule| {
+            let invalid = |reason: String| ConfigError::Invalid("intent_validation", reason);
+            match rule {
+                ValidationRule::Expiry { max_horizon: 0 } => {
+                    Err(invalid("expiry horizon must be positive".to_string()))
+                }
+                ValidationRule::TokenAllowlist { tokens } if tokens.is_empty() => {
+                    Err(invalid("token allowlist must not be empty".to_string()))
+                }
+                ValidationRule::TokenAllowlist { tokens }
+                | ValidationRule::TokenDenylist { tokens } => {
+                    match tokens.iter().find(|token| !is_token_address(token)) {
+                        Some(token) => Err(invalid(format!("{} is not a token address", token))),
+                        None => Ok(()),
+                    }
+                }
+                _ => Ok(()),
+            }
+        })
+    }
+
+    /// Configuration, as a TOML document.
+    pub fn to_toml(&self) -> Result<String, ConfigError> {
+        // values are written before tables once converted, as TOML requires
+        Value::try_from(self)
+            .and_then(|value| toml::to_string_pretty(&value))
+            .map_err(|e| ConfigError::Parse(e.to_string()))
+    }
+}
+
+/// Hex encoded 32 bytes address, with or without `0x` prefix.
+fn is_token_address(token: &str) -> bool {
+    decode(token.trim_start_matches("0x"))
+        .map(|bytes| bytes.len() == 32)
+        .unwrap_or(false)
+}
+
+/// Command line of the service.
+#[derive(Debug, Default, Parser)]
+#[clap(
+    name = "solina-service",
+    version,
+    about = "Solina intents matching service"
+)]
+pub struct Cli {
+    /// TOML config file. Environment variables prefixed with `SOLINA_`, then flags,
+    /// override its fields.
+    #[clap(long, short, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+    /// Prints the resolved config, as TOML, and exits.
+    #[clap(long)]
+    pub print_config: bool,
+    /// Address the JSON-RPC server listens on.
+    #[clap(long, value_name = "ADDRESS")]
+    pub socket_address: Option<SocketAddr>,
+    /// SQLite database file.
+    #[clap(long, value_name = "FILE")]
+    pub storage_file_path: Option<PathBuf>,
+    /// Directory of the circuit artifacts solutions are verified with.
+    #[clap(long, value_name = "DIR")]
+    pub circuit_artifacts_dir: Option<PathBuf>,
+    /// Maximum number of pending intents in the mempool.
+    #[clap(long, value_name = "INTENTS")]
+    pub mempool_capacity: Option<usize>,
+    /// Maximum number of pending intents of a single signer.
+    #[clap(long, value_name = "INTENTS")]
+    pub max_intents_per_signer: Option<usize>,
+    /// Validity of auth credentials, in seconds.
+    #[clap(long, value_name = "SECONDS")]
+    pub auth_credential_timeout: Option<u64>,
+    /// Maximum time a batch collects intents before being sealed, in seconds.
+    #[clap(long, value_name = "SECONDS")]
+    pub batch_sealing_interval: Option<u64>,
+    /// Time solvers have to submit solutions for a sealed batch, in seconds.
+    #[clap(long, value_name = "SECONDS")]
+    pub solving_window: Option<u64>,
+}
+
+impl Cli {
+    /// Loads the config file, overridden by `env` then by flags, and validates the result.
+    pub fn load_config(
+        &self,
+        env: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<SolinaConfig, ConfigError> {
+        let mut config = SolinaConfig::load(self.config.as_deref(), env)?;
+        if let Some(socket_address) = self.socket_address {
+            config.socket_address = socket_address;
+        }
+        if let Some(storage_file_path) = &self.storage_file_path {
+            config.storage_file_path = storage_file_path.clone();
+        }
+        if let Some(circuit_artifacts_dir) = &self.circuit_artifacts_dir {
+            config.circuit_artifacts_dir = circuit_artifacts_dir.clone();
+        }
+        if let Some(mempool_capacity) = self.mempool_capacity {
+            config.mempool_capacity = mempool_capacity;
+        }
+        if let Some(max_intents_per_signer) = self.max_intents_per_signer {
+            config.max_intents_per_signer = max_intents_per_signer;
+        }
+        if let Some(auth_credential_timeout) = self.auth_credential_timeout {
+            config.auth_credential_timeout = auth_credential_timeout;
+        }
+        if let Some(batch_sealing_interval) = self.batch_sealing_interval {
+            config.batch_sealing_interval = batch_sealing_interval;
+        }
+        if let Some(solving_window) = self.solving_window {
+            config.solving_window = solving_window;
+        }
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+/// (De)serializes big integers as decimal strings, which TOML and JSON numbers could not
+/// hold. Integers are accepted as well, when deserializing.
+pub(crate) mod decimal {
+    use num_bigint::BigUint;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use std::str::FromStr;
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    pub(crate) enum Decimal {
+        String(String),
+        Integer(u64),
+    }
+
+    impl Decimal {
+        pub(crate) fn parse<E: Error>(self) -> Result<BigUint, E> {
+            match self {
+                Self::String(value) => BigUint::from_str(&value)
+                    .map_err(|e| E::custom(format!("invalid integer {}: {}", value, e))),
+                Self::Integer(value) => Ok(BigUint::from(value)),
+            }
+        }
+    }
+
+    pub fn serialize<S: Serializer>(value: &BigUint, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&value.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BigUint, D::Error> {
+        Decimal::deserialize(deserializer)?.parse()
+    }
+}
+
+/// (De)serializes price snapshots as tables of decimal prices, indexed by token address.
+mod decimal_prices {
+    use super::decimal::Decimal;
+    use serde::{ser::SerializeMap, Deserialize, Deserializer, Serializer};
+    use solina::price_oracle::PriceSnapshot;
+    use std::collections::BTreeMap;
+
+    pub fn serialize<S: Serializer>(
+        snapshot: &PriceSnapshot,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(snapshot.prices.len()))?;
+        for (token, price) in &snapshot.prices {
+            map.serialize_entry(token, &price.to_string())?;
+        }
+        map.end()
+    }
+
+    /// Token addresses are normalized as in snapshots, lowercase and without `0x` prefix.
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<PriceSnapshot, D::Error> {
+        let prices = BTreeMap::<String, Decimal>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(token, price)| {
+                let token = token.trim_start_matches("0x").to_lowercase();
+                price.parse().map(|price| (token, price))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(PriceSnapshot { prices })
+    }
 }
 
 impl Default for SolinaConfig {
     fn default() -> Self {
         Self {
             mempool_capacity: 5,
+            max_intents_per_signer: 5,
+            mempool_ordering: OrderingPolicy::default(),
+            intent_validation: ValidationRule::default_rules(),
             storage_file_path: PathBuf::from("solina-data.sqlite"),
             socket_address: "127.0.0.1:3000".parse().unwrap(),
             auth_credential_timeout: 360,
+            circuit_artifacts_dir: PathBuf::from("circuit-artifacts"),
+            solution_circuit_id: None,
+            batch_sealing_interval: 60,
+            solving_window: 30,
+            scoring_metric: ScoringMetric::default(),
+            selection_policy: SelectionPolicy::default(),
+            token_prices: PriceSnapshot::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_bigint::BigUint;
+
+    /// Config file of a test, removed once dropped.
+    struct TestFile(PathBuf);
+
+    impl TestFile {
+        fn new(name: &str, contents: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("solina-{}-{}.toml", name, std::process::id()));
+            fs::write(&path, contents).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TestFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
         }
     }
+
+    fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn it_loads_config_layers_by_precedence() {
+        let token = "0x".to_string() + &"AB".repeat(32);
+        let file = TestFile::new(
+            "config-layers",
+            &format!(
+                r#"
+                mempool_capacity = 10
+                solving_window = 20
+                socket_address = "0.0.0.0:4000"
+
+                [token_prices]
+                "{}" = "1000000000000000000000"
+
+                [[intent_validation]]
+                rule = "min_notional_value"
+                min_value = 100
+                "#,
+                token
+            ),
+        );
+        let cli = Cli {
+            config: Some(file.0.clone()),
+            solving_window: Some(40),
+            ..Cli::default()
+        };
+        let config = cli
+            .load_config(env(&[
+                ("SOLINA_SOLVING_WINDOW", "30"),
+                ("SOLINA_STORAGE_FILE_PATH", "/var/lib/solina/data.sqlite"),
+                ("RUST_LOG", "info"),
+            ]))
+            .unwrap();
+
+        assert_eq!(config.mempool_capacity(), 10);
+        assert_eq!(
+            config.solution_circuit_id(),
+            batch_solution_shape(10, 5).id()
+        );
+        assert_eq!(config.socket_address(), "0.0.0.0:4000".parse().unwrap());
+        assert_eq!(
+            config.storage_file_path(),
+            &PathBuf::from("/var/lib/solina/data.sqlite")
+        );
+        assert_eq!(config.solving_window(), 40);
+        assert_eq!(
+            config.batch_sealing_interval(),
+            SolinaConfig::default().batch_sealing_interval()
+        );
+        assert_eq!(
+            config.token_prices().prices.get(&"ab".repeat(32)),
+            Some(&BigUint::from(10_u64).pow(21))
+        );
+        assert_eq!(
+            config.intent_validation(),
+            &[ValidationRule::MinNotionalValue {
+                min_value: BigUint::from(100_u8)
+            }]
+        );
+
+        // printed configs load back to the same config
+        let printed = TestFile::new("config-printed", &config.to_toml().unwrap());
+        let reloaded = SolinaConfig::load(Some(&printed.0), vec![]).unwrap();
+        assert_eq!(reloaded.to_toml().unwrap(), config.to_toml().unwrap());
+    }
+
+    #[test]
+    fn it_rejects_invalid_configs() {
+        let invalid = |vars: &[(&str, &str)]| Cli::default().load_config(env(vars)).unwrap_err();
+        assert!(matches!(
+            invalid(&[("SOLINA_MEMPOOL_CAPACITY", "0")]),
+            ConfigError::Invalid("mempool_capacity", _)
+        ));
+        assert!(matches!(
+            invalid(&[(
+                "SOLINA_INTENT_VALIDATION",
+                r#"[{ rule = "token_denylist", tokens = ["0x01"] }]"#
+            )]),
+            ConfigError::Invalid("intent_validation", _)
+        ));
+        // the circuit of another batch size
+        let circuit_id = batch_solution_shape(5, 2).id();
+        assert!(matches!(
+            invalid(&[
+                ("SOLINA_MEMPOOL_CAPACITY", "10"),
+                ("SOLINA_SOLUTION_CIRCUIT_ID", circuit_id.as_str()),
+            ]),
+            ConfigError::Invalid("solution_circuit_id", _)
+        ));
+        assert!(matches!(
+            invalid(&[("SOLINA_MEMPOOL_CAPACITY", "many")]),
+            ConfigError::Parse(_)
+        ));
+        assert!(matches!(
+            invalid(&[("SOLINA_UNKNOWN_FIELD", "1")]),
+            ConfigError::Parse(_)
+        ));
+    }
 }
@@ -1,65 +1,173 @@
+use crate::types::JsonRpcError;
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
+use axum::Json;
 use serde::Serialize;
+use serde_json::json;
+use solina::IntentId;
 
 pub type Result<T> = core::result::Result<T, Error>;
 
-#[derive(Clone, Debug, Serialize, strum_macros::AsRefStr)]
+/// Errors returned to clients. Variants carry the context clients can act upon, while
+/// internal details are only logged.
+#[derive(Clone, Debug, Serialize, strum_macros::AsRefStr, thiserror::Error)]
 #[serde(tag = "type", content = "data")]
 pub enum Error {
-    // Authentication errors.
-    AuthError,
+    // -- Auth errors.
+    #[error("Authentication failed: {0}")]
+    AuthError(String),
     // -- Request errors.
-    InvalidRequest,
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
     // -- Server errors.
-    FailedToStartService,
+    #[error("Failed to start service: {0}")]
+    FailedToStartService(String),
+    #[error("Storage error: {0}")]
+    StorageError(StorageErrorKind),
+    #[error("Internal error")]
     InternalError,
     // -- Model errors.
-    FailedToStoreIntent,
+    /// The intent, looked up by id or structured hash, is unknown.
+    #[error("Intent not found: {0}")]
+    IntentNotFound(String),
+    /// The intent is already pending, with the given id.
+    #[error("Intent already pending, with id {0}")]
+    DuplicateIntent(IntentId),
+    /// The intent failed the validation `rule`.
+    #[error("Intent rejected by the {rule} rule: {reason}")]
+    IntentRejected { rule: String, reason: String },
+    #[error("Signer reached its limit of pending intents")]
+    SignerLimitReached,
+    #[error("Solution reuses spent nullifiers")]
+    SpentNullifier,
+    // -- Solution errors.
+    #[error("Solver {0} is not registered")]
+    UnregisteredSolver(String),
+    #[error("Batch not found: {0}")]
+    BatchNotFound(String),
+    #[error("Solving window of the batch is closed")]
+    SolvingWindowClosed,
+    #[error("Invalid solution: {0}")]
+    InvalidSolution(String),
+    #[error("Proof verification failed: {0}")]
+    ProofVerificationFailed(String),
 }
 
-impl core::fmt::Display for Error {
-    fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::result::Result<(), core::fmt::Error> {
-        write!(fmt, "{self:?}")
-    }
+/// Storage operation which failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, strum_macros::Display)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum StorageErrorKind {
+    /// Opening, or migrating, the database.
+    Connection,
+    /// Beginning, or committing, a transaction.
+    Transaction,
+    Read,
+    Write,
+    /// Stored data could not be decoded.
+    CorruptedData,
 }
 
-impl std::error::Error for Error {}
-
 impl IntoResponse for Error {
+    /// REST errors are answered with their status, and their JSON-RPC error as body.
     fn into_response(self) -> Response {
-        let mut response = StatusCode::INTERNAL_SERVER_ERROR.into_response();
-        response.extensions_mut().insert(self);
-        response
+        let (status, client_error) = self.client_status_and_error();
+        let body = json!({
+            "kind": client_error.as_ref(),
+            "error": JsonRpcError::from(self),
+        });
+        (status, Json(body)).into_response()
     }
 }
 
 impl Error {
     pub fn client_status_and_error(&self) -> (StatusCode, ClientError) {
-        #[allow(unreachable_patterns)]
         match self {
             // -- Auth errors.
-            Self::AuthError => (StatusCode::INTERNAL_SERVER_ERROR, ClientError::AUTH_ERROR),
+            Self::AuthError(_) => (StatusCode::UNAUTHORIZED, ClientError::AUTH_ERROR),
             // -- Request errors.
-            Self::InvalidRequest => (StatusCode::BAD_REQUEST, ClientError::INVALID_PARAMS),
+            Self::InvalidRequest(_) => (StatusCode::BAD_REQUEST, ClientError::INVALID_PARAMS),
             // -- Server
-            Self::FailedToStartService => (
+            Self::FailedToStartService(_) | Self::InternalError => (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 ClientError::INTERNAL_SERVER_ERROR,
             ),
-            Self::InternalError => (
+            // stored data must be fixed, while other storage failures are transient
+            Self::StorageError(StorageErrorKind::CorruptedData) => (
                 StatusCode::INTERNAL_SERVER_ERROR,
-                ClientError::INTERNAL_SERVER_ERROR,
+                ClientError::SERVICE_ERROR,
             ),
+            Self::StorageError(_) => (StatusCode::SERVICE_UNAVAILABLE, ClientError::SERVICE_ERROR),
             // -- Model
-            Self::FailedToStoreIntent => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                ClientError::SERVICE_ERROR,
+            Self::IntentNotFound(_) => (StatusCode::NOT_FOUND, ClientError::INVALID_PARAMS),
+            Self::DuplicateIntent(_) => (StatusCode::CONFLICT, ClientError::INVALID_PARAMS),
+            Self::IntentRejected { .. } => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                ClientError::INVALID_PARAMS,
             ),
+            Self::SignerLimitReached => {
+                (StatusCode::TOO_MANY_REQUESTS, ClientError::INVALID_PARAMS)
+            }
+            Self::SpentNullifier => (StatusCode::CONFLICT, ClientError::INVALID_PARAMS),
+            // -- Solution
+            Self::UnregisteredSolver(_) => (StatusCode::FORBIDDEN, ClientError::AUTH_ERROR),
+            Self::BatchNotFound(_) => (StatusCode::NOT_FOUND, ClientError::INVALID_PARAMS),
+            Self::SolvingWindowClosed => (StatusCode::CONFLICT, ClientError::INVALID_PARAMS),
+            Self::InvalidSolution(_) | Self::ProofVerificationFailed(_) => {
+                (StatusCode::BAD_REQUEST, ClientError::INVALID_PARAMS)
+            }
         }
     }
 }
 
+impl Error {
+    /// JSON-RPC 2.0 error code, either a standard one, or an implementation defined
+    /// server error code (from -32000 to -32099).
+    pub fn json_rpc_code(&self) -> i64 {
+        match self {
+            // -- Auth errors.
+            Self::AuthError(_) | Self::UnregisteredSolver(_) => json_rpc_codes::AUTH_ERROR,
+            // -- Request errors.
+            Self::InvalidRequest(_) => json_rpc_codes::INVALID_PARAMS,
+            // -- Server
+            Self::FailedToStartService(_) | Self::InternalError => json_rpc_codes::INTERNAL_ERROR,
+            Self::StorageError(_) => json_rpc_codes::SERVICE_ERROR,
+            // -- Model
+            Self::IntentNotFound(_) => json_rpc_codes::INTENT_NOT_FOUND,
+            Self::DuplicateIntent(_) => json_rpc_codes::DUPLICATE_INTENT,
+            Self::IntentRejected { .. } => json_rpc_codes::INTENT_REJECTED,
+            Self::SignerLimitReached => json_rpc_codes::SIGNER_LIMIT_REACHED,
+            Self::SpentNullifier => json_rpc_codes::SPENT_NULLIFIER,
+            // -- Solution
+            Self::BatchNotFound(_) => json_rpc_codes::BATCH_NOT_FOUND,
+            Self::SolvingWindowClosed => json_rpc_codes::SOLVING_WINDOW_CLOSED,
+            Self::InvalidSolution(_) => json_rpc_codes::INVALID_SOLUTION,
+            Self::ProofVerificationFailed(_) => json_rpc_codes::PROOF_VERIFICATION_FAILED,
+        }
+    }
+}
+
+pub mod json_rpc_codes {
+    // -- Standard errors.
+    pub const PARSE_ERROR: i64 = -32700;
+    pub const INVALID_REQUEST: i64 = -32600;
+    pub const METHOD_NOT_FOUND: i64 = -32601;
+    pub const INVALID_PARAMS: i64 = -32602;
+    pub const INTERNAL_ERROR: i64 = -32603;
+    // -- Server errors.
+    pub const AUTH_ERROR: i64 = -32001;
+    pub const SERVICE_ERROR: i64 = -32002;
+    pub const SPENT_NULLIFIER: i64 = -32003;
+    pub const BATCH_NOT_FOUND: i64 = -32004;
+    pub const INVALID_SOLUTION: i64 = -32005;
+    pub const PROOF_VERIFICATION_FAILED: i64 = -32006;
+    pub const SOLVING_WINDOW_CLOSED: i64 = -32007;
+    pub const INTENT_NOT_FOUND: i64 = -32008;
+    pub const DUPLICATE_INTENT: i64 = -32009;
+    pub const SIGNER_LIMIT_REACHED: i64 = -32010;
+    pub const INTENT_REJECTED: i64 = -32011;
+}
+
 #[derive(Debug, strum_macros::AsRefStr)]
 #[allow(non_camel_case_types)]
 pub enum ClientError {
@@ -0,0 +1,248 @@
+use crate::json_rpc_server::AppState;
+use axum::{
+    extract::{Query, State},
+    response::sse::{Event, KeepAlive, Sse},
+};
+use chrono::NaiveDateTime;
+use futures::stream::{self, Stream};
+use hex::encode;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use solina::intent::Intent;
+use std::convert::Infallible;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+/// Number of events buffered for each subscriber. Subscribers lagging further behind miss
+/// the oldest events.
+pub const EVENTS_CHANNEL_CAPACITY: usize = 1024;
+
+pub type EventSender = broadcast::Sender<SolinaEvent>;
+
+/// Pair of (hex encoded) tokens traded by an intent, as its quote and base tokens.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TokenPair {
+    pub quote_token: String,
+    pub base_token: String,
+}
+
+impl TokenPair {
+    pub fn from_intent(intent: &Intent) -> Self {
+        Self {
+            quote_token: encode(intent.inputs.quote_token),
+            base_token: encode(intent.inputs.base_token),
+        }
+    }
+
+    fn contains(&self, token: &str) -> bool {
+        self.quote_token.eq_ignore_ascii_case(token) || self.base_token.eq_ignore_ascii_case(token)
+    }
+}
+
+/// Events pushed to subscribers, as they happen in the worker. Intents are referred to by
+/// their (hex encoded) structured hash, and signers by their (hex encoded) public key.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, strum_macros::AsRefStr)]
+#[serde(tag = "type", rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum SolinaEvent {
+    BatchSealed {
+        batch_id: i32,
+        root: String,
+        sealed_at: NaiveDateTime,
+        token_pairs: Vec<TokenPair>,
+    },
+    SolutionAccepted {
+        batch_id: i32,
+        solver_address: String,
+        score: String,
+    },
+    SolutionRejected {
+        batch_id: i32,
+        solver_address: String,
+        reason: String,
+    },
+    WinnerSelected {
+        batch_id: i32,
+        solution_ids: Vec<i32>,
+        solver_addresses: Vec<String>,
+    },
+    IntentFilled {
+        batch_id: i32,
+        intent: String,
+        signer: String,
+        token_pair: TokenPair,
+        quote_amount_swapped: String,
+        base_amount_received: String,
+    },
+    IntentExpired {
+        intent: String,
+        signer: String,
+        token_pair: TokenPair,
+    },
+}
+
+impl SolinaEvent {
+    fn addresses(&self) -> Vec<&str> {
+        match self {
+            Self::BatchSealed { .. } => vec![],
+            Self::SolutionAccepted { solver_address, .. }
+            | Self::SolutionRejected { solver_address, .. } => vec![solver_address],
+            Self::WinnerSelected {
+                solver_addresses, ..
+            } => solver_addresses.iter().map(String::as_str).collect(),
+            Self::IntentFilled { signer, .. } | Self::IntentExpired { signer, .. } => vec![signer],
+        }
+    }
+
+    fn token_pairs(&self) -> Vec<&TokenPair> {
+        match self {
+            Self::BatchSealed { token_pairs, .. } => token_pairs.iter().collect(),
+            Self::SolutionAccepted { .. }
+            | Self::SolutionRejected { .. }
+            | Self::WinnerSelected { .. } => vec![],
+            Self::IntentFilled { token_pair, .. } | Self::IntentExpired { token_pair, .. } => {
+                vec![token_pair]
+            }
+        }
+    }
+}
+
+/// Subscription filters, all optional. `event_types` is a comma separated list of event
+/// types, and a token pair is given by `token_a` and `token_b`, in any order. Events not
+/// involving any address, or token, are only filtered by type.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct EventFilter {
+    pub event_types: Option<String>,
+    pub address: Option<String>,
+    pub token_a: Option<String>,
+    pub token_b: Option<String>,
+}
+
+impl EventFilter {
+    pub fn matches(&self, event: &SolinaEvent) -> bool {
+        if let Some(event_types) = &self.event_types {
+            if !event_types
+                .split(',')
+                .any(|event_type| event_type.trim() == event.as_ref())
+            {
+                return false;
+            }
+        }
+
+        let addresses = event.addresses();
+        if let Some(address) = &self.address {
+            let address = address.trim_start_matches("0x");
+            if !addresses.is_empty()
+                && !addresses
+                    .iter()
+                    .any(|a| a.trim_start_matches("0x").eq_ignore_ascii_case(address))
+            {
+                return false;
+            }
+        }
+
+        let token_pairs = event.token_pairs();
+        let tokens = [&self.token_a, &self.token_b]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        token_pairs.is_empty()
+            || token_pairs
+                .iter()
+                .any(|pair| tokens.iter().all(|token| pair.contains(token)))
+    }
+}
+
+/// Server-Sent Events stream of the worker events matching the query filters.
+pub(crate) async fn events_handler(
+    State(app_state): State<AppState>,
+    Query(filter): Query<EventFilter>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    info!("New subscription to events, with filter: {:?}", filter);
+    let receiver = app_state.worker.subscribe();
+
+    let stream = stream::unfold((receiver, filter), |(mut receiver, filter)| async move {
+        loop {
+            let event = match receiver.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Subscriber lagged behind, skipping {} events", skipped);
+                    continue;
+                }
+                Err(RecvError::Closed) => return None,
+            };
+            if !filter.matches(&event) {
+                continue;
+            }
+            match Event::default().event(event.as_ref()).json_data(&event) {
+                Ok(sse_event) => return Some((Ok(sse_event), (receiver, filter))),
+                Err(e) => error!("Failed to serialize event, with error: {}", e),
+            }
+        }
+    });
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn intent_expired(signer: &str, quote_token: &str, base_token: &str) -> SolinaEvent {
+        SolinaEvent::IntentExpired {
+            intent: "00".to_string(),
+            signer: signer.to_string(),
+            token_pair: TokenPair {
+                quote_token: quote_token.to_string(),
+                base_token: base_token.to_string(),
+            },
+        }
+    }
+
+    #[test]
+    fn it_works_event_filter() {
+        let event = intent_expired("ab", "01", "02");
+        let batch_sealed = SolinaEvent::BatchSealed {
+            batch_id: 1,
+            root: "00".to_string(),
+            sealed_at: NaiveDateTime::default(),
+            token_pairs: vec![],
+        };
+
+        assert!(EventFilter::default().matches(&event));
+
+        let by_type = EventFilter {
+            event_types: Some("batch_sealed, intent_expired".to_string()),
+            ..Default::default()
+        };
+        assert!(by_type.matches(&event));
+        assert!(by_type.matches(&batch_sealed));
+        let by_type = EventFilter {
+            event_types: Some("intent_filled".to_string()),
+            ..Default::default()
+        };
+        assert!(!by_type.matches(&event));
+
+        let by_address = EventFilter {
+            address: Some("0xAB".to_string()),
+            ..Default::default()
+        };
+        assert!(by_address.matches(&event));
+        assert!(!by_address.matches(&intent_expired("cd", "01", "02")));
+        // batch events do not involve any address
+        assert!(by_address.matches(&batch_sealed));
+
+        // token pairs match in any order
+        let by_pair = EventFilter {
+            token_a: Some("02".to_string()),
+            token_b: Some("01".to_string()),
+            ..Default::default()
+        };
+        assert!(by_pair.matches(&event));
+        assert!(!by_pair.matches(&intent_expired("ab", "01", "03")));
+        let by_token = EventFilter {
+            token_a: Some("03".to_string()),
+            ..Default::default()
+        };
+        assert!(by_token.matches(&intent_expired("ab", "01", "03")));
+    }
+}
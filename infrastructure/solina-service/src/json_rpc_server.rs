@@ -1,35 +1,45 @@
 use log::{error, info};
-use std::sync::{Arc, RwLock};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::future::Future;
 
-use crate::error::{Error, Result};
+use crate::error::{json_rpc_codes, Error, Result};
 
 use axum::{
+    body::Bytes,
     extract::FromRef,
-    extract::{Json, State},
-    routing::get,
+    extract::{Json, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
     Router,
 };
 
 use crate::{
-    auth_middleware::EthereumAuthMiddlewareLayer,
+    auth_challenge::{extract_address, extract_signature},
+    auth_middleware::{authenticate, EthereumAuthMiddlewareLayer},
+    batch_lifecycle::run_batch_lifecycle,
+    events::events_handler,
+    reader::SolinaReader,
     types::{
         GetAuthCredentialsRequest, GetAuthCredentialsResponse, GetBatchIntentsRequest,
-        GetBatchIntentsResponse, GetIntentRequest, GetIntentResponse, RegisterSolverRequest,
-        RegisterSolverResponse, StoreIntentRequest, StoreIntentResponse,
+        GetBatchIntentsResponse, GetBatchOutcomeRequest, GetBatchOutcomeResponse, GetBatchRequest,
+        GetBatchResponse, GetIntentRequest, GetIntentResponse, GetIntentStatusRequest,
+        GetIntentStatusResponse, GetLatestSealedBatchRequest, GetVerifierDataRequest,
+        GetVerifierDataResponse, JsonRpcError, JsonRpcRequest, JsonRpcResponse, ListIntentsRequest,
+        ListIntentsResponse, RegisterSolverRequest, RegisterSolverResponse, StoreIntentRequest,
+        StoreIntentResponse, SubmitSolutionRequest, SubmitSolutionResponse, JSON_RPC_VERSION,
     },
-    worker::SolinaWorker,
+    worker::{SolinaWorker, WorkerHandle},
 };
 
 #[derive(Clone, FromRef)]
 pub struct AppState {
-    pub(crate) solina_worker: Arc<RwLock<SolinaWorker>>,
+    pub(crate) worker: WorkerHandle,
+    pub(crate) reader: SolinaReader,
 }
 
-pub fn routes(solina_worker: SolinaWorker) -> Router {
-    let app_state = AppState {
-        solina_worker: Arc::new(RwLock::new(solina_worker)),
-    };
-
+pub fn routes(app_state: AppState) -> Router {
     Router::new()
         .route(
             "/store_intent",
@@ -39,11 +49,27 @@ pub fn routes(solina_worker: SolinaWorker) -> Router {
             "/register_solver",
             get(get_auth_credentials_handler).post(register_solver_handler),
         )
+        .route(
+            "/submit_solution",
+            get(get_auth_credentials_handler).post(submit_solution_handler),
+        )
         .layer(EthereumAuthMiddlewareLayer {
             app_state: app_state.clone(),
         })
         .route("/get_intent", get(get_intent_handler))
         .route("/get_batch_intents", get(get_batch_intents_handler))
+        // requests of the routes below are read from the query string
+        .route("/get_intent_status", get(get_intent_status_handler))
+        .route("/list_intents", get(list_intents_handler))
+        .route("/get_verifier_data", get(get_verifier_data_handler))
+        .route("/get_batch", get(get_batch_handler))
+        .route(
+            "/get_latest_sealed_batch",
+            get(get_latest_sealed_batch_handler),
+        )
+        .route("/get_batch_outcome", get(get_batch_outcome_handler))
+        .route("/events", get(events_handler))
+        .route("/", post(json_rpc_handler))
         .with_state(app_state)
 }
 
@@ -56,8 +82,13 @@ pub async fn run_json_rpc(solina_worker: SolinaWorker) -> Result<()> {
             bind = false;
             axum::Server::try_bind(&"127.0.0.1:0".parse().unwrap())
         })
-        .map_err(|_| Error::FailedToStartService)?;
-    let server = server.serve(routes(solina_worker).into_make_service());
+        .map_err(|e| Error::FailedToStartService(format!("Failed to bind: {}", e)))?;
+    let app_state = AppState {
+        reader: SolinaReader::new(solina_worker.config().clone())?,
+        worker: solina_worker.spawn()?,
+    };
+    tokio::spawn(run_batch_lifecycle(app_state.worker.clone()));
+    let server = server.serve(routes(app_state).into_make_service());
 
     let bind_addr = if bind {
         socket_address
@@ -66,84 +97,431 @@ pub async fn run_json_rpc(solina_worker: SolinaWorker) -> Result<()> {
     };
     info!("Started JSON RPC service at {:?}", bind_addr);
 
-    server.await.map_err(|_| Error::FailedToStartService)?;
+    server
+        .await
+        .map_err(|e| Error::FailedToStartService(e.to_string()))?;
 
     Ok(())
 }
 
+/// JSON-RPC 2.0 endpoint, for single and batch requests. Methods are named after the
+/// routes above, and authenticated methods take `address` and `signature` params.
+async fn json_rpc_handler(State(app_state): State<AppState>, body: Bytes) -> Response {
+    let response = match serde_json::from_slice::<Value>(&body) {
+        Ok(Value::Array(requests)) if !requests.is_empty() => {
+            // requests are handled in order, for writes to be applied in that order
+            let mut responses = vec![];
+            for request in requests {
+                responses.extend(handle_json_rpc_request(&app_state, request).await);
+            }
+            (!responses.is_empty()).then(|| serde_json::to_value(responses))
+        }
+        Ok(Value::Array(_)) => Some(serde_json::to_value(JsonRpcResponse::new(
+            Value::Null,
+            Err(JsonRpcError::new(
+                json_rpc_codes::INVALID_REQUEST,
+                "Empty batch request".to_string(),
+            )),
+        ))),
+        Ok(request) => handle_json_rpc_request(&app_state, request)
+            .await
+            .map(serde_json::to_value),
+        Err(e) => Some(serde_json::to_value(JsonRpcResponse::new(
+            Value::Null,
+            Err(JsonRpcError::new(
+                json_rpc_codes::PARSE_ERROR,
+                format!("Failed to parse request: {}", e),
+            )),
+        ))),
+    };
+
+    match response {
+        Some(Ok(response)) => Json(response).into_response(),
+        Some(Err(e)) => {
+            error!("Failed to serialize JSON RPC response, with error: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+        // only notifications were received
+        None => StatusCode::NO_CONTENT.into_response(),
+    }
+}
+
+async fn handle_json_rpc_request(app_state: &AppState, request: Value) -> Option<JsonRpcResponse> {
+    let request = match serde_json::from_value::<JsonRpcRequest>(request) {
+        Ok(request) if request.jsonrpc == JSON_RPC_VERSION => request,
+        _ => {
+            return Some(JsonRpcResponse::new(
+                Value::Null,
+                Err(JsonRpcError::new(
+                    json_rpc_codes::INVALID_REQUEST,
+                    "Invalid JSON RPC 2.0 request".to_string(),
+                )),
+            ))
+        }
+    };
+
+    info!("New JSON RPC request for method: {}", request.method);
+    let result = dispatch(app_state, &request.method, request.params).await;
+    // notifications are processed, but never answered
+    request.id.map(|id| JsonRpcResponse::new(id, result))
+}
+
+async fn dispatch(
+    app_state: &AppState,
+    method: &str,
+    params: Value,
+) -> core::result::Result<Value, JsonRpcError> {
+    let AppState { worker, reader } = app_state;
+    match method {
+        "store_intent" => {
+            authenticate_params(app_state, &params).await?;
+            call(params, |request| {
+                worker.run(|worker| worker.handle_post_store_intent_request(request))
+            })
+            .await
+        }
+        "get_intent" => {
+            call(params, |request| {
+                reader.run(|reader| reader.handle_get_intent_request(request))
+            })
+            .await
+        }
+        "get_batch_intents" => {
+            call(params, |request| {
+                reader.run(|reader| reader.handle_get_batch_intents_request(request))
+            })
+            .await
+        }
+        "get_intent_status" => {
+            call(params, |request| {
+                reader.run(|reader| reader.handle_get_intent_status_request(request))
+            })
+            .await
+        }
+        "list_intents" => {
+            call(params, |request| {
+                reader.run(|reader| reader.handle_list_intents_request(request))
+            })
+            .await
+        }
+        "get_verifier_data" => {
+            call(params, |request| {
+                reader.run(|reader| reader.handle_get_verifier_data_request(request))
+            })
+            .await
+        }
+        "get_batch" => {
+            call(params, |request| {
+                reader.run(|reader| reader.handle_get_batch_request(request))
+            })
+            .await
+        }
+        "get_latest_sealed_batch" => {
+            call(params, |request| {
+                reader.run(|reader| reader.handle_get_latest_sealed_batch_request(request))
+            })
+            .await
+        }
+        "get_batch_outcome" => {
+            call(params, |request| {
+                reader.run(|reader| reader.handle_get_batch_outcome_request(request))
+            })
+            .await
+        }
+        "get_auth_credentials" => {
+            call(params, |request| {
+                worker.run(|worker| worker.handle_get_auth_credentials_request(request))
+            })
+            .await
+        }
+        "register_solver" => {
+            authenticate_params(app_state, &params).await?;
+            call(params, |request| {
+                worker.run(|worker| worker.handle_solver_registration(request))
+            })
+            .await
+        }
+        "submit_solution" => {
+            authenticate_params(app_state, &params).await?;
+            call(params, |request| {
+                worker.run(|worker| worker.handle_submit_solution_request(request))
+            })
+            .await
+        }
+        _ => Err(JsonRpcError::new(
+            json_rpc_codes::METHOD_NOT_FOUND,
+            format!("Method not found: {}", method),
+        )),
+    }
+}
+
+/// Deserializes `params` into the request of a handler, and serializes its response.
+async fn call<T, R, F>(
+    params: Value,
+    handler: impl FnOnce(T) -> F,
+) -> core::result::Result<Value, JsonRpcError>
+where
+    T: DeserializeOwned,
+    R: Serialize,
+    F: Future<Output = Result<R>>,
+{
+    let request = serde_json::from_value(params).map_err(|e| {
+        JsonRpcError::new(
+            json_rpc_codes::INVALID_PARAMS,
+            format!("Invalid params: {}", e),
+        )
+    })?;
+    let response = handler(request).await?;
+    serde_json::to_value(response).map_err(|e| {
+        error!("Failed to serialize response, with error: {}", e);
+        JsonRpcError::from(Error::InternalError)
+    })
+}
+
+async fn authenticate_params(app_state: &AppState, params: &Value) -> Result<()> {
+    let address = extract_address(params)?;
+    let signature = extract_signature(params)?;
+    authenticate(app_state, address, signature).await
+}
+
 async fn store_intent_handler(
-    State(solina_worker): State<Arc<RwLock<SolinaWorker>>>,
+    State(worker): State<WorkerHandle>,
     Json(request): Json<StoreIntentRequest>,
-) -> Json<Result<StoreIntentResponse>> {
+) -> Result<Json<StoreIntentResponse>> {
     info!("New POST request to submit intent: {:?}", request);
-    let response = solina_worker
-        .write()
-        .expect("Failed to acquire lock")
-        .handle_post_store_intent_request(request);
-    Json(response)
+    worker
+        .run(|worker| worker.handle_post_store_intent_request(request))
+        .await
+        .map(Json)
 }
 
 async fn get_intent_handler(
-    State(solina_worker): State<Arc<RwLock<SolinaWorker>>>,
+    State(reader): State<SolinaReader>,
     Json(request): Json<GetIntentRequest>,
-) -> Json<Result<GetIntentResponse>> {
+) -> Result<Json<GetIntentResponse>> {
     info!("New GET request for intent with id: {}", request.id);
-    let response = solina_worker
-        .write()
-        .expect("Failed to acquire lock")
-        .handle_get_intent_request(request);
-    Json(response)
+    reader
+        .run(|reader| reader.handle_get_intent_request(request))
+        .await
+        .map(Json)
 }
 
 async fn get_batch_intents_handler(
-    State(solina_worker): State<Arc<RwLock<SolinaWorker>>>,
+    State(reader): State<SolinaReader>,
     Json(request): Json<GetBatchIntentsRequest>,
-) -> Json<Result<GetBatchIntentsResponse>> {
+) -> Result<Json<GetBatchIntentsResponse>> {
     info!(
         "New GET request for batch intents with ids: {:?}",
         request.ids
     );
-    let response = solina_worker
-        .write()
-        .expect("Failed to acquire lock")
-        .handle_get_batch_intents_request(request);
-    Json(response)
+    reader
+        .run(|reader| reader.handle_get_batch_intents_request(request))
+        .await
+        .map(Json)
+}
+
+async fn get_verifier_data_handler(
+    State(reader): State<SolinaReader>,
+    Query(request): Query<GetVerifierDataRequest>,
+) -> Result<Json<GetVerifierDataResponse>> {
+    info!(
+        "New GET request for verifier data of circuit: {}",
+        request.circuit_id
+    );
+    reader
+        .run(|reader| reader.handle_get_verifier_data_request(request))
+        .await
+        .map(Json)
+}
+
+async fn get_batch_handler(
+    State(reader): State<SolinaReader>,
+    Query(request): Query<GetBatchRequest>,
+) -> Result<Json<GetBatchResponse>> {
+    info!("New GET request for batch: {}", request.batch_id);
+    reader
+        .run(|reader| reader.handle_get_batch_request(request))
+        .await
+        .map(Json)
+}
+
+async fn get_latest_sealed_batch_handler(
+    State(reader): State<SolinaReader>,
+    Query(request): Query<GetLatestSealedBatchRequest>,
+) -> Result<Json<GetBatchResponse>> {
+    info!("New GET request for the latest sealed batch");
+    reader
+        .run(|reader| reader.handle_get_latest_sealed_batch_request(request))
+        .await
+        .map(Json)
+}
+
+async fn get_batch_outcome_handler(
+    State(reader): State<SolinaReader>,
+    Query(request): Query<GetBatchOutcomeRequest>,
+) -> Result<Json<GetBatchOutcomeResponse>> {
+    info!(
+        "New GET request for the outcome of batch: {}",
+        request.batch_id
+    );
+    reader
+        .run(|reader| reader.handle_get_batch_outcome_request(request))
+        .await
+        .map(Json)
+}
+
+async fn get_intent_status_handler(
+    State(reader): State<SolinaReader>,
+    Query(request): Query<GetIntentStatusRequest>,
+) -> Result<Json<GetIntentStatusResponse>> {
+    info!(
+        "New GET request for the status of intent: {:?}",
+        request
+            .id
+            .map(|id| id.to_string())
+            .or(request.structured_hash.clone())
+    );
+    reader
+        .run(|reader| reader.handle_get_intent_status_request(request))
+        .await
+        .map(Json)
+}
+
+async fn list_intents_handler(
+    State(reader): State<SolinaReader>,
+    Query(request): Query<ListIntentsRequest>,
+) -> Result<Json<ListIntentsResponse>> {
+    info!(
+        "New GET request for the intents of signer: {}",
+        request.signer
+    );
+    reader
+        .run(|reader| reader.handle_list_intents_request(request))
+        .await
+        .map(Json)
 }
 
 async fn get_auth_credentials_handler(
-    State(solina_worker): State<Arc<RwLock<SolinaWorker>>>,
+    State(worker): State<WorkerHandle>,
     Json(request): Json<GetAuthCredentialsRequest>,
-) -> Json<Result<GetAuthCredentialsResponse>> {
+) -> Result<Json<GetAuthCredentialsResponse>> {
     info!(
         "New GET request for authentication credentials, for address: {}",
         request.address
     );
-    let write_lock = solina_worker.write();
-    if let Err(e) = write_lock {
-        error!("Failed to acquire worker lock, with error: {}", e);
-        return Json(Err(Error::InternalError));
-    } else {
-        let response = write_lock
-            .unwrap()
-            .handle_get_auth_credentials_request(request);
-        Json(response)
-    }
+    worker
+        .run(|worker| worker.handle_get_auth_credentials_request(request))
+        .await
+        .map(Json)
 }
 
 async fn register_solver_handler(
-    State(solina_worker): State<Arc<RwLock<SolinaWorker>>>,
+    State(worker): State<WorkerHandle>,
     Json(request): Json<RegisterSolverRequest>,
-) -> Json<Result<RegisterSolverResponse>> {
+) -> Result<Json<RegisterSolverResponse>> {
     info!(
         "New POST request for solver registration, {}",
         request.solver_address
     );
-    let write_lock = solina_worker.write();
-    if let Err(e) = write_lock {
-        error!("Failed to acquire worker lock, with error: {}", e);
-        return Json(Err(Error::InternalError));
-    } else {
-        let response = write_lock.unwrap().handle_solver_registration(request);
-        Json(response)
+    worker
+        .run(|worker| worker.handle_solver_registration(request))
+        .await
+        .map(Json)
+}
+
+async fn submit_solution_handler(
+    State(worker): State<WorkerHandle>,
+    Json(request): Json<SubmitSolutionRequest>,
+) -> Result<Json<SubmitSolutionResponse>> {
+    info!(
+        "New POST request for solution submission, for batch {} by solver {}",
+        request.batch_id, request.address
+    );
+    worker
+        .run(|worker| worker.handle_submit_solution_request(request))
+        .await
+        .map(Json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn it_works_json_rpc_invalid_params() {
+        let result =
+            call::<GetIntentRequest, GetIntentResponse, _>(json!({"ids": [1]}), |_| async {
+                unreachable!("Handler called with invalid params")
+            })
+            .await;
+        assert_eq!(result.unwrap_err().code, json_rpc_codes::INVALID_PARAMS);
+    }
+
+    #[test]
+    fn it_answers_null_ids() {
+        let request = |request: Value| serde_json::from_value::<JsonRpcRequest>(request).unwrap();
+        let notification = request(json!({"jsonrpc": "2.0", "method": "get_intent"}));
+        assert_eq!(notification.id, None);
+        let null_id = request(json!({"jsonrpc": "2.0", "method": "get_intent", "id": null}));
+        assert_eq!(null_id.id, Some(Value::Null));
+    }
+
+    #[test]
+    fn it_works_json_rpc_error_response() {
+        let error = Error::BatchNotFound("7".to_string());
+        let response = JsonRpcResponse::new(json!(1), Err(error.into()));
+        assert_eq!(
+            serde_json::to_value(response).unwrap(),
+            json!({
+                "jsonrpc": "2.0",
+                "error": {
+                    "code": json_rpc_codes::BATCH_NOT_FOUND,
+                    "message": "Batch not found: 7",
+                    "data": {"type": "BatchNotFound", "data": "7"}
+                },
+                "id": 1
+            })
+        );
+    }
+
+    #[test]
+    fn it_works_query_requests() {
+        let query = |uri: &str| uri.parse::<axum::http::Uri>().unwrap();
+
+        let Query(request) = Query::<ListIntentsRequest>::try_from_uri(&query(
+            "/list_intents?signer=0x01&status=pending&limit=10",
+        ))
+        .unwrap();
+        assert_eq!(request.signer, "0x01");
+        assert_eq!(request.status.as_deref(), Some("pending"));
+        assert_eq!((request.offset, request.limit), (None, Some(10)));
+
+        let Query(request) =
+            Query::<GetIntentStatusRequest>::try_from_uri(&query("/get_intent_status?id=7"))
+                .unwrap();
+        assert_eq!((request.id, request.structured_hash), (Some(7), None));
+
+        assert!(
+            Query::<GetBatchOutcomeRequest>::try_from_uri(&query("/get_batch_outcome")).is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn it_works_rest_error_response() {
+        let response = Error::AuthError("Challenge has timed out".to_string()).into_response();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(
+            serde_json::from_slice::<Value>(&body).unwrap(),
+            json!({
+                "kind": "AUTH_ERROR",
+                "error": {
+                    "code": json_rpc_codes::AUTH_ERROR,
+                    "message": "Authentication failed: Challenge has timed out",
+                    "data": {"type": "AuthError", "data": "Challenge has timed out"}
+                }
+            })
+        );
     }
 }
@@ -1,8 +1,12 @@
 pub mod auth_challenge;
 pub mod auth_middleware;
+pub mod batch_lifecycle;
 pub mod config;
 pub mod error;
+pub mod events;
 pub mod json_rpc_server;
 pub mod mempool;
+pub mod reader;
 pub mod types;
+pub mod validation;
 pub mod worker;
@@ -1,11 +1,25 @@
-use solina_service::{config::SolinaConfig, error::Result};
+use clap::Parser;
+use solina_service::{
+    config::Cli,
+    error::{Error, Result},
+};
 use solina_service::{json_rpc_server::run_json_rpc, worker::SolinaWorker};
 
 #[tokio::main]
 async fn main() -> Result<()> {
     env_logger::init();
 
-    let solina_config = SolinaConfig::default();
+    let cli = Cli::parse();
+    let solina_config = cli
+        .load_config(std::env::vars())
+        .map_err(|e| Error::FailedToStartService(e.to_string()))?;
+    if cli.print_config {
+        let config = solina_config
+            .to_toml()
+            .map_err(|e| Error::FailedToStartService(e.to_string()))?;
+        print!("{}", config);
+        return Ok(());
+    }
 
     let solina_worker = SolinaWorker::new(solina_config).expect("Failed to start a Solina worker");
     run_json_rpc(solina_worker).await?;
@@ -1,37 +1,281 @@
-use solina::intent::Intent;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use solina::{
+    intent::Intent,
+    price_oracle::PriceOracle,
+    structured_hash::{StructuredHash, StructuredHashInterface},
+    IntentId, PublicKey,
+};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
-pub type IntentId = i64;
+/// Order in which pending intents are taken from the mempool, when a batch is sealed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderingPolicy {
+    /// Intents are batched in submission order.
+    #[default]
+    Fifo,
+    /// Intents expiring the soonest are batched first.
+    ExpiryFirst,
+    /// Intents swapping the most liquidity, at reference prices, are batched first, as
+    /// fees are split in proportion to the liquidity swapped.
+    FeePriority,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MempoolError {
+    /// The intent is already pending, with the given id.
+    DuplicateIntent(IntentId),
+    /// The signer has reached its maximum number of pending intents.
+    SignerLimitReached,
+    ExpiredIntent,
+}
 
+/// Pending intents, indexed by id. Structured hashes and signers are indexed as well, to
+/// reject duplicates and enforce the per signer limit on insertion.
 #[derive(Default)]
 pub struct SolinaMempool {
-    // TODO: other data structures might be more suitable for our purposes
-    pub(crate) mempool_data: Vec<(IntentId, Intent)>,
+    intents: BTreeMap<IntentId, Intent>,
+    by_structured_hash: HashMap<StructuredHash, IntentId>,
+    by_signer: HashMap<PublicKey, BTreeSet<IntentId>>,
     mempool_capacity: usize,
+    max_intents_per_signer: usize,
+    ordering: OrderingPolicy,
 }
 
 impl SolinaMempool {
-    pub fn new(mempool_capacity: usize) -> Self {
+    pub fn new(
+        mempool_capacity: usize,
+        max_intents_per_signer: usize,
+        ordering: OrderingPolicy,
+    ) -> Self {
         Self {
-            mempool_data: Vec::with_capacity(mempool_capacity),
             mempool_capacity,
+            max_intents_per_signer,
+            ordering,
+            ..Default::default()
         }
     }
 
+    /// Adds a new pending intent, unless it has already expired at `now`, is already
+    /// pending, or its signer reached its limit of pending intents.
     pub fn insert(
         &mut self,
         intent_id: IntentId,
         intent: Intent,
-    ) -> Option<Vec<(IntentId, Intent)>> {
-        self.mempool_data.push((intent_id, intent));
-        if self.mempool_data.len() == self.mempool_capacity {
-            let mempool_data = std::mem::take(&mut self.mempool_data);
-            self.mempool_data.clear();
-            return Some(mempool_data);
+        now: NaiveDateTime,
+    ) -> Result<(), MempoolError> {
+        if intent.is_expired_at(&now) {
+            return Err(MempoolError::ExpiredIntent);
+        }
+        let structured_hash = intent.structured_hash();
+        if let Some(id) = self.by_structured_hash.get(&structured_hash) {
+            return Err(MempoolError::DuplicateIntent(*id));
+        }
+        let signer_intents = self.by_signer.get(&intent.public_key);
+        if signer_intents.map(BTreeSet::len).unwrap_or(0) >= self.max_intents_per_signer {
+            return Err(MempoolError::SignerLimitReached);
+        }
+
+        self.index(intent_id, intent);
+        Ok(())
+    }
+
+    /// Adds back previously accepted intents, without checking them again, when recovering
+    /// the mempool on startup, or after a failed sealing.
+    pub fn restore(&mut self, intents: Vec<(IntentId, Intent)>) {
+        for (intent_id, intent) in intents {
+            self.index(intent_id, intent);
+        }
+    }
+
+    fn index(&mut self, intent_id: IntentId, intent: Intent) {
+        self.by_structured_hash
+            .insert(intent.structured_hash(), intent_id);
+        self.by_signer
+            .entry(intent.public_key)
+            .or_default()
+            .insert(intent_id);
+        self.intents.insert(intent_id, intent);
+    }
+
+    pub fn remove(&mut self, intent_id: IntentId) -> Option<Intent> {
+        let intent = self.intents.remove(&intent_id)?;
+        self.by_structured_hash.remove(&intent.structured_hash());
+        if let Some(ids) = self.by_signer.get_mut(&intent.public_key) {
+            ids.remove(&intent_id);
+            if ids.is_empty() {
+                self.by_signer.remove(&intent.public_key);
+            }
+        }
+        Some(intent)
+    }
+
+    /// Removes, and returns, the intents expired at `now`.
+    pub fn evict_expired(&mut self, now: NaiveDateTime) -> Vec<(IntentId, Intent)> {
+        let expired_ids = self
+            .intents
+            .iter()
+            .filter(|(_, intent)| intent.is_expired_at(&now))
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        expired_ids
+            .into_iter()
+            .filter_map(|id| self.remove(id).map(|intent| (id, intent)))
+            .collect()
+    }
+
+    pub fn get(&self, intent_id: IntentId) -> Option<&Intent> {
+        self.intents.get(&intent_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.intents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.intents.is_empty()
+    }
+
+    /// Whether enough intents are pending to seal a batch.
+    pub fn is_full(&self) -> bool {
+        self.intents.len() >= self.mempool_capacity
+    }
+
+    /// Removes, and returns, up to `mempool_capacity` pending intents, picked in the order
+    /// of the configured policy. Ties are broken in submission order, and the intents left
+    /// out remain pending. The `oracle` prices intents for fee priority.
+    pub fn take(&mut self, oracle: &impl PriceOracle) -> Vec<(IntentId, Intent)> {
+        let mut ids = self.intents.keys().copied().collect::<Vec<_>>();
+        match self.ordering {
+            OrderingPolicy::Fifo => {}
+            OrderingPolicy::ExpiryFirst => ids.sort_by_key(|id| self.intents[id].expiry_date),
+            OrderingPolicy::FeePriority => ids.sort_by_cached_key(|id| {
+                let intent = &self.intents[id];
+                std::cmp::Reverse(
+                    &intent.inputs.quote_amount
+                        * oracle.get_current_price(intent.inputs.quote_token),
+                )
+            }),
         }
-        None
+        ids.truncate(self.mempool_capacity);
+        ids.into_iter()
+            .filter_map(|id| self.remove(id).map(|intent| (id, intent)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use num_bigint::BigUint;
+    use solina::{
+        intent::{IntentConstraints, IntentInputs, TradeDirection},
+        price_oracle::PriceSnapshot,
+        Signature,
+    };
+
+    fn date(day: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2023, 11, day)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+    }
+
+    fn intent(signer: u8, tokens: (u8, u8), quote_amount: u64, expiry_day: u32) -> Intent {
+        Intent::new(
+            [signer; 32],
+            IntentInputs::new(
+                [tokens.0; 32],
+                [tokens.1; 32],
+                BigUint::from(quote_amount),
+                TradeDirection::Sell,
+            ),
+            IntentConstraints::new(BigUint::from(0_u8)),
+            Signature([0u8; 64]),
+            date(expiry_day),
+        )
     }
 
-    pub fn rollback(&mut self) -> Option<(IntentId, Intent)> {
-        self.mempool_data.pop()
+    fn ids(intents: &[(IntentId, impl Sized)]) -> Vec<IntentId> {
+        intents.iter().map(|(id, _)| *id).collect()
+    }
+
+    #[test]
+    fn it_works_mempool_indexes() {
+        let mut mempool = SolinaMempool::new(3, 2, OrderingPolicy::Fifo);
+        let now = date(1);
+
+        mempool.insert(1, intent(1, (1, 2), 10, 10), now).unwrap();
+        mempool.insert(2, intent(2, (2, 1), 20, 10), now).unwrap();
+        mempool.insert(3, intent(1, (1, 3), 30, 10), now).unwrap();
+        assert!(mempool.is_full());
+
+        assert_eq!(
+            mempool.insert(4, intent(2, (2, 1), 20, 10), now),
+            Err(MempoolError::DuplicateIntent(2))
+        );
+        assert_eq!(
+            mempool.insert(4, intent(1, (1, 2), 40, 10), now),
+            Err(MempoolError::SignerLimitReached)
+        );
+        assert_eq!(
+            mempool.insert(4, intent(3, (1, 2), 40, 1), date(2)),
+            Err(MempoolError::ExpiredIntent)
+        );
+
+        assert!(mempool.remove(1).is_some());
+        assert!(mempool.get(1).is_none());
+        // removing an intent frees its structured hash and a slot of its signer
+        mempool.insert(4, intent(1, (1, 2), 10, 10), now).unwrap();
+        assert_eq!(mempool.len(), 3);
+    }
+
+    #[test]
+    fn it_works_mempool_ordering() {
+        let now = date(1);
+        let prices = PriceSnapshot {
+            prices: BTreeMap::from([
+                (hex::encode([1; 32]), BigUint::from(1_u8)),
+                (hex::encode([2; 32]), BigUint::from(5_u8)),
+            ]),
+        };
+        let pending = [
+            intent(1, (1, 2), 30, 5),
+            intent(2, (2, 1), 10, 9),
+            intent(3, (1, 2), 20, 3),
+        ];
+
+        // the policy decides which intents fit in a batch, the others remain pending
+        for (ordering, batched, remaining) in [
+            (OrderingPolicy::Fifo, vec![1, 2], 3),
+            (OrderingPolicy::ExpiryFirst, vec![3, 1], 2),
+            (OrderingPolicy::FeePriority, vec![2, 1], 3),
+        ] {
+            let mut mempool = SolinaMempool::new(2, 3, ordering);
+            for (id, intent) in pending.iter().enumerate() {
+                mempool
+                    .insert(id as IntentId + 1, intent.clone(), now)
+                    .unwrap();
+            }
+            assert_eq!(ids(&mempool.take(&prices)), batched);
+            assert_eq!(mempool.len(), 1);
+            assert!(mempool.get(remaining).is_some());
+            assert!(batched.iter().all(|id| mempool.get(*id).is_none()));
+            assert_eq!(ids(&mempool.take(&prices)), vec![remaining]);
+            assert!(mempool.is_empty());
+            assert!(mempool.signer_intents(&[1; 32]).is_empty());
+        }
+
+        let mut mempool = SolinaMempool::new(3, 3, OrderingPolicy::Fifo);
+        for (id, intent) in pending.iter().enumerate() {
+            mempool
+                .insert(id as IntentId + 1, intent.clone(), now)
+                .unwrap();
+        }
+        assert_eq!(ids(&mempool.evict_expired(date(4))), vec![3]);
+        assert_eq!(ids(&mempool.evict_expired(date(6))), vec![1]);
+        assert_eq!(mempool.len(), 1);
     }
 }
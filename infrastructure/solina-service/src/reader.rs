@@ -0,0 +1,594 @@
+use crate::{
+    config::SolinaConfig,
+    error::{Error, Result, StorageErrorKind},
+    types::{
+        BatchIntent, GetBatchIntentsRequest, GetBatchIntentsResponse, GetBatchOutcomeRequest,
+        GetBatchOutcomeResponse, GetBatchRequest, GetBatchResponse, GetIntentRequest,
+        GetIntentResponse, GetIntentStatusRequest, GetIntentStatusResponse,
+        GetLatestSealedBatchRequest, GetVerifierDataRequest, GetVerifierDataResponse,
+        IntentStatusInfo, ListIntentsRequest, ListIntentsResponse, SolutionSubmission,
+    },
+};
+use hex::encode;
+use log::error;
+use num_bigint::BigUint;
+use solina::{intent::Intent, price_oracle::PriceSnapshot};
+use solina_circuits::registry::{read_verifier_artifacts, CircuitRegistryError};
+use std::{
+    collections::BTreeMap,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+use storage_sqlite::{
+    Batch, BatchState, IntentStatus, PendingIntent, ReadWriterTransaction, SolinaStorage,
+    SolinaStorageError, TrackedIntent,
+};
+
+/// Maximum number of intents returned by a single batch request.
+pub const MAX_BATCH_PAGE_SIZE: i64 = 100;
+
+/// Maximum number of intents returned by a single intents listing request.
+pub const MAX_INTENTS_PAGE_SIZE: i64 = 100;
+
+/// Number of storage connections serving read requests.
+pub const READER_CONNECTIONS: usize = 4;
+
+/// Serves read requests on its own storage connections, on the blocking thread pool, so
+/// that reads neither wait for the worker, nor hold it back. Intents are persisted before
+/// being acknowledged, so that pending intents are read from storage as well.
+#[derive(Clone)]
+pub struct SolinaReader {
+    storage_connections: Arc<Vec<SolinaStorage>>,
+    next_connection: Arc<AtomicUsize>,
+    config: Arc<SolinaConfig>,
+}
+
+impl SolinaReader {
+    /// Opens the reader connections, on storage already migrated by the worker.
+    pub fn new(config: SolinaConfig) -> Result<Self> {
+        let storage_connections = (0..READER_CONNECTIONS)
+            .map(|_| {
+                SolinaStorage::try_open(config.storage_file_path()).map_err(|e| {
+                    error!("Failed to start a storage connection, with error: {}", e);
+                    Error::StorageError(StorageErrorKind::Connection)
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            storage_connections: Arc::new(storage_connections),
+            next_connection: Arc::new(AtomicUsize::new(0)),
+            config: Arc::new(config),
+        })
+    }
+
+    /// Runs `handler` on the blocking thread pool.
+    pub async fn run<R: Send + 'static>(
+        &self,
+        handler: impl FnOnce(&SolinaReader) -> Result<R> + Send + 'static,
+    ) -> Result<R> {
+        let reader = self.clone();
+        tokio::task::spawn_blocking(move || handler(&reader))
+            .await
+            .map_err(|e| {
+                error!("Failed to run read request, with error: {}", e);
+                Error::InternalError
+            })?
+    }
+
+    /// Connections are handed out in turn.
+    fn storage_connection(&self) -> &SolinaStorage {
+        let index = self.next_connection.fetch_add(1, Ordering::Relaxed);
+        &self.storage_connections[index % self.storage_connections.len()]
+    }
+}
+
+impl SolinaReader {
+    pub fn handle_get_intent_request(
+        &self,
+        get_intent_request: GetIntentRequest,
+    ) -> Result<GetIntentResponse> {
+        let intent_id = get_intent_request.id;
+        let mut tx = self
+            .storage_connection()
+            .create_transaction()
+            .map_err(|e| {
+                error!(
+                    "Failed to create transaction on the database, with error: {:?}",
+                    e
+                );
+                Error::StorageError(StorageErrorKind::Transaction)
+            })?;
+        // we first verify if the intent is still pending
+        let pending_intent = tx
+            .get_pending_intents_batch(&[intent_id])
+            .map_err(|e| {
+                error!("Failed to retrieve pending intent, with error: {:?}", e);
+                Error::StorageError(StorageErrorKind::Read)
+            })?
+            .pop();
+        let intent = match pending_intent {
+            Some(pending_intent) => decode_pending_intent(pending_intent)?,
+            // Otherwise, it has been sealed in a batch
+            None => tx
+                .get_intent(intent_id)
+                .map_err(|e| {
+                    error!("Failed to retrieve intent, with error: {:?}", e);
+                    match e {
+                        SolinaStorageError::NotFound(_) => {
+                            Error::IntentNotFound(intent_id.to_string())
+                        }
+                        _ => Error::StorageError(StorageErrorKind::Read),
+                    }
+                })?
+                .to_intent()
+                .map_err(|e| {
+                    error!("Failed to convert intent, with error: {}", e);
+                    Error::StorageError(StorageErrorKind::CorruptedData)
+                })?,
+        };
+
+        Ok(GetIntentResponse {
+            intent_json: intent_json(&intent)?,
+            message: String::from("GET intent successfully"),
+            is_success: true,
+        })
+    }
+
+    pub fn handle_get_batch_intents_request(
+        &self,
+        get_intent_request: GetBatchIntentsRequest,
+    ) -> Result<GetBatchIntentsResponse> {
+        let mut intent_ids = get_intent_request.ids;
+        let mut tx = self
+            .storage_connection()
+            .create_transaction()
+            .map_err(|e| {
+                error!("Failed to retrieve database transaction, with error: {}", e);
+                Error::StorageError(StorageErrorKind::Transaction)
+            })?;
+
+        // we first look for the intents still pending
+        let pending_intents = tx.get_pending_intents_batch(&intent_ids).map_err(|e| {
+            error!("Failed to retrieve pending intents, with error: {:?}", e);
+            Error::StorageError(StorageErrorKind::Read)
+        })?;
+        intent_ids.retain(|id| !pending_intents.iter().any(|intent| intent.id == *id));
+        let mut batch_intents = pending_intents
+            .into_iter()
+            .map(decode_pending_intent)
+            .collect::<Result<Vec<_>>>()?;
+
+        if !intent_ids.is_empty() {
+            let sealed_intents = tx.get_intents_batch(&intent_ids).map_err(|e| {
+                error!("Failed to retrieve batch of intents, with error: {:?}", e);
+                Error::StorageError(StorageErrorKind::Read)
+            })?;
+            let missing_ids = intent_ids
+                .iter()
+                .filter(|id| !sealed_intents.iter().any(|intent| intent.id == **id))
+                .map(|id| id.to_string())
+                .collect::<Vec<_>>();
+            if !missing_ids.is_empty() {
+                error!("Intents requested in batch not found: {:?}", missing_ids);
+                return Err(Error::IntentNotFound(missing_ids.join(", ")));
+            }
+            for intent in sealed_intents {
+                batch_intents.push(intent.to_intent().map_err(|e| {
+                    error!("Failed to convert intent, with error: {}", e);
+                    Error::StorageError(StorageErrorKind::CorruptedData)
+                })?);
+            }
+        }
+
+        Ok(GetBatchIntentsResponse {
+            batch_intents_json: batch_intents
+                .iter()
+                .map(intent_json)
+                .collect::<Result<Vec<_>>>()?,
+            message: String::from("GET batch intents successfully"),
+            is_success: true,
+        })
+    }
+
+    pub fn handle_get_verifier_data_request(
+        &self,
+        request: GetVerifierDataRequest,
+    ) -> Result<GetVerifierDataResponse> {
+        let circuit_id = request.circuit_id;
+        let (manifest, verifier_data) = read_verifier_artifacts(
+            self.config.circuit_artifacts_dir(),
+            &circuit_id,
+        )
+        .map_err(|e| {
+            error!(
+                "Failed to read verifier data for circuit {}, with error: {}",
+                circuit_id, e
+            );
+            match e {
+                CircuitRegistryError::InvalidCircuitId(_)
+                | CircuitRegistryError::UnknownCircuit(_) => Error::InvalidRequest(e.to_string()),
+                _ => Error::InternalError,
+            }
+        })?;
+
+        Ok(GetVerifierDataResponse {
+            circuit_id,
+            version_hash: manifest.version_hash,
+            verifier_data: encode(verifier_data),
+            is_success: true,
+            message: "GET verifier data successfully".to_string(),
+        })
+    }
+
+    pub(crate) fn handle_get_batch_request(
+        &self,
+        request: GetBatchRequest,
+    ) -> Result<GetBatchResponse> {
+        let batch_id = request.batch_id;
+        let mut tx = self
+            .storage_connection()
+            .create_transaction()
+            .map_err(|e| {
+                error!("Failed to retrieve database transaction, with error: {}", e);
+                Error::StorageError(StorageErrorKind::Transaction)
+            })?;
+
+        let batch = tx.get_batch(batch_id).map_err(|e| {
+            error!("Failed to retrieve batch {}, with error: {}", batch_id, e);
+            match e {
+                SolinaStorageError::NotFound(_) => Error::BatchNotFound(batch_id.to_string()),
+                _ => Error::StorageError(StorageErrorKind::Read),
+            }
+        })?;
+        batch_response(&mut tx, batch, request.offset, request.limit)
+    }
+
+    pub(crate) fn handle_get_latest_sealed_batch_request(
+        &self,
+        request: GetLatestSealedBatchRequest,
+    ) -> Result<GetBatchResponse> {
+        let mut tx = self
+            .storage_connection()
+            .create_transaction()
+            .map_err(|e| {
+                error!("Failed to retrieve database transaction, with error: {}", e);
+                Error::StorageError(StorageErrorKind::Transaction)
+            })?;
+
+        let batch = tx
+            .get_latest_sealed_batch()
+            .map_err(|e| {
+                error!("Failed to retrieve latest sealed batch, with error: {}", e);
+                Error::StorageError(StorageErrorKind::Read)
+            })?
+            .ok_or_else(|| {
+                error!("No batch has been sealed yet");
+                Error::BatchNotFound("no batch has been sealed yet".to_string())
+            })?;
+        batch_response(&mut tx, batch, request.offset, request.limit)
+    }
+
+    pub(crate) fn handle_get_batch_outcome_request(
+        &self,
+        request: GetBatchOutcomeRequest,
+    ) -> Result<GetBatchOutcomeResponse> {
+        let batch_id = request.batch_id;
+        let mut tx = self
+            .storage_connection()
+            .create_transaction()
+            .map_err(|e| {
+                error!("Failed to retrieve database transaction, with error: {}", e);
+                Error::StorageError(StorageErrorKind::Transaction)
+            })?;
+
+        let batch = tx.get_batch(batch_id).map_err(|e| {
+            error!("Failed to retrieve batch {}, with error: {}", batch_id, e);
+            match e {
+                SolinaStorageError::NotFound(_) => Error::BatchNotFound(batch_id.to_string()),
+                _ => Error::StorageError(StorageErrorKind::Read),
+            }
+        })?;
+        let state = batch.state().map_err(|e| {
+            error!(
+                "Invalid state stored for batch {}, with error: {}",
+                batch_id, e
+            );
+            Error::StorageError(StorageErrorKind::CorruptedData)
+        })?;
+
+        // submissions are kept private until the solving window closes
+        let submissions = match state {
+            BatchState::Collecting | BatchState::Sealed | BatchState::Solving => vec![],
+            BatchState::SolutionSelected | BatchState::Settled => tx
+                .get_solutions(batch_id)
+                .map_err(|e| {
+                    error!(
+                        "Failed to retrieve solutions of batch {}, with error: {}",
+                        batch_id, e
+                    );
+                    Error::StorageError(StorageErrorKind::Read)
+                })?
+                .into_iter()
+                .map(|solution| SolutionSubmission {
+                    solution_id: solution.id,
+                    solver_address: solution.solver_address,
+                    score: solution.score,
+                    submitted_at: solution.created_at,
+                    is_selected: solution.is_selected,
+                })
+                .collect::<Vec<_>>(),
+        };
+
+        // scores of the selected solutions of each solver, for fees to be split among them
+        let mut solver_scores = BTreeMap::<String, BigUint>::new();
+        for submission in submissions.iter().filter(|s| s.is_selected) {
+            let score = BigUint::from_str(&submission.score).map_err(|e| {
+                error!(
+                    "Invalid score stored for solution {}, with error: {}",
+                    submission.solution_id, e
+                );
+                Error::StorageError(StorageErrorKind::CorruptedData)
+            })?;
+            *solver_scores
+                .entry(submission.solver_address.clone())
+                .or_default() += score;
+        }
+        let winner = batch.winning_solution_id.and_then(|id| {
+            submissions
+                .iter()
+                .find(|submission| submission.solution_id == id)
+                .cloned()
+        });
+
+        Ok(GetBatchOutcomeResponse {
+            batch_id,
+            state: state.as_str().to_string(),
+            winner,
+            submissions,
+            solver_scores: solver_scores
+                .into_iter()
+                .map(|(solver, score)| (solver, score.to_string()))
+                .collect(),
+            is_success: true,
+            message: "GET batch outcome successfully".to_string(),
+        })
+    }
+
+    pub(crate) fn handle_get_intent_status_request(
+        &self,
+        request: GetIntentStatusRequest,
+    ) -> Result<GetIntentStatusResponse> {
+        let mut tx = self
+            .storage_connection()
+            .create_transaction()
+            .map_err(|e| {
+                error!("Failed to retrieve database transaction, with error: {}", e);
+                Error::StorageError(StorageErrorKind::Transaction)
+            })?;
+
+        let (tracked_intent, key) = match (request.id, request.structured_hash) {
+            (Some(id), _) => (tx.get_tracked_intent(id), id.to_string()),
+            (None, Some(structured_hash)) => {
+                let structured_hash = structured_hash.trim_start_matches("0x").to_lowercase();
+                (
+                    tx.get_tracked_intent_by_hash(&structured_hash),
+                    structured_hash,
+                )
+            }
+            (None, None) => {
+                error!("Intent status requested without any id, or structured hash");
+                return Err(Error::InvalidRequest(
+                    "Either an intent id, or a structured hash, is required".to_string(),
+                ));
+            }
+        };
+        let tracked_intent = tracked_intent
+            .map_err(|e| {
+                error!("Failed to retrieve intent status, with error: {}", e);
+                Error::StorageError(StorageErrorKind::Read)
+            })?
+            .ok_or(Error::IntentNotFound(key))?;
+
+        Ok(GetIntentStatusResponse {
+            intent: intent_status_info(tracked_intent)?,
+            is_success: true,
+            message: "GET intent status successfully".to_string(),
+        })
+    }
+
+    pub(crate) fn handle_list_intents_request(
+        &self,
+        request: ListIntentsRequest,
+    ) -> Result<ListIntentsResponse> {
+        let (offset, limit) = page(request.offset, request.limit, MAX_INTENTS_PAGE_SIZE)?;
+        let status = request
+            .status
+            .as_deref()
+            .map(IntentStatus::from_str)
+            .transpose()
+            .map_err(|e| {
+                error!("Invalid intent status filter, with error: {}", e);
+                Error::InvalidRequest(format!("Invalid intent status filter: {}", e))
+            })?;
+        let signer = request.signer.trim_start_matches("0x").to_lowercase();
+
+        let mut tx = self
+            .storage_connection()
+            .create_transaction()
+            .map_err(|e| {
+                error!("Failed to retrieve database transaction, with error: {}", e);
+                Error::StorageError(StorageErrorKind::Transaction)
+            })?;
+        let total_intents = tx.count_signer_intents(&signer, status).map_err(|e| {
+            error!(
+                "Failed to count intents of signer {}, with error: {}",
+                signer, e
+            );
+            Error::StorageError(StorageErrorKind::Read)
+        })?;
+        let intents = tx
+            .get_signer_intents(&signer, status, offset, limit)
+            .map_err(|e| {
+                error!(
+                    "Failed to retrieve intents of signer {}, with error: {}",
+                    signer, e
+                );
+                Error::StorageError(StorageErrorKind::Read)
+            })?
+            .into_iter()
+            .map(intent_status_info)
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(ListIntentsResponse {
+            signer,
+            total_intents,
+            offset,
+            intents,
+            is_success: true,
+            message: "GET intents successfully".to_string(),
+        })
+    }
+}
+
+/// Builds the solver facing view of `batch`, with a page of its intents, of at most
+/// `MAX_BATCH_PAGE_SIZE` intents.
+fn batch_response(
+    tx: &mut ReadWriterTransaction,
+    batch: Batch,
+    offset: Option<i64>,
+    limit: Option<i64>,
+) -> Result<GetBatchResponse> {
+    let (offset, limit) = page(offset, limit, MAX_BATCH_PAGE_SIZE)?;
+
+    let state = batch.state().map_err(|e| {
+        error!(
+            "Invalid state stored for batch {}, with error: {}",
+            batch.id, e
+        );
+        Error::StorageError(StorageErrorKind::CorruptedData)
+    })?;
+    let price_snapshot = match batch.price_snapshot.as_deref() {
+        Some(price_snapshot) => serde_json::from_str(price_snapshot).map_err(|e| {
+            error!(
+                "Invalid price snapshot stored for batch {}, with error: {}",
+                batch.id, e
+            );
+            Error::StorageError(StorageErrorKind::CorruptedData)
+        })?,
+        None => PriceSnapshot::default(),
+    };
+
+    let total_intents = tx.count_batch_intents(batch.id).map_err(|e| {
+        error!(
+            "Failed to count intents of batch {}, with error: {}",
+            batch.id, e
+        );
+        Error::StorageError(StorageErrorKind::Read)
+    })?;
+    let intents = tx
+        .get_batch_intents(batch.id, offset, limit)
+        .map_err(|e| {
+            error!(
+                "Failed to retrieve intents of batch {}, with error: {}",
+                batch.id, e
+            );
+            Error::StorageError(StorageErrorKind::Read)
+        })?
+        .into_iter()
+        .map(|intent| {
+            let intent_json = intent
+                .to_intent()
+                .map_err(|e| {
+                    error!("Failed to convert intent, with error: {}", e);
+                    Error::StorageError(StorageErrorKind::CorruptedData)
+                })
+                .and_then(|intent| {
+                    serde_json::to_value(intent).map_err(|e| {
+                        error!("Failed to serialize intent data to JSON, with error: {}", e);
+                        Error::InternalError
+                    })
+                })?;
+            Ok(BatchIntent {
+                id: intent.id,
+                intent_json,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(GetBatchResponse {
+        batch_id: batch.id,
+        state: state.as_str().to_string(),
+        root: batch.root,
+        sealed_at: batch.sealed_at,
+        solving_deadline: batch.solving_deadline,
+        price_snapshot: price_snapshot
+            .prices
+            .into_iter()
+            .map(|(token, price)| (token, price.to_string()))
+            .collect(),
+        total_intents,
+        offset,
+        intents,
+        is_success: true,
+        message: "GET batch successfully".to_string(),
+    })
+}
+
+/// Validates the requested pagination, returning the offset and limit to query, of at most
+/// `max_limit` items.
+fn page(offset: Option<i64>, limit: Option<i64>, max_limit: i64) -> Result<(i64, i64)> {
+    let offset = offset.unwrap_or(0);
+    let limit = limit.unwrap_or(max_limit);
+    if offset < 0 || limit < 0 {
+        error!("Invalid pagination, offset = {}, limit = {}", offset, limit);
+        return Err(Error::InvalidRequest(
+            "Offset and limit must not be negative".to_string(),
+        ));
+    }
+    Ok((offset, limit.min(max_limit)))
+}
+
+fn intent_status_info(tracked_intent: TrackedIntent) -> Result<IntentStatusInfo> {
+    let status = tracked_intent.status().map_err(|e| {
+        error!(
+            "Invalid status stored for intent {}, with error: {}",
+            tracked_intent.intent_id, e
+        );
+        Error::StorageError(StorageErrorKind::CorruptedData)
+    })?;
+
+    Ok(IntentStatusInfo {
+        id: tracked_intent.intent_id,
+        structured_hash: tracked_intent.structured_hash,
+        signer: tracked_intent.signer,
+        base_token: tracked_intent.base_token,
+        quote_token: tracked_intent.quote_token,
+        status: status.as_str().to_string(),
+        batch_id: tracked_intent.batch_id,
+        quote_amount_swapped: tracked_intent.quote_amount_swapped,
+        base_amount_received: tracked_intent.base_amount_received,
+        created_at: tracked_intent.created_at,
+        updated_at: tracked_intent.updated_at,
+    })
+}
+
+/// Decodes an intent accepted into the mempool, from its stored JSON.
+pub(crate) fn decode_pending_intent(pending_intent: PendingIntent) -> Result<Intent> {
+    serde_json::from_str::<Intent>(&pending_intent.intent).map_err(|e| {
+        error!(
+            "Invalid pending intent {} stored, with error: {}",
+            pending_intent.id, e
+        );
+        Error::StorageError(StorageErrorKind::CorruptedData)
+    })
+}
+
+fn intent_json(intent: &Intent) -> Result<serde_json::Value> {
+    serde_json::to_value(intent).map_err(|e| {
+        error!("Failed to serialize intent data to JSON, with error: {}", e);
+        Error::InternalError
+    })
+}
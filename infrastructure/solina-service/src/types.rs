@@ -1,4 +1,87 @@
+use crate::error::Error;
+use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solina::IntentId;
+use std::collections::BTreeMap;
+
+pub const JSON_RPC_VERSION: &str = "2.0";
+
+/// A JSON-RPC 2.0 request. Requests without an id are notifications, which are never
+/// answered. A `null` id is still an id, answered as such.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct JsonRpcRequest {
+    pub(crate) jsonrpc: String,
+    pub(crate) method: String,
+    #[serde(default)]
+    pub(crate) params: Value,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_present",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub(crate) id: Option<Value>,
+}
+
+/// Present values, including `null`, are `Some`, while missing ones default to `None`.
+fn deserialize_present<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> core::result::Result<Option<Value>, D::Error> {
+    Value::deserialize(deserializer).map(Some)
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct JsonRpcResponse {
+    pub(crate) jsonrpc: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) error: Option<JsonRpcError>,
+    pub(crate) id: Value,
+}
+
+impl JsonRpcResponse {
+    pub fn new(id: Value, result: core::result::Result<Value, JsonRpcError>) -> Self {
+        let (result, error) = match result {
+            Ok(result) => (Some(result), None),
+            Err(error) => (None, Some(error)),
+        };
+        Self {
+            jsonrpc: JSON_RPC_VERSION.to_string(),
+            result,
+            error,
+            id,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct JsonRpcError {
+    pub(crate) code: i64,
+    pub(crate) message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) data: Option<Value>,
+}
+
+impl JsonRpcError {
+    pub fn new(code: i64, message: String) -> Self {
+        Self {
+            code,
+            message,
+            data: None,
+        }
+    }
+}
+
+impl From<Error> for JsonRpcError {
+    fn from(error: Error) -> Self {
+        Self {
+            code: error.json_rpc_code(),
+            message: error.to_string(),
+            data: serde_json::to_value(&error).ok(),
+        }
+    }
+}
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct StoreIntentRequest {
@@ -7,14 +90,14 @@ pub struct StoreIntentRequest {
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct StoreIntentResponse {
-    pub(crate) intent_id: Option<i64>,
+    pub(crate) intent_id: Option<IntentId>,
     pub(crate) is_success: bool,
     pub(crate) message: String,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct GetIntentRequest {
-    pub(crate) id: i32,
+    pub(crate) id: IntentId,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -26,7 +109,7 @@ pub struct GetIntentResponse {
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct GetBatchIntentsRequest {
-    pub(crate) ids: Vec<i32>,
+    pub(crate) ids: Vec<IntentId>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -71,3 +154,142 @@ pub struct DeregisterSolverResponse {
     pub(crate) is_success: bool,
     pub(crate) message: String,
 }
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct GetVerifierDataRequest {
+    pub(crate) circuit_id: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct GetVerifierDataResponse {
+    pub(crate) circuit_id: String,
+    pub(crate) version_hash: String,
+    pub(crate) verifier_data: String,
+    pub(crate) is_success: bool,
+    pub(crate) message: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SubmitSolutionRequest {
+    pub(crate) address: String,
+    pub(crate) batch_id: i32,
+    pub(crate) solution_json: serde_json::Value,
+    pub(crate) proof: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SubmitSolutionResponse {
+    pub(crate) is_success: bool,
+    pub(crate) message: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct GetBatchRequest {
+    pub(crate) batch_id: i32,
+    pub(crate) offset: Option<i64>,
+    pub(crate) limit: Option<i64>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct GetLatestSealedBatchRequest {
+    pub(crate) offset: Option<i64>,
+    pub(crate) limit: Option<i64>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BatchIntent {
+    pub(crate) id: IntentId,
+    pub(crate) intent_json: Value,
+}
+
+/// A sealed batch, as seen by solvers. Intents are paginated, and returned in canonical
+/// (batch) order, that is, in the order of the leaves of the batch Merkle root.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct GetBatchResponse {
+    pub(crate) batch_id: i32,
+    pub(crate) state: String,
+    pub(crate) root: Option<String>,
+    pub(crate) sealed_at: Option<NaiveDateTime>,
+    pub(crate) solving_deadline: Option<NaiveDateTime>,
+    pub(crate) price_snapshot: BTreeMap<String, String>,
+    pub(crate) total_intents: i64,
+    pub(crate) offset: i64,
+    pub(crate) intents: Vec<BatchIntent>,
+    pub(crate) is_success: bool,
+    pub(crate) message: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct GetBatchOutcomeRequest {
+    pub(crate) batch_id: i32,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SolutionSubmission {
+    pub(crate) solution_id: i32,
+    pub(crate) solver_address: String,
+    pub(crate) score: String,
+    pub(crate) submitted_at: NaiveDateTime,
+    pub(crate) is_selected: bool,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct GetBatchOutcomeResponse {
+    pub(crate) batch_id: i32,
+    pub(crate) state: String,
+    pub(crate) winner: Option<SolutionSubmission>,
+    pub(crate) submissions: Vec<SolutionSubmission>,
+    pub(crate) solver_scores: BTreeMap<String, String>,
+    pub(crate) is_success: bool,
+    pub(crate) message: String,
+}
+
+/// Intents are looked up by id, or by (hex encoded) structured hash, in which case the most
+/// recently submitted intent with that hash is returned.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct GetIntentStatusRequest {
+    pub(crate) id: Option<IntentId>,
+    pub(crate) structured_hash: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct IntentStatusInfo {
+    pub(crate) id: IntentId,
+    pub(crate) structured_hash: String,
+    pub(crate) signer: String,
+    pub(crate) base_token: String,
+    pub(crate) quote_token: String,
+    pub(crate) status: String,
+    pub(crate) batch_id: Option<i32>,
+    pub(crate) quote_amount_swapped: Option<String>,
+    pub(crate) base_amount_received: Option<String>,
+    pub(crate) created_at: NaiveDateTime,
+    pub(crate) updated_at: NaiveDateTime,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct GetIntentStatusResponse {
+    pub(crate) intent: IntentStatusInfo,
+    pub(crate) is_success: bool,
+    pub(crate) message: String,
+}
+
+/// Intents of a (hex encoded) signer public key, optionally filtered by status.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ListIntentsRequest {
+    pub(crate) signer: String,
+    pub(crate) status: Option<String>,
+    pub(crate) offset: Option<i64>,
+    pub(crate) limit: Option<i64>,
+}
+
+/// A page of the intents of a signer, from the most recent to the oldest.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ListIntentsResponse {
+    pub(crate) signer: String,
+    pub(crate) total_intents: i64,
+    pub(crate) offset: i64,
+    pub(crate) intents: Vec<IntentStatusInfo>,
+    pub(crate) is_success: bool,
+    pub(crate) message: String,
+}
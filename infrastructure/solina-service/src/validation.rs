@@ -0,0 +1,340 @@
+use chrono::{Duration, NaiveDateTime};
+use hex::encode;
+use num_bigint::BigUint;
+use serde::{Deserialize, Serialize};
+use solina::{
+    intent::Intent,
+    price_oracle::{PriceOracle, PriceSnapshot},
+    TokenAddress,
+};
+use std::collections::BTreeSet;
+
+/// Basis points in a unit, for premiums expressed in basis points.
+const BPS: u32 = 10_000;
+
+/// Default maximum time, in seconds, from submission to expiry of an intent (a week).
+pub const DEFAULT_MAX_EXPIRY_HORIZON: u64 = 7 * 24 * 60 * 60;
+
+/// Intent submitted at `now`, checked against the reference prices of the service.
+pub struct ValidationContext<'a> {
+    pub now: NaiveDateTime,
+    pub prices: &'a PriceSnapshot,
+}
+
+/// A check intents go through before entering the mempool.
+pub trait IntentValidator: Send + Sync {
+    /// Name of the rule, reported when it rejects an intent.
+    fn rule(&self) -> &str;
+
+    /// Returns the reason `intent` is rejected, if any.
+    fn validate(&self, intent: &Intent, context: &ValidationContext) -> Result<(), String>;
+}
+
+/// Validation rules, as configured. Tokens are hex encoded, with or without `0x` prefix.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum ValidationRule {
+    /// Intents must expire in the future, at most `max_horizon` seconds after submission.
+    Expiry { max_horizon: u64 },
+    /// Quote amounts must be positive, and so must minimum base token amounts, if
+    /// `min_base_token_amount` is set. Intents without a minimum are accepted otherwise.
+    NonZeroAmounts {
+        #[serde(default)]
+        min_base_token_amount: bool,
+    },
+    /// Base and quote tokens must differ.
+    DistinctTokens,
+    /// Both tokens of intents must be listed.
+    TokenAllowlist { tokens: BTreeSet<String> },
+    /// Neither token of intents may be listed.
+    TokenDenylist { tokens: BTreeSet<String> },
+    /// Quote amounts must be worth at least `min_value`, at reference prices.
+    MinNotionalValue {
+        #[serde(with = "crate::config::decimal")]
+        min_value: BigUint,
+    },
+    /// Minimum base token amounts must not exceed, by more than `max_premium_bps` basis
+    /// points, the base amount the quote amount is worth at reference prices. Such intents
+    /// could never be filled.
+    MinBaseTokenAmount { max_premium_bps: u32 },
+}
+
+impl ValidationRule {
+    /// Rules intents go through by default.
+    pub fn default_rules() -> Vec<Self> {
+        vec![
+            Self::Expiry {
+                max_horizon: DEFAULT_MAX_EXPIRY_HORIZON,
+            },
+            Self::NonZeroAmounts {
+                min_base_token_amount: false,
+            },
+            Self::DistinctTokens,
+        ]
+    }
+}
+
+fn normalize_token(token: &str) -> String {
+    token.trim_start_matches("0x").to_lowercase()
+}
+
+fn is_listed(tokens: &BTreeSet<String>, token: TokenAddress) -> bool {
+    let token = encode(token);
+    tokens.iter().any(|listed| normalize_token(listed) == token)
+}
+
+impl IntentValidator for ValidationRule {
+    fn rule(&self) -> &str {
+        match self {
+            Self::Expiry { .. } => "expiry",
+            Self::NonZeroAmounts { .. } => "non_zero_amounts",
+            Self::DistinctTokens => "distinct_tokens",
+            Self::TokenAllowlist { .. } => "token_allowlist",
+            Self::TokenDenylist { .. } => "token_denylist",
+            Self::MinNotionalValue { .. } => "min_notional_value",
+            Self::MinBaseTokenAmount { .. } => "min_base_token_amount",
+        }
+    }
+
+    fn validate(&self, intent: &Intent, context: &ValidationContext) -> Result<(), String> {
+        let inputs = &intent.inputs;
+        let tokens = [inputs.quote_token, inputs.base_token];
+        match self {
+            Self::Expiry { max_horizon } => {
+                if intent.is_expired_at(&context.now) {
+                    return Err(format!("intent expired at {}", intent.expiry_date));
+                }
+                let horizon = Duration::seconds(i64::try_from(*max_horizon).unwrap_or(i64::MAX));
+                if context
+                    .now
+                    .checked_add_signed(horizon)
+                    .map(|latest_expiry| intent.expiry_date > latest_expiry)
+                    .unwrap_or(false)
+                {
+                    return Err(format!(
+                        "intent expires more than {} seconds after submission",
+                        max_horizon
+                    ));
+                }
+            }
+            Self::NonZeroAmounts {
+                min_base_token_amount,
+            } => {
+                if inputs.quote_amount == BigUint::default() {
+                    return Err("quote amount is zero".to_string());
+                }
+                if *min_base_token_amount
+                    && intent.constraints.min_base_token_amount == BigUint::default()
+                {
+                    return Err("minimum base token amount is zero".to_string());
+                }
+            }
+            Self::DistinctTokens => {
+                if inputs.quote_token == inputs.base_token {
+                    return Err("base and quote tokens are the same".to_string());
+                }
+            }
+            Self::TokenAllowlist { tokens: allowlist } => {
+                if let Some(token) = tokens.iter().find(|token| !is_listed(allowlist, **token)) {
+                    return Err(format!("token {} is not allowed", encode(token)));
+                }
+            }
+            Self::TokenDenylist { tokens: denylist } => {
+                if let Some(token) = tokens.iter().find(|token| is_listed(denylist, **token)) {
+                    return Err(format!("token {} is denied", encode(token)));
+                }
+            }
+            Self::MinNotionalValue { min_value } => {
+                let value =
+                    &inputs.quote_amount * context.prices.get_current_price(inputs.quote_token);
+                if value < *min_value {
+                    return Err(format!(
+                        "notional value {} is below the minimum of {}",
+                        value, min_value
+                    ));
+                }
+            }
+            Self::MinBaseTokenAmount { max_premium_bps } => {
+                let quote_price = context.prices.get_current_price(inputs.quote_token);
+                let base_price = context.prices.get_current_price(inputs.base_token);
+                if quote_price == BigUint::default() || base_price == BigUint::default() {
+                    return Err("tokens have no reference price".to_string());
+                }
+                // min_base / (quote_amount * quote_price / base_price) <= 1 + premium,
+                // without rounding
+                let asked = &intent.constraints.min_base_token_amount * base_price * BPS;
+                let worth = &inputs.quote_amount * quote_price * (BPS + *max_premium_bps);
+                if asked > worth {
+                    return Err(format!(
+                        "minimum base token amount exceeds the reference price by more than {} \
+                         basis points",
+                        max_premium_bps
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Intent rejected by the `rule` validator, for `reason`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rejection {
+    pub rule: String,
+    pub reason: String,
+}
+
+/// Validators intents go through in order, until the first rejection.
+#[derive(Default)]
+pub struct IntentValidatorChain {
+    validators: Vec<Box<dyn IntentValidator>>,
+}
+
+impl IntentValidatorChain {
+    pub fn new(rules: &[ValidationRule]) -> Self {
+        rules
+            .iter()
+            .cloned()
+            .fold(Self::default(), |chain, rule| chain.with_validator(rule))
+    }
+
+    /// Appends `validator` to the chain.
+    pub fn with_validator(mut self, validator: impl IntentValidator + 'static) -> Self {
+        self.validators.push(Box::new(validator));
+        self
+    }
+
+    pub fn validate(&self, intent: &Intent, context: &ValidationContext) -> Result<(), Rejection> {
+        self.validators.iter().try_for_each(|validator| {
+            validator
+                .validate(intent, context)
+                .map_err(|reason| Rejection {
+                    rule: validator.rule().to_string(),
+                    reason,
+                })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use solina::{
+        intent::{IntentConstraints, IntentInputs, TradeDirection},
+        Signature,
+    };
+    use std::collections::BTreeMap;
+
+    fn date(day: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2023, 11, day)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+    }
+
+    fn intent(tokens: (u8, u8), quote_amount: u64, min_base_amount: u64) -> Intent {
+        Intent::new(
+            [1; 32],
+            IntentInputs::new(
+                [tokens.0; 32],
+                [tokens.1; 32],
+                BigUint::from(quote_amount),
+                TradeDirection::Sell,
+            ),
+            IntentConstraints::new(BigUint::from(min_base_amount)),
+            Signature([0u8; 64]),
+            date(10),
+        )
+    }
+
+    fn rejected_rule(chain: &IntentValidatorChain, intent: &Intent, now: u32) -> Option<String> {
+        let prices = PriceSnapshot {
+            prices: BTreeMap::from([
+                (encode([1; 32]), BigUint::from(2_u8)),
+                (encode([2; 32]), BigUint::from(4_u8)),
+            ]),
+        };
+        let context = ValidationContext {
+            now: date(now),
+            prices: &prices,
+        };
+        chain
+            .validate(intent, &context)
+            .err()
+            .map(|rejection| rejection.rule)
+    }
+
+    #[test]
+    fn it_works_default_rules() {
+        let chain = IntentValidatorChain::new(&ValidationRule::default_rules());
+        assert_eq!(rejected_rule(&chain, &intent((1, 2), 10, 5), 5), None);
+        assert_eq!(
+            rejected_rule(&chain, &intent((1, 2), 10, 5), 11).as_deref(),
+            Some("expiry")
+        );
+        let chain = IntentValidatorChain::new(&[ValidationRule::Expiry { max_horizon: 3600 }]);
+        assert_eq!(
+            rejected_rule(&chain, &intent((1, 2), 10, 5), 5).as_deref(),
+            Some("expiry")
+        );
+
+        let chain = IntentValidatorChain::new(&ValidationRule::default_rules());
+        assert_eq!(
+            rejected_rule(&chain, &intent((1, 2), 0, 5), 5).as_deref(),
+            Some("non_zero_amounts")
+        );
+        assert_eq!(rejected_rule(&chain, &intent((1, 2), 10, 0), 5), None);
+        assert_eq!(
+            rejected_rule(&chain, &intent((1, 1), 10, 5), 5).as_deref(),
+            Some("distinct_tokens")
+        );
+        let chain = IntentValidatorChain::new(&[ValidationRule::NonZeroAmounts {
+            min_base_token_amount: true,
+        }]);
+        assert_eq!(
+            rejected_rule(&chain, &intent((1, 2), 10, 0), 5).as_deref(),
+            Some("non_zero_amounts")
+        );
+    }
+
+    #[test]
+    fn it_works_token_and_price_rules() {
+        let tokens = |token: u8| BTreeSet::from([format!("0x{}", encode([token; 32]))]);
+        let chain = IntentValidatorChain::new(&[
+            ValidationRule::TokenDenylist { tokens: tokens(3) },
+            ValidationRule::TokenAllowlist { tokens: tokens(1) },
+        ]);
+        assert_eq!(
+            rejected_rule(&chain, &intent((3, 1), 10, 5), 5).as_deref(),
+            Some("token_denylist")
+        );
+        assert_eq!(
+            rejected_rule(&chain, &intent((1, 2), 10, 5), 5).as_deref(),
+            Some("token_allowlist")
+        );
+
+        // 10 quote tokens are worth 20, or 5 base tokens
+        let chain = IntentValidatorChain::new(&[
+            ValidationRule::MinNotionalValue {
+                min_value: BigUint::from(20_u8),
+            },
+            ValidationRule::MinBaseTokenAmount {
+                max_premium_bps: 2_000,
+            },
+        ]);
+        assert_eq!(rejected_rule(&chain, &intent((1, 2), 10, 6), 5), None);
+        assert_eq!(
+            rejected_rule(&chain, &intent((1, 2), 9, 1), 5).as_deref(),
+            Some("min_notional_value")
+        );
+        assert_eq!(
+            rejected_rule(&chain, &intent((1, 2), 10, 7), 5).as_deref(),
+            Some("min_base_token_amount")
+        );
+        assert_eq!(
+            rejected_rule(&chain, &intent((1, 3), 10, 1), 5).as_deref(),
+            Some("min_base_token_amount")
+        );
+    }
+}
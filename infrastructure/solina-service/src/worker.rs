@@ -1,28 +1,76 @@
 use crate::{
     auth_challenge::generate_challenge,
-    mempool::SolinaMempool,
+    events::{EventSender, SolinaEvent, TokenPair, EVENTS_CHANNEL_CAPACITY},
+    mempool::{MempoolError, SolinaMempool},
+    reader::decode_pending_intent,
     types::{
-        GetAuthCredentialsRequest, GetAuthCredentialsResponse, GetBatchIntentsRequest,
-        GetBatchIntentsResponse, GetIntentRequest, GetIntentResponse, RegisterSolverRequest,
-        RegisterSolverResponse, StoreIntentRequest, StoreIntentResponse,
+        GetAuthCredentialsRequest, GetAuthCredentialsResponse, RegisterSolverRequest,
+        RegisterSolverResponse, StoreIntentRequest, StoreIntentResponse, SubmitSolutionRequest,
+        SubmitSolutionResponse,
     },
+    validation::{IntentValidatorChain, ValidationContext},
 };
 use crate::{
     config::SolinaConfig,
-    error::{Error, Result},
+    error::{Error, Result, StorageErrorKind},
 };
+use chrono::{Duration, NaiveDateTime, Utc};
 use ethers::prelude::*;
-use hex::encode;
+use hex::{decode, encode};
 use log::{error, info};
-use solina::{intent::Intent, structured_hash::StructuredHashInterface};
-use std::str::FromStr;
-use storage_sqlite::{AuthCredentials, SolinaStorage};
+use num_bigint::BigUint;
+use solina::{
+    batch::batch_root,
+    competition::{combine_solutions, SelectionPolicy, Submission},
+    intent::Intent,
+    price_oracle::PriceSnapshot,
+    solver::BatchSolution,
+    structured_hash::{StructuredHash, StructuredHashInterface},
+    IntentId,
+};
+use solina_circuits::{
+    registry::read_verifier_artifacts,
+    verifier::{verify_batch_solution_proof, BatchSolutionPublicInputs},
+};
+use std::{collections::BTreeMap, str::FromStr};
+use storage_sqlite::{
+    AuthCredentials, Batch, BatchState, IntentStatus, NewSolution, NewTrackedIntent, PendingIntent,
+    ReadWriterTransaction, SolinaStorage, SolinaStorageError,
+};
+use tokio::sync::{broadcast, mpsc, oneshot};
+
+/// Solutions selected to settle a batch, merged into a single settlement.
+struct Selection {
+    /// Ids of the selected solutions, from best to worst.
+    solution_ids: Vec<i32>,
+    solver_addresses: Vec<String>,
+    settlement: BatchSolution,
+}
+
+/// Number of jobs queued for the worker. Callers wait for room once the queue is full, so
+/// that bursts of submissions are throttled, instead of piling up.
+pub const WORKER_QUEUE_CAPACITY: usize = 1024;
+
+type Job = Box<dyn FnOnce(&mut SolinaWorker) + Send>;
 
+/// Messages processed by the worker, in order.
+enum Message {
+    Job(Job),
+    /// Stops the worker, once the jobs queued before are processed, and replies once its
+    /// resources are released.
+    Shutdown(oneshot::Sender<()>),
+}
+
+/// Owns the mempool, and the storage connection writes go through. Once spawned, the worker
+/// runs as an actor on its own thread, processing the jobs sent through its handles one at
+/// a time, in order.
 pub struct SolinaWorker {
     mempool: SolinaMempool,
+    validator: IntentValidatorChain,
     storage_connection: SolinaStorage,
-    current_intent_id: i64,
+    current_intent_id: IntentId,
     config: SolinaConfig,
+    events: EventSender,
 }
 
 impl SolinaWorker {
@@ -30,26 +78,169 @@ impl SolinaWorker {
         let storage_connection =
             SolinaStorage::try_open(config.storage_file_path()).map_err(|e| {
                 error!("Failed to start a storage connection, with error: {}", e);
-                Error::InternalError
+                Error::StorageError(StorageErrorKind::Connection)
             })?;
         storage_connection.run_migrations().map_err(|e| {
             error!("Failed to run migrations, with error: {}", e);
-            Error::InternalError
+            Error::StorageError(StorageErrorKind::Connection)
         })?;
-        Ok(Self {
-            mempool: SolinaMempool::new(config.mempool_capacity()),
+        let current_intent_id = {
+            let mut tx = storage_connection.create_transaction().map_err(|e| {
+                error!("Failed to retrieve database transaction, with error: {}", e);
+                Error::StorageError(StorageErrorKind::Transaction)
+            })?;
+            let current_batch_id = tx.get_current_batch_id().map_err(|e| {
+                error!("Failed to retrieve current batch id, with error: {}", e);
+                Error::StorageError(StorageErrorKind::Read)
+            })?;
+            tx.open_batch(current_batch_id, Utc::now().naive_utc())
+                .map_err(|e| {
+                    error!("Failed to open batch, with error: {}", e);
+                    Error::StorageError(StorageErrorKind::Write)
+                })?;
+            // ids keep increasing across restarts, so that they are never reused
+            let last_intent_id = tx.get_last_intent_id().map_err(|e| {
+                error!("Failed to retrieve last intent id, with error: {}", e);
+                Error::StorageError(StorageErrorKind::Read)
+            })?;
+            commit(&mut tx)?;
+            last_intent_id.unwrap_or(0)
+        };
+        let mut worker = Self {
+            mempool: SolinaMempool::new(
+                config.mempool_capacity(),
+                config.max_intents_per_signer(),
+                config.mempool_ordering(),
+            ),
+            validator: IntentValidatorChain::new(config.intent_validation()),
             storage_connection,
-            current_intent_id: 0,
+            current_intent_id,
             config,
-        })
+            events: broadcast::channel(EVENTS_CHANNEL_CAPACITY).0,
+        };
+        worker.recover_mempool()?;
+        Ok(worker)
+    }
+
+    /// Rebuilds the mempool from the intents accepted before the last shutdown, and not
+    /// sealed yet. Intents expired since are evicted.
+    fn recover_mempool(&mut self) -> Result<()> {
+        let pending_intents = {
+            let mut tx = self.storage_connection.create_transaction().map_err(|e| {
+                error!("Failed to retrieve database transaction, with error: {}", e);
+                Error::StorageError(StorageErrorKind::Transaction)
+            })?;
+            tx.get_pending_intents().map_err(|e| {
+                error!("Failed to retrieve pending intents, with error: {}", e);
+                Error::StorageError(StorageErrorKind::Read)
+            })?
+        };
+        let pending_intents = pending_intents
+            .into_iter()
+            .map(|pending_intent| {
+                let intent_id = pending_intent.id;
+                decode_pending_intent(pending_intent).map(|intent| (intent_id, intent))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if pending_intents.is_empty() {
+            return Ok(());
+        }
+
+        info!("Recovered {} pending intents", pending_intents.len());
+        self.mempool.restore(pending_intents);
+        self.evict_expired_intents(Utc::now().naive_utc())
+    }
+
+    /// Moves the worker to a dedicated thread, as storage work is blocking, and returns a
+    /// handle to send it jobs. The worker stops once shut down, or once all its handles are
+    /// dropped.
+    pub fn spawn(self) -> Result<WorkerHandle> {
+        let (jobs, mut queue) = mpsc::channel::<Message>(WORKER_QUEUE_CAPACITY);
+        let events = self.events.clone();
+        let mut worker = self;
+        std::thread::Builder::new()
+            .name("solina-worker".to_string())
+            .spawn(move || {
+                while let Some(message) = queue.blocking_recv() {
+                    match message {
+                        Message::Job(job) => job(&mut worker),
+                        Message::Shutdown(reply) => {
+                            info!("Shutting down the worker");
+                            drop(worker);
+                            let _ = reply.send(());
+                            return;
+                        }
+                    }
+                }
+                info!("All worker handles dropped, stopping the worker");
+            })
+            .map_err(|e| {
+                error!("Failed to spawn the worker thread, with error: {}", e);
+                Error::FailedToStartService(e.to_string())
+            })?;
+        Ok(WorkerHandle { jobs, events })
     }
 
-    fn update_current_id(&mut self) -> i64 {
+    fn update_current_id(&mut self) -> IntentId {
         self.current_intent_id += 1;
         self.current_intent_id
     }
 }
 
+/// Handle to a spawned worker.
+#[derive(Clone)]
+pub struct WorkerHandle {
+    jobs: mpsc::Sender<Message>,
+    events: EventSender,
+}
+
+impl WorkerHandle {
+    /// Runs `job` on the worker, once the jobs queued before it are processed.
+    pub async fn run<R: Send + 'static>(
+        &self,
+        job: impl FnOnce(&mut SolinaWorker) -> Result<R> + Send + 'static,
+    ) -> Result<R> {
+        let (reply, response) = oneshot::channel();
+        self.jobs
+            .send(Message::Job(Box::new(move |worker| {
+                // the caller may have given up on the response
+                let _ = reply.send(job(worker));
+            })))
+            .await
+            .map_err(|_| {
+                error!("Failed to send job, the worker has stopped");
+                Error::InternalError
+            })?;
+        response.await.map_err(|_| {
+            error!("The worker stopped before completing the job");
+            Error::InternalError
+        })?
+    }
+
+    /// Stops the worker, once the jobs already queued are processed, and waits for its
+    /// resources, such as its storage connection, to be released. Jobs sent afterwards
+    /// fail.
+    pub async fn shutdown(&self) -> Result<()> {
+        let (reply, stopped) = oneshot::channel();
+        self.jobs
+            .send(Message::Shutdown(reply))
+            .await
+            .map_err(|_| {
+                error!("Failed to shut down the worker, it has already stopped");
+                Error::InternalError
+            })?;
+        stopped.await.map_err(|_| {
+            error!("The worker stopped before shutting down");
+            Error::InternalError
+        })
+    }
+
+    /// Events are published by the worker directly, without going through its queue.
+    pub fn subscribe(&self) -> broadcast::Receiver<SolinaEvent> {
+        self.events.subscribe()
+    }
+}
+
 impl SolinaWorker {
     pub fn config(&self) -> &SolinaConfig {
         &self.config
@@ -58,24 +249,34 @@ impl SolinaWorker {
     pub fn storage_connection(&mut self) -> &mut SolinaStorage {
         &mut self.storage_connection
     }
+
+    fn publish(&self, event: SolinaEvent) {
+        // sending only fails when there are no subscribers
+        let _ = self.events.send(event);
+    }
 }
 
 impl SolinaWorker {
     pub fn rollback(&mut self) -> Option<Intent> {
+        let intent = self.mempool.remove(self.current_intent_id);
         self.current_intent_id = self.current_intent_id.checked_sub(1).unwrap_or(0);
-        self.mempool.rollback().map(|(_, i)| i)
+        intent
     }
 
     pub fn handle_post_store_intent_request(
         &mut self,
         store_intent_request: StoreIntentRequest,
     ) -> Result<StoreIntentResponse> {
+        let current_intent_id = self.current_intent_id;
         let result = self.process_store_intent_request(store_intent_request);
         match result {
             Ok(response) => Ok(response),
             Err(e) => {
-                // if we get an error, we need to rollback the internal state
-                self.rollback();
+                // if we get an error once an id is assigned to the intent, we need to
+                // rollback the internal state
+                if self.current_intent_id != current_intent_id {
+                    self.rollback();
+                }
                 Err(e)
             }
         }
@@ -91,44 +292,76 @@ impl SolinaWorker {
                     "Failed to deserialize intent request to an Intent, with error: {:?}",
                     e
                 );
-                Error::InvalidRequest
+                Error::InvalidRequest(format!("Invalid intent: {}", e))
             })?;
         let intent_structured_hash = intent.structured_hash();
         info!(
             "Requested intent has structured hash: {}",
             encode(intent_structured_hash)
         );
+        let submitted_at = Utc::now().naive_utc();
+        let context = ValidationContext {
+            now: submitted_at,
+            prices: self.config.token_prices(),
+        };
+        self.validator
+            .validate(&intent, &context)
+            .map_err(|rejection| {
+                info!(
+                    "Intent rejected by the {} rule: {}",
+                    rejection.rule, rejection.reason
+                );
+                Error::IntentRejected {
+                    rule: rejection.rule,
+                    reason: rejection.reason,
+                }
+            })?;
+        self.evict_expired_intents(submitted_at)?;
         let intent_id = self.update_current_id();
         info!("Current intent id is: {}", intent_id);
-        let batch = self.mempool.insert(intent_id, intent);
-
-        info!("Current batch is: {:?}", batch);
+        let pending_intent = PendingIntent {
+            id: intent_id,
+            intent: serde_json::to_string(&intent).map_err(|e| {
+                error!("Failed to serialize intent data to JSON, with error: {}", e);
+                Error::InternalError
+            })?,
+            created_at: submitted_at,
+        };
+        let tracked_intent = NewTrackedIntent {
+            intent_id,
+            structured_hash: encode(intent_structured_hash),
+            signer: encode(intent.public_key),
+            base_token: encode(intent.inputs.base_token),
+            quote_token: encode(intent.inputs.quote_token),
+            status: IntentStatus::Pending.as_str().to_string(),
+            created_at: submitted_at,
+            updated_at: submitted_at,
+        };
+        self.mempool
+            .insert(intent_id, intent, submitted_at)
+            .map_err(|e| {
+                error!("Intent rejected by the mempool, with error: {:?}", e);
+                match e {
+                    MempoolError::DuplicateIntent(id) => Error::DuplicateIntent(id),
+                    MempoolError::SignerLimitReached => Error::SignerLimitReached,
+                    MempoolError::ExpiredIntent => {
+                        Error::InvalidRequest("Intent has already expired".to_string())
+                    }
+                }
+            })?;
+        // the intent is only acknowledged once persisted, to be recovered after a restart
+        self.persist_intent(pending_intent, tracked_intent)?;
 
-        if batch.is_none() {
-            return Ok(StoreIntentResponse {
-                intent_id: Some(intent_id),
-                is_success: true,
-                message: String::from("Intent has been successfully submitted"),
-            });
+        if self.mempool.is_full() {
+            let batch = self.mempool.take(self.config.token_prices());
+            info!("Current batch is: {:?}", batch);
+            if let Err(e) = self.seal_batch(batch.clone(), submitted_at) {
+                // the intents remain pending, until the next sealing attempt
+                error!("Failed to seal full batch, with error: {}", e);
+                self.mempool.restore(batch);
+            }
         }
 
-        let batch = batch.unwrap();
-        let mut tx = self.storage_connection.create_transaction().map_err(|e| {
-            error!(
-                "Failed to store intent batch to database, with error: {}",
-                e
-            );
-            Error::InternalError
-        })?;
-
-        tx.store_intents(&batch).map_err(|e| {
-            error!(
-                "Failed to store intent batch to database, with error: {}",
-                e
-            );
-            Error::InternalError
-        })?;
-
         Ok(StoreIntentResponse {
             intent_id: Some(intent_id),
             is_success: true,
@@ -136,121 +369,62 @@ impl SolinaWorker {
         })
     }
 
-    pub fn handle_get_intent_request(
-        &self,
-        get_intent_request: GetIntentRequest,
-    ) -> Result<GetIntentResponse> {
-        let intent_id = get_intent_request.id;
-        // we first verify if the intent is still in the mempool
-        if let Some(intent) = self
-            .mempool
-            .mempool_data
-            .iter()
-            .find(|(id, _)| *id == intent_id as i64)
-            .map(|(_, int)| int)
+    /// Evicts the pending intents expired at `now` from the mempool.
+    fn evict_expired_intents(&mut self, now: NaiveDateTime) -> Result<()> {
+        let expired_intents = self.mempool.evict_expired(now);
+        if expired_intents.is_empty() {
+            return Ok(());
+        }
+        info!("Evicted {} expired intents", expired_intents.len());
+
+        let (expired_ids, expired_intents): (Vec<_>, Vec<_>) = expired_intents.into_iter().unzip();
         {
-            let intent_json = serde_json::to_value(intent).map_err(|e| {
-                error!("Failed to serialize intent data to JSON, with error: {}", e);
-                Error::InternalError
+            let mut tx = self.storage_connection.create_transaction().map_err(|e| {
+                error!("Failed to retrieve database transaction, with error: {}", e);
+                Error::StorageError(StorageErrorKind::Transaction)
             })?;
-            return Ok(GetIntentResponse {
-                intent_json,
-                message: String::from("GET intent successfully"),
-                is_success: true,
-            });
+            tx.update_intent_statuses(&expired_ids, IntentStatus::Expired, None, now)
+                .and_then(|_| tx.remove_pending_intents(&expired_ids))
+                .map_err(|e| {
+                    error!(
+                        "Failed to update expired intent statuses, with error: {}",
+                        e
+                    );
+                    Error::StorageError(StorageErrorKind::Write)
+                })?;
+            commit(&mut tx)?;
         }
-        // Otherwise, we need to query the database
-        let mut tx = self.storage_connection.create_transaction().map_err(|e| {
-            error!(
-                "Failed to create transaction on the database, with error: {:?}",
-                e
-            );
-            Error::InternalError
-        })?;
-        let intent = tx
-            .get_intent(intent_id)
-            .map_err(|e| {
-                error!("Failed to store batch of intents, with error: {:?}", e);
-                Error::InternalError
-            })?
-            .to_intent()
-            .map_err(|e| {
-                error!("Failed to convert intent, with error: {}", e);
-                Error::InternalError
-            })?;
-
-        let intent_json = serde_json::to_value(intent).map_err(|e| {
-            error!("Failed to serialize intent data to JSON, with error: {}", e);
-            Error::InternalError
-        })?;
+        self.publish_expired(expired_intents);
 
-        Ok(GetIntentResponse {
-            intent_json,
-            message: String::from("GET intent successfully"),
-            is_success: true,
-        })
+        Ok(())
     }
 
-    pub fn handle_get_batch_intents_request(
-        &self,
-        get_intent_request: GetBatchIntentsRequest,
-    ) -> Result<GetBatchIntentsResponse> {
-        let mut intent_ids = get_intent_request.ids;
-        // we first verify if the intent is still in the mempool
-        let batch_intents = self
-            .mempool
-            .mempool_data
-            .iter()
-            .filter_map(|(id, intent)| {
-                if intent_ids.contains(&(*id as i32)) {
-                    intent_ids.retain(|&i| i != *id as i32);
-                    Some(intent.clone())
-                } else {
-                    None
-                }
-            })
-            .collect::<Vec<Intent>>();
-
-        if intent_ids.is_empty() {
-            return Ok(GetBatchIntentsResponse {
-                batch_intents_json: batch_intents
-                    .iter()
-                    .map(|intent| {
-                        serde_json::to_value(intent).expect("Failed to deserialize intent")
-                    })
-                    .collect(),
-                message: String::from("GET batch intents successfully"),
-                is_success: true,
+    fn publish_expired(&self, expired_intents: Vec<Intent>) {
+        for intent in expired_intents {
+            self.publish(SolinaEvent::IntentExpired {
+                intent: encode(intent.structured_hash()),
+                signer: encode(intent.public_key),
+                token_pair: TokenPair::from_intent(&intent),
             });
         }
+    }
 
-        // let missing_intent_ids = intent_ids.iter().filter(|id| );
-
+    fn persist_intent(
+        &self,
+        pending_intent: PendingIntent,
+        tracked_intent: NewTrackedIntent,
+    ) -> Result<()> {
         let mut tx = self.storage_connection.create_transaction().map_err(|e| {
-            error!(
-                "Failed to store intent batch to database, with error: {}",
-                e
-            );
-            Error::InternalError
+            error!("Failed to retrieve database transaction, with error: {}", e);
+            Error::StorageError(StorageErrorKind::Transaction)
         })?;
-
-        let batch_intents = tx.get_intents_batch(&intent_ids).map_err(|e| {
-            error!("Failed to store batch of intents, with error: {:?}", e);
-            Error::InternalError
-        })?;
-        let batch_intents_json = batch_intents
-            .iter()
-            .map(|intent| {
-                let intent = intent.to_intent().expect("Failed to convert intent");
-                serde_json::to_value(intent).expect("Failed to deserialize intent")
-            })
-            .collect();
-
-        Ok(GetBatchIntentsResponse {
-            batch_intents_json,
-            message: String::from("GET batch intents successfully"),
-            is_success: true,
-        })
+        tx.insert_pending_intent(pending_intent)
+            .and_then(|_| tx.track_intent(tracked_intent))
+            .map_err(|e| {
+                error!("Failed to persist intent, with error: {}", e);
+                Error::StorageError(StorageErrorKind::Write)
+            })?;
+        commit(&mut tx)
     }
 
     pub fn handle_get_auth_credentials_request(
@@ -269,14 +443,15 @@ impl SolinaWorker {
                         "Failed to store intent batch to database, with error: {}",
                         e
                     );
-                    Error::InternalError
+                    Error::StorageError(StorageErrorKind::Write)
                 })?;
 
             tx.insert_new_credential(address, challenge.clone())
                 .map_err(|e| {
                     error!("Failed to insert new credential to DB, with error: {}", e);
-                    Error::InternalError
+                    Error::StorageError(StorageErrorKind::Write)
                 })?;
+            commit(&mut tx)?;
 
             info!("New challenge {}, stored in the database", challenge);
         }
@@ -288,6 +463,185 @@ impl SolinaWorker {
         })
     }
 
+    pub(crate) fn handle_submit_solution_request(
+        &mut self,
+        request: SubmitSolutionRequest,
+    ) -> Result<SubmitSolutionResponse> {
+        let (batch_id, solver_address) = (request.batch_id, request.address.clone());
+        let result = self.process_submit_solution_request(request);
+        if let Err(e) = &result {
+            self.publish(SolinaEvent::SolutionRejected {
+                batch_id,
+                solver_address,
+                reason: e.to_string(),
+            });
+        }
+        result
+    }
+
+    fn process_submit_solution_request(
+        &mut self,
+        request: SubmitSolutionRequest,
+    ) -> Result<SubmitSolutionResponse> {
+        let SubmitSolutionRequest {
+            address,
+            batch_id,
+            solution_json,
+            proof,
+        } = request;
+
+        let solution: BatchSolution =
+            serde_json::from_value(solution_json.clone()).map_err(|e| {
+                error!("Failed to deserialize batch solution, with error: {}", e);
+                Error::InvalidSolution(format!("Failed to deserialize solution: {}", e))
+            })?;
+        let proof_bytes = decode(&proof).map_err(|e| {
+            error!("Failed to decode proof bytes, with error: {}", e);
+            Error::ProofVerificationFailed(format!("Failed to decode proof: {}", e))
+        })?;
+
+        let batch = {
+            let mut tx = self.storage_connection.create_transaction().map_err(|e| {
+                error!("Failed to retrieve database transaction, with error: {}", e);
+                Error::StorageError(StorageErrorKind::Transaction)
+            })?;
+
+            let is_registered_solver = tx.is_registered_solver(&address).map_err(|e| {
+                error!("Failed to query registered solvers, with error: {}", e);
+                Error::StorageError(StorageErrorKind::Read)
+            })?;
+            if !is_registered_solver {
+                error!("Solution submitted by unregistered solver: {}", address);
+                return Err(Error::UnregisteredSolver(address));
+            }
+
+            tx.get_batch(batch_id).map_err(|e| {
+                error!("Failed to retrieve batch {}, with error: {}", batch_id, e);
+                match e {
+                    SolinaStorageError::NotFound(_) => Error::BatchNotFound(batch_id.to_string()),
+                    _ => Error::StorageError(StorageErrorKind::Read),
+                }
+            })?
+        };
+
+        let is_solving = batch.state().map_err(|e| {
+            error!(
+                "Invalid state stored for batch {}, with error: {}",
+                batch_id, e
+            );
+            Error::StorageError(StorageErrorKind::CorruptedData)
+        })? == BatchState::Solving;
+        let now = Utc::now().naive_utc();
+        // a missing deadline compares lower than any timestamp
+        if !is_solving || batch.solving_deadline <= Some(now) {
+            error!(
+                "Solution submitted outside of the solving window of batch {}",
+                batch_id
+            );
+            return Err(Error::SolvingWindowClosed);
+        }
+
+        let (root, sealed_at, price_snapshot) = sealed_batch_context(&batch)?;
+
+        let batch_intents = {
+            let mut tx = self.storage_connection.create_transaction().map_err(|e| {
+                error!("Failed to retrieve database transaction, with error: {}", e);
+                Error::StorageError(StorageErrorKind::Transaction)
+            })?;
+            let total_intents = tx.count_batch_intents(batch_id).map_err(|e| {
+                error!(
+                    "Failed to count intents of batch {}, with error: {}",
+                    batch_id, e
+                );
+                Error::StorageError(StorageErrorKind::Read)
+            })?;
+            tx.get_batch_intents(batch_id, 0, total_intents)
+                .map_err(|e| {
+                    error!(
+                        "Failed to retrieve intents of batch {}, with error: {}",
+                        batch_id, e
+                    );
+                    Error::StorageError(StorageErrorKind::Read)
+                })?
+                .iter()
+                .map(|intent| {
+                    intent.to_intent().map_err(|e| {
+                        error!("Failed to convert intent, with error: {}", e);
+                        Error::StorageError(StorageErrorKind::CorruptedData)
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?
+        };
+
+        solution
+            .validate_batch(&batch_intents, &sealed_at)
+            .map_err(|e| {
+                error!("Invalid solution for batch {}, with error: {}", batch_id, e);
+                Error::InvalidSolution(e.to_string())
+            })?;
+        // the total liquidity committed to by the proof must be priced with the batch snapshot
+        if solution.total_liquidity_with(&price_snapshot) != *solution.total_liquidity() {
+            error!(
+                "Total liquidity of solution for batch {} does not match its price snapshot",
+                batch_id
+            );
+            return Err(Error::InvalidSolution(
+                "Total liquidity does not match the batch price snapshot".to_string(),
+            ));
+        }
+        self.verify_solution_proof(
+            batch_id,
+            root,
+            sealed_at,
+            &price_snapshot,
+            &solution,
+            &proof_bytes,
+        )?;
+
+        let score = self.config.scoring_metric().score(&solution).to_string();
+        {
+            let mut tx = self.storage_connection.create_transaction().map_err(|e| {
+                error!("Failed to retrieve database transaction, with error: {}", e);
+                Error::StorageError(StorageErrorKind::Transaction)
+            })?;
+            // nullifiers are only spent once the winning solution is selected, but solutions
+            // settling already spent intents can be rejected right away
+            let nullifiers = solution.nullifiers().iter().map(encode).collect::<Vec<_>>();
+            if is_any_nullifier_spent(&mut tx, &nullifiers)? {
+                error!("Solution for batch {} reuses spent nullifiers", batch_id);
+                return Err(Error::SpentNullifier);
+            }
+            tx.store_solution(NewSolution {
+                batch_id,
+                solver_address: address.clone(),
+                solution: solution_json.to_string(),
+                proof,
+                score: score.clone(),
+                created_at: Utc::now().naive_utc(),
+            })
+            .map_err(|e| {
+                error!("Failed to store solution to DB, with error: {}", e);
+                Error::StorageError(StorageErrorKind::Write)
+            })?;
+            commit(&mut tx)?;
+        }
+
+        info!(
+            "New solution for batch {}, by solver {}, stored in the database",
+            batch_id, address
+        );
+        self.publish(SolinaEvent::SolutionAccepted {
+            batch_id,
+            solver_address: address,
+            score,
+        });
+
+        Ok(SubmitSolutionResponse {
+            is_success: true,
+            message: "Solution successfully verified and submitted".to_string(),
+        })
+    }
+
     pub(crate) fn handle_solver_registration(
         &mut self,
         request: RegisterSolverRequest,
@@ -302,7 +656,7 @@ impl SolinaWorker {
                 "Failed to extract Address from public key, address = {}, error = {}",
                 solver_address, e
             );
-            Error::InternalError
+            Error::InvalidRequest(format!("Invalid solver address: {}", solver_address))
         })?;
         match Signature::from_str(&address_signature) {
             Ok(sig) => sig.verify(solver_address.clone(), address).map_err(|e| {
@@ -310,11 +664,16 @@ impl SolinaWorker {
                     "Failed to recover solver address hash from signature, with error: {}",
                     e
                 );
-                Error::InvalidRequest
+                Error::InvalidRequest(
+                    "Address signature does not match the solver address".to_string(),
+                )
             })?,
             Err(e) => {
                 error!("Failed to obtain signature from request, with error: {}", e);
-                return Err(Error::InvalidRequest);
+                return Err(Error::InvalidRequest(format!(
+                    "Invalid address signature: {}",
+                    e
+                )));
             }
         };
 
@@ -324,13 +683,14 @@ impl SolinaWorker {
                 .create_transaction()
                 .map_err(|e| {
                     error!("Failed to retrieve database transaction, with error: {}", e);
-                    Error::InternalError
+                    Error::StorageError(StorageErrorKind::Transaction)
                 })?;
 
             tx.register_solver(solver_address.clone()).map_err(|e| {
                 error!("Failed to store new solver data to DB, with error: {}", e);
-                Error::InternalError
+                Error::StorageError(StorageErrorKind::Write)
             })?;
+            commit(&mut tx)?;
 
             info!(
                 "New solver with address={}, registered in the database",
@@ -345,20 +705,485 @@ impl SolinaWorker {
     }
 }
 
+fn commit(tx: &mut ReadWriterTransaction) -> Result<()> {
+    tx.commit().map_err(|e| {
+        error!("Failed to commit database transaction, with error: {}", e);
+        Error::StorageError(StorageErrorKind::Transaction)
+    })
+}
+
+/// Root, sealing timestamp and price snapshot of a sealed batch, which its solution proofs
+/// are verified against. A batch sealed without any of them is corrupted, as its solutions
+/// could not be checked.
+fn sealed_batch_context(batch: &Batch) -> Result<(StructuredHash, NaiveDateTime, PriceSnapshot)> {
+    let sealed_at = batch.sealed_at.ok_or_else(|| {
+        error!("Missing sealing timestamp for batch {}", batch.id);
+        Error::StorageError(StorageErrorKind::CorruptedData)
+    })?;
+    let mut root = [0u8; 32];
+    batch
+        .root
+        .as_deref()
+        .and_then(|root| decode(root).ok())
+        .filter(|bytes| bytes.len() == 32)
+        .map(|bytes| root.copy_from_slice(&bytes))
+        .ok_or_else(|| {
+            error!("Invalid root stored for batch {}", batch.id);
+            Error::StorageError(StorageErrorKind::CorruptedData)
+        })?;
+    let price_snapshot = batch
+        .price_snapshot
+        .as_deref()
+        .ok_or_else(|| {
+            error!("Missing price snapshot for batch {}", batch.id);
+            Error::StorageError(StorageErrorKind::CorruptedData)
+        })
+        .and_then(|price_snapshot| {
+            serde_json::from_str(price_snapshot).map_err(|e| {
+                error!(
+                    "Invalid price snapshot stored for batch {}, with error: {}",
+                    batch.id, e
+                );
+                Error::StorageError(StorageErrorKind::CorruptedData)
+            })
+        })?;
+    Ok((root, sealed_at, price_snapshot))
+}
+
+fn is_any_nullifier_spent(tx: &mut ReadWriterTransaction, nullifiers: &[String]) -> Result<bool> {
+    let spent_nullifiers = tx.get_spent_nullifiers(nullifiers).map_err(|e| {
+        error!("Failed to query spent nullifiers, with error: {}", e);
+        Error::StorageError(StorageErrorKind::Read)
+    })?;
+    Ok(!spent_nullifiers.is_empty())
+}
+
 impl SolinaWorker {
+    /// Stores `batch` as the current batch, sealed at `sealed_at`, and opens the next one.
+    /// Intents expired at `sealed_at` are left out of the batch by the storage, and reported
+    /// as expired. Returns the id of the sealed batch, if any intent was left to seal.
+    fn seal_batch(
+        &mut self,
+        batch: Vec<(IntentId, Intent)>,
+        sealed_at: NaiveDateTime,
+    ) -> Result<Option<i32>> {
+        let mut tx = self.storage_connection.create_transaction().map_err(|e| {
+            error!(
+                "Failed to store intent batch to database, with error: {}",
+                e
+            );
+            Error::StorageError(StorageErrorKind::Write)
+        })?;
+
+        let batch_ids = tx.store_intents(&batch, sealed_at).map_err(|e| {
+            error!(
+                "Failed to store intent batch to database, with error: {}",
+                e
+            );
+            Error::StorageError(StorageErrorKind::Write)
+        })?;
+
+        // intents expired at `sealed_at` were left out of the batch by the storage
+        let (batch, expired_intents): (Vec<_>, Vec<_>) = batch
+            .into_iter()
+            .partition(|(id, _)| batch_ids.contains(id));
+        let (expired_ids, expired_intents): (Vec<_>, Vec<_>) =
+            expired_intents.into_iter().unzip();
+        if !expired_ids.is_empty() {
+            tx.update_intent_statuses(&expired_ids, IntentStatus::Expired, None, sealed_at)
+                .and_then(|_| tx.remove_pending_intents(&expired_ids))
+                .map_err(|e| {
+                    error!(
+                        "Failed to update expired intent statuses, with error: {}",
+                        e
+                    );
+                    Error::StorageError(StorageErrorKind::Write)
+                })?;
+        }
+        if batch.is_empty() {
+            commit(&mut tx)?;
+            self.publish_expired(expired_intents);
+            return Ok(None);
+        }
+
+        let batch_intents = batch
+            .into_iter()
+            .map(|(_, intent)| intent)
+            .collect::<Vec<_>>();
+        let price_snapshot = PriceSnapshot::new(
+            self.config.token_prices(),
+            batch_intents
+                .iter()
+                .flat_map(|intent| [intent.inputs.quote_token, intent.inputs.base_token]),
+        );
+        let price_snapshot = serde_json::to_string(&price_snapshot).map_err(|e| {
+            error!("Failed to serialize price snapshot, with error: {}", e);
+            Error::InternalError
+        })?;
+        let root = encode(batch_root(&batch_intents));
+        let batch_id = tx
+            .seal_batch(root.clone(), price_snapshot, sealed_at)
+            .map_err(|e| {
+                error!("Failed to seal intent batch, with error: {}", e);
+                Error::StorageError(StorageErrorKind::Write)
+            })?;
+        info!("Sealed batch with id: {}", batch_id);
+        tx.update_intent_statuses(&batch_ids, IntentStatus::Batched, Some(batch_id), sealed_at)
+            .and_then(|_| tx.remove_pending_intents(&batch_ids))
+            .map_err(|e| {
+                error!(
+                    "Failed to update intent statuses of batch {}, with error: {}",
+                    batch_id, e
+                );
+                Error::StorageError(StorageErrorKind::Write)
+            })?;
+        commit(&mut tx)?;
+        self.publish_expired(expired_intents);
+
+        let mut token_pairs = vec![];
+        for pair in batch_intents.iter().map(TokenPair::from_intent) {
+            if !token_pairs.contains(&pair) {
+                token_pairs.push(pair);
+            }
+        }
+        self.publish(SolinaEvent::BatchSealed {
+            batch_id,
+            root,
+            sealed_at,
+            token_pairs,
+        });
+
+        Ok(Some(batch_id))
+    }
+
+    /// Drives the lifecycle of the batches at time `now`. The current batch is sealed once
+    /// the sealing interval has elapsed, provided it collected any intent. Sealed batches
+    /// are then open to solutions for the solving window, after which a solution is
+    /// selected, if any was submitted, and settled. Each batch moves by at most one state
+    /// per call.
+    pub fn advance_batch_lifecycle(&mut self, now: NaiveDateTime) -> Result<()> {
+        let mut tx = self.storage_connection.create_transaction().map_err(|e| {
+            error!("Failed to retrieve database transaction, with error: {}", e);
+            Error::StorageError(StorageErrorKind::Transaction)
+        })?;
+        let mut batches_in_state = |state: BatchState| {
+            tx.get_batches_in_state(state).map_err(|e| {
+                error!(
+                    "Failed to retrieve {} batches, with error: {}",
+                    state.as_str(),
+                    e
+                );
+                Error::StorageError(StorageErrorKind::Read)
+            })
+        };
+
+        let selected_batches = batches_in_state(BatchState::SolutionSelected)?;
+        let solving_batches = batches_in_state(BatchState::Solving)?;
+        let sealed_batches = batches_in_state(BatchState::Sealed)?;
+        let collecting_batches = batches_in_state(BatchState::Collecting)?;
+
+        // TODO: settle the selected solution on chain, for now its nullifiers are spent on
+        // selection
+        for batch in selected_batches {
+            tx.update_batch_state(batch.id, BatchState::Settled, now)
+                .and_then(|_| tx.settle_batch_intents(batch.id, now))
+                .map_err(|e| {
+                    error!("Failed to settle batch {}, with error: {}", batch.id, e);
+                    Error::StorageError(StorageErrorKind::Write)
+                })?;
+            info!("Settled batch with id: {}", batch.id);
+        }
+
+        // events are only published once committed, so that subscribers never see selections,
+        // or fills, which are rolled back
+        let mut events = vec![];
+        for batch in solving_batches {
+            if batch.solving_deadline > Some(now) {
+                continue;
+            }
+            let selection = match self.select_solutions(&mut tx, &batch)? {
+                Some(selection) => selection,
+                None => {
+                    // without any valid solution, there is nothing left to settle
+                    tx.update_batch_state(batch.id, BatchState::Settled, now)
+                        .map_err(|e| {
+                            error!(
+                                "Failed to close solving window of batch {}, with error: {}",
+                                batch.id, e
+                            );
+                            Error::StorageError(StorageErrorKind::Write)
+                        })?;
+                    info!("No valid solution submitted for batch {}", batch.id);
+                    continue;
+                }
+            };
+
+            tx.select_solutions(batch.id, &selection.solution_ids, now)
+                .map_err(|e| {
+                    error!(
+                        "Failed to select solutions of batch {}, with error: {}",
+                        batch.id, e
+                    );
+                    Error::StorageError(StorageErrorKind::Write)
+                })?;
+            info!(
+                "Selected solutions {:?} to settle batch {}",
+                selection.solution_ids, batch.id
+            );
+
+            events.push(SolinaEvent::WinnerSelected {
+                batch_id: batch.id,
+                solution_ids: selection.solution_ids,
+                solver_addresses: selection.solver_addresses,
+            });
+            let intents = selection
+                .settlement
+                .batch_matches()
+                .iter()
+                .flat_map(|m| [m.intent_a(), m.intent_b()])
+                .map(|intent| (intent.key(), intent))
+                .collect::<BTreeMap<_, _>>();
+            for (key, (swapped, received)) in selection.settlement.matched_amounts() {
+                let (intent, hash) = (intents[&key], key.0);
+                tx.fill_intent(
+                    batch.id,
+                    &encode(hash),
+                    &encode(intent.public_key),
+                    swapped.to_string(),
+                    received.to_string(),
+                    now,
+                )
+                .map_err(|e| {
+                    error!(
+                        "Failed to record fill of intent {} in batch {}, with error: {}",
+                        encode(hash),
+                        batch.id,
+                        e
+                    );
+                    Error::StorageError(StorageErrorKind::Write)
+                })?;
+                events.push(SolinaEvent::IntentFilled {
+                    batch_id: batch.id,
+                    intent: encode(hash),
+                    signer: encode(intent.public_key),
+                    token_pair: TokenPair::from_intent(intent),
+                    quote_amount_swapped: swapped.to_string(),
+                    base_amount_received: received.to_string(),
+                });
+            }
+        }
+
+        let solving_deadline = now + Duration::seconds(self.config.solving_window() as i64);
+        for batch in sealed_batches {
+            tx.open_solving_window(batch.id, solving_deadline, now)
+                .map_err(|e| {
+                    error!(
+                        "Failed to open solving window of batch {}, with error: {}",
+                        batch.id, e
+                    );
+                    Error::StorageError(StorageErrorKind::Write)
+                })?;
+            info!(
+                "Opened solving window of batch {}, until {}",
+                batch.id, solving_deadline
+            );
+        }
+        commit(&mut tx)?;
+        drop(tx);
+        for event in events {
+            self.publish(event);
+        }
+
+        let sealing_interval = Duration::seconds(self.config.batch_sealing_interval() as i64);
+        let is_sealing_due = collecting_batches
+            .iter()
+            .any(|batch| batch.created_at + sealing_interval <= now);
+        self.evict_expired_intents(now)?;
+        if is_sealing_due && !self.mempool.is_empty() {
+            let batch = self.mempool.take(self.config.token_prices());
+            if let Err(e) = self.seal_batch(batch.clone(), now) {
+                // the intents remain pending, until the next sealing attempt
+                self.mempool.restore(batch);
+                return Err(e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Selects the solutions settling batch `batch_id`, following the configured selection
+    /// policy, and spends their nullifiers. Submissions settling intents whose nullifiers
+    /// have been spent in the meantime are discarded.
+    /// Verifies that `proof` attests to `solution`, with the amounts and prices it settles,
+    /// for the batch `batch_id`, sealed at `sealed_at` with the given root and prices.
+    fn verify_solution_proof(
+        &self,
+        batch_id: i32,
+        root: StructuredHash,
+        sealed_at: NaiveDateTime,
+        price_snapshot: &PriceSnapshot,
+        solution: &BatchSolution,
+        proof: &[u8],
+    ) -> Result<()> {
+        let (_, verifier_data) = read_verifier_artifacts(
+            self.config.circuit_artifacts_dir(),
+            &self.config.solution_circuit_id(),
+        )
+        .map_err(|e| {
+            error!(
+                "Failed to read solution circuit verifier data, with error: {}",
+                e
+            );
+            Error::InternalError
+        })?;
+        let public_inputs =
+            BatchSolutionPublicInputs::new(root, sealed_at, solution, price_snapshot);
+        verify_batch_solution_proof(&verifier_data, proof, &public_inputs).map_err(|e| {
+            error!(
+                "Failed to verify solution proof for batch {}, with error: {}",
+                batch_id, e
+            );
+            Error::ProofVerificationFailed(e.to_string())
+        })
+    }
+
+    /// Selects the solutions settling `batch`, among the stored ones. Stored proofs are
+    /// verified again, so that only amounts attested to by a proof are ever filled.
+    fn select_solutions(
+        &self,
+        tx: &mut ReadWriterTransaction,
+        batch: &Batch,
+    ) -> Result<Option<Selection>> {
+        let batch_id = batch.id;
+        let (root, sealed_at, price_snapshot) = sealed_batch_context(batch)?;
+        let solutions = tx.get_solutions(batch_id).map_err(|e| {
+            error!(
+                "Failed to retrieve solutions of batch {}, with error: {}",
+                batch_id, e
+            );
+            Error::StorageError(StorageErrorKind::Read)
+        })?;
+
+        let mut candidates = vec![];
+        for solution in solutions {
+            let score = BigUint::from_str(&solution.score).map_err(|e| {
+                error!(
+                    "Invalid score stored for solution {}, with error: {}",
+                    solution.id, e
+                );
+                Error::StorageError(StorageErrorKind::CorruptedData)
+            })?;
+            let batch_solution: BatchSolution =
+                serde_json::from_str(&solution.solution).map_err(|e| {
+                    error!(
+                        "Failed to deserialize solution {}, with error: {}",
+                        solution.id, e
+                    );
+                    Error::StorageError(StorageErrorKind::CorruptedData)
+                })?;
+            let verification = decode(&solution.proof)
+                .map_err(|e| Error::ProofVerificationFailed(e.to_string()))
+                .and_then(|proof| {
+                    self.verify_solution_proof(
+                        batch_id,
+                        root,
+                        sealed_at,
+                        &price_snapshot,
+                        &batch_solution,
+                        &proof,
+                    )
+                });
+            if let Err(e) = verification {
+                error!(
+                    "Discarding solution {} of batch {}, whose proof does not verify: {}",
+                    solution.id, batch_id, e
+                );
+                continue;
+            }
+
+            let nullifiers = batch_solution
+                .nullifiers()
+                .iter()
+                .map(encode)
+                .collect::<Vec<_>>();
+            if is_any_nullifier_spent(tx, &nullifiers)? {
+                info!(
+                    "Discarding solution {} of batch {}, reusing spent nullifiers",
+                    solution.id, batch_id
+                );
+                continue;
+            }
+
+            let submission = Submission {
+                id: solution.id as i64,
+                solver: solution.solver_address,
+                score,
+                submitted_at: solution.created_at,
+            };
+            candidates.push((submission, batch_solution));
+        }
+
+        let selection = match self.config.selection_policy() {
+            SelectionPolicy::Winner => candidates
+                .into_iter()
+                .min_by(|(a, _), (b, _)| a.rank(b))
+                .map(|(submission, solution)| Selection {
+                    solution_ids: vec![submission.id as i32],
+                    solver_addresses: vec![submission.solver],
+                    settlement: solution,
+                }),
+            SelectionPolicy::Combinatorial => {
+                combine_solutions(candidates).map(|combined| Selection {
+                    solution_ids: combined
+                        .submission_ids
+                        .into_iter()
+                        .map(|id| id as i32)
+                        .collect(),
+                    solver_addresses: combined.solver_scores.into_keys().collect(),
+                    settlement: combined.solution,
+                })
+            }
+        };
+
+        if let Some(selection) = &selection {
+            let nullifiers = selection
+                .settlement
+                .nullifiers()
+                .iter()
+                .map(encode)
+                .collect::<Vec<_>>();
+            tx.insert_nullifiers(&nullifiers, batch_id).map_err(|e| {
+                error!("Failed to store nullifiers to DB, with error: {}", e);
+                Error::StorageError(StorageErrorKind::Write)
+            })?;
+        }
+
+        Ok(selection)
+    }
+
     pub(crate) fn get_current_credential(&mut self, address: &String) -> Result<AuthCredentials> {
         let mut tx = self
             .storage_connection()
             .create_transaction()
             .map_err(|e| {
                 error!("Failed to connect to the database, with error: {}", e);
-                Error::InternalError
+                Error::StorageError(StorageErrorKind::Transaction)
             })?;
 
-        tx.get_current_auth_credential(address).map_err(|e| {
-            error!("Failed to insert new credential to DB, with error: {}", e);
-            Error::InternalError
-        })
+        tx.get_current_auth_credential(address)
+            .map_err(|e| match e {
+                SolinaStorageError::NotFound(_) => {
+                    error!(
+                        "User {} does not have a valid challenge in memory to sign",
+                        address
+                    );
+                    Error::AuthError(format!("No challenge to sign for address {}", address))
+                }
+                _ => {
+                    error!("Failed to retrieve credential from DB, with error: {}", e);
+                    Error::StorageError(StorageErrorKind::Read)
+                }
+            })
     }
 
     pub(crate) fn update_is_valid_credential(&mut self, id: i32) -> Result<()> {
@@ -370,12 +1195,13 @@ impl SolinaWorker {
                     "Failed to update is_valid credential to database, with error: {}",
                     e
                 );
-                Error::InternalError
+                Error::StorageError(StorageErrorKind::Write)
             })?;
         tx.update_is_valid_credential(id).map_err(|e| {
             error!("Failed to update is_auth credential, with error: {}", e);
-            Error::InternalError
-        })
+            Error::StorageError(StorageErrorKind::Write)
+        })?;
+        commit(&mut tx)
     }
 
     pub(crate) fn update_is_auth_credential(&mut self, id: i32) -> Result<()> {
@@ -387,11 +1213,557 @@ impl SolinaWorker {
                     "Failed to update is_auth credential to database, with error: {}",
                     e
                 );
-                Error::InternalError
+                Error::StorageError(StorageErrorKind::Write)
             })?;
         tx.update_is_auth_credential(id).map_err(|e| {
             error!("Failed to update is_valid credential, with error: {}", e);
-            Error::InternalError
+            Error::StorageError(StorageErrorKind::Write)
+        })?;
+        commit(&mut tx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        mempool::OrderingPolicy,
+        reader::SolinaReader,
+        types::{GetIntentRequest, GetIntentStatusRequest, GetLatestSealedBatchRequest},
+    };
+    use plonky2::field::{secp256k1_scalar::Secp256K1Scalar, types::Field};
+    use plonky2_ecdsa::curve::ecdsa::ECDSASecretKey;
+    use solina::{
+        intent::{IntentConstraints, IntentInputs, TradeDirection},
+        solver::{Match, SwappedAmount},
+        Signature,
+    };
+    use solina_circuits::{
+        batch_circuit::{batch_solution_prover, batch_solution_registry, batch_solution_shape},
+        prover::{SolutionProver, SolutionWitness},
+        signature::sign_intent,
+    };
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    /// Database files of a test, removed once dropped.
+    struct TestStorage(PathBuf);
+
+    impl TestStorage {
+        fn new(name: &str) -> Self {
+            let storage = Self(std::env::temp_dir().join(format!(
+                "solina-{}-{}.sqlite",
+                name,
+                std::process::id()
+            )));
+            storage.remove();
+            storage
+        }
+
+        /// Removes the database, with its write-ahead log.
+        fn remove(&self) {
+            for suffix in ["", "-wal", "-shm"] {
+                let mut path = self.0.clone().into_os_string();
+                path.push(suffix);
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+
+    impl Drop for TestStorage {
+        fn drop(&mut self) {
+            self.remove();
+        }
+    }
+
+    fn config(storage_file_path: &Path, mempool_capacity: usize) -> SolinaConfig {
+        let default = SolinaConfig::default();
+        ordered_config(
+            storage_file_path,
+            mempool_capacity,
+            default.mempool_ordering(),
+            default.token_prices().clone(),
+        )
+    }
+
+    fn ordered_config(
+        storage_file_path: &Path,
+        mempool_capacity: usize,
+        mempool_ordering: OrderingPolicy,
+        token_prices: PriceSnapshot,
+    ) -> SolinaConfig {
+        let default = SolinaConfig::default();
+        SolinaConfig::new(
+            mempool_capacity,
+            default.max_intents_per_signer(),
+            mempool_ordering,
+            default.intent_validation().to_vec(),
+            storage_file_path.to_path_buf(),
+            default.socket_address(),
+            default.auth_credential_timeout(),
+            default.circuit_artifacts_dir().clone(),
+            None,
+            default.batch_sealing_interval(),
+            default.solving_window(),
+            default.scoring_metric(),
+            default.selection_policy(),
+            token_prices,
+        )
+    }
+
+    fn intent(signer: u8, quote_amount: u64) -> Intent {
+        Intent::new(
+            [signer; 32],
+            IntentInputs::new(
+                [1; 32],
+                [2; 32],
+                BigUint::from(quote_amount),
+                TradeDirection::Sell,
+            ),
+            IntentConstraints::new(BigUint::from(0_u8)),
+            Signature([0u8; 64]),
+            // within the expiry horizon, while identical across calls
+            Utc::now().date_naive().and_hms_opt(0, 0, 0).unwrap() + Duration::days(2),
+        )
+    }
+
+    /// An intent quoting 1000 tokens, for at least 900 base tokens, signed with `secret_key`.
+    fn signed_intent(secret_key: u64, tokens: (u8, u8)) -> Intent {
+        let mut intent = Intent::new(
+            [0; 32],
+            IntentInputs::new(
+                [tokens.0; 32],
+                [tokens.1; 32],
+                BigUint::from(1_000_u64),
+                TradeDirection::Sell,
+            ),
+            IntentConstraints::new(BigUint::from(900_u64)),
+            Signature([0u8; 64]),
+            Utc::now().date_naive().and_hms_opt(0, 0, 0).unwrap() + Duration::days(2),
+        );
+        sign_intent(
+            &mut intent,
+            ECDSASecretKey(Secp256K1Scalar::from_canonical_u64(secret_key)),
+        );
+        intent
+    }
+
+    fn store_intent(worker: &mut SolinaWorker, intent: &Intent) -> Result<IntentId> {
+        worker
+            .handle_post_store_intent_request(StoreIntentRequest {
+                intent_json: serde_json::to_value(intent).unwrap(),
+            })
+            .map(|response| response.intent_id.unwrap())
+    }
+
+    fn intent_status(worker: &SolinaWorker, id: IntentId) -> String {
+        SolinaReader::new(worker.config().clone())
+            .unwrap()
+            .handle_get_intent_status_request(GetIntentStatusRequest {
+                id: Some(id),
+                structured_hash: None,
+            })
+            .unwrap()
+            .intent
+            .status
+    }
+
+    /// Batch solution circuit artifacts of a test, removed once dropped.
+    struct TestArtifactsDir(PathBuf);
+
+    impl TestArtifactsDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("solina-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            Self(path)
+        }
+    }
+
+    impl Drop for TestArtifactsDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Shuts the worker down, as the service would be before a restart.
+    fn shutdown(worker: SolinaWorker) {
+        let handle = worker.spawn().unwrap();
+        futures::executor::block_on(handle.shutdown()).unwrap();
+    }
+
+    /// Stops the worker without releasing anything, as if the service was killed: the
+    /// worker is never dropped, so that only what it committed to storage survives.
+    fn kill(worker: SolinaWorker) {
+        std::mem::forget(worker);
+    }
+
+    #[test]
+    fn it_recovers_pending_intents_after_restart() {
+        let storage = TestStorage::new("recovery");
+        let mut worker = SolinaWorker::new(config(&storage.0, 3)).unwrap();
+        let (a, b) = (intent(1, 10), intent(2, 20));
+        assert_eq!(store_intent(&mut worker, &a).unwrap(), 1);
+        assert_eq!(store_intent(&mut worker, &b).unwrap(), 2);
+        // killed after acknowledging the intents, and before sealing them
+        kill(worker);
+
+        let mut worker = SolinaWorker::new(config(&storage.0, 3)).unwrap();
+        assert_eq!(worker.mempool.len(), 2);
+        assert_eq!(
+            worker.mempool.get(1).unwrap().structured_hash(),
+            a.structured_hash()
+        );
+        assert_eq!(intent_status(&worker, 2), "pending");
+        // recovered intents are still deduplicated
+        assert!(matches!(
+            store_intent(&mut worker, &a),
+            Err(Error::DuplicateIntent(1))
+        ));
+
+        // ids of new intents do not collide with recovered ones, and the batch is sealed
+        // once full
+        assert_eq!(store_intent(&mut worker, &intent(3, 30)).unwrap(), 3);
+        assert!(worker.mempool.is_empty());
+        assert_eq!(intent_status(&worker, 1), "batched");
+        shutdown(worker);
+
+        // sealed intents are not recovered again
+        let worker = SolinaWorker::new(config(&storage.0, 3)).unwrap();
+        assert!(worker.mempool.is_empty());
+    }
+
+    #[test]
+    fn it_keeps_intent_ids_unique_across_restarts() {
+        let storage = TestStorage::new("intent-ids");
+        let mut worker = SolinaWorker::new(config(&storage.0, 2)).unwrap();
+        store_intent(&mut worker, &intent(1, 10)).unwrap();
+        store_intent(&mut worker, &intent(2, 20)).unwrap();
+        // the batch is sealed, so that no intent is pending anymore
+        assert!(worker.mempool.is_empty());
+        shutdown(worker);
+
+        let mut worker = SolinaWorker::new(config(&storage.0, 2)).unwrap();
+        assert_eq!(store_intent(&mut worker, &intent(3, 30)).unwrap(), 3);
+        let reader = SolinaReader::new(worker.config().clone()).unwrap();
+        let response = reader
+            .handle_get_intent_request(GetIntentRequest { id: 1 })
+            .unwrap();
+        let stored_intent = serde_json::from_value::<Intent>(response.intent_json).unwrap();
+        assert_eq!(stored_intent.public_key, [1; 32]);
+    }
+
+    #[test]
+    fn it_rejects_invalid_intents_before_assigning_ids() {
+        let storage = TestStorage::new("validation");
+        let mut worker = SolinaWorker::new(config(&storage.0, 2)).unwrap();
+        match store_intent(&mut worker, &intent(1, 0)) {
+            Err(Error::IntentRejected { rule, .. }) => assert_eq!(rule, "non_zero_amounts"),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+        assert!(worker.mempool.is_empty());
+        assert_eq!(store_intent(&mut worker, &intent(1, 10)).unwrap(), 1);
+    }
+
+    #[test]
+    fn it_seals_recovered_intents() {
+        let storage = TestStorage::new("recovery-sealing");
+        let mut worker = SolinaWorker::new(config(&storage.0, 5)).unwrap();
+        store_intent(&mut worker, &intent(1, 10)).unwrap();
+        shutdown(worker);
+
+        let mut worker = SolinaWorker::new(config(&storage.0, 5)).unwrap();
+        let sealing_interval = Duration::seconds(worker.config().batch_sealing_interval() as i64);
+        worker
+            .advance_batch_lifecycle(Utc::now().naive_utc() + sealing_interval)
+            .unwrap();
+        assert!(worker.mempool.is_empty());
+        assert_eq!(intent_status(&worker, 1), "batched");
+        shutdown(worker);
+
+        let worker = SolinaWorker::new(config(&storage.0, 5)).unwrap();
+        assert!(worker.mempool.is_empty());
+    }
+
+    #[test]
+    fn it_evicts_expired_intents_before_sealing() {
+        let storage = TestStorage::new("expiry-sealing");
+        let mut worker = SolinaWorker::new(config(&storage.0, 5)).unwrap();
+        let mut events = worker.events.subscribe();
+        let expiring_intent = intent(1, 10);
+        let mut lasting_intent = intent(2, 20);
+        lasting_intent.expiry_date += Duration::days(3);
+        store_intent(&mut worker, &expiring_intent).unwrap();
+        store_intent(&mut worker, &lasting_intent).unwrap();
+
+        worker
+            .advance_batch_lifecycle(expiring_intent.expiry_date)
+            .unwrap();
+        assert!(worker.mempool.is_empty());
+        assert_eq!(intent_status(&worker, 1), "expired");
+        assert_eq!(intent_status(&worker, 2), "batched");
+        match events.try_recv().unwrap() {
+            SolinaEvent::IntentExpired { intent, .. } => {
+                assert_eq!(intent, encode(expiring_intent.structured_hash()))
+            }
+            event => panic!("Unexpected event: {:?}", event),
+        }
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            SolinaEvent::BatchSealed { .. }
+        ));
+    }
+
+    #[test]
+    fn it_leaves_expired_intents_out_of_sealed_batches() {
+        let storage = TestStorage::new("expiry-storage");
+        let mut worker = SolinaWorker::new(config(&storage.0, 5)).unwrap();
+        let expiring_intent = intent(1, 10);
+        let mut lasting_intent = intent(2, 20);
+        lasting_intent.expiry_date += Duration::days(3);
+        store_intent(&mut worker, &expiring_intent).unwrap();
+        store_intent(&mut worker, &lasting_intent).unwrap();
+
+        // the batch is sealed without evicting the expired intent first
+        let batch = worker.mempool.take(worker.config().token_prices());
+        assert_eq!(batch.len(), 2);
+        assert!(worker
+            .seal_batch(batch, expiring_intent.expiry_date)
+            .unwrap()
+            .is_some());
+        assert_eq!(intent_status(&worker, 1), "expired");
+        assert_eq!(intent_status(&worker, 2), "batched");
+
+        let batch = SolinaReader::new(worker.config().clone())
+            .unwrap()
+            .handle_get_latest_sealed_batch_request(GetLatestSealedBatchRequest {
+                offset: None,
+                limit: None,
+            })
+            .unwrap();
+        assert_eq!(
+            batch.intents.iter().map(|intent| intent.id).collect::<Vec<_>>(),
+            vec![2]
+        );
+        assert_eq!(batch.root, Some(encode(batch_root(&[lasting_intent]))));
+    }
+
+    #[test]
+    fn it_serves_batches_in_root_order() {
+        let storage = TestStorage::new("batch-order");
+        let token_prices = PriceSnapshot {
+            prices: BTreeMap::from([(encode([1; 32]), BigUint::from(1_u8))]),
+        };
+        let mut worker = SolinaWorker::new(ordered_config(
+            &storage.0,
+            3,
+            OrderingPolicy::FeePriority,
+            token_prices,
+        ))
+        .unwrap();
+        for (signer, quote_amount) in [(1, 10), (2, 30), (3, 20)] {
+            store_intent(&mut worker, &intent(signer, quote_amount)).unwrap();
+        }
+
+        let batch = SolinaReader::new(worker.config().clone())
+            .unwrap()
+            .handle_get_latest_sealed_batch_request(GetLatestSealedBatchRequest {
+                offset: None,
+                limit: None,
+            })
+            .unwrap();
+        let (ids, intents): (Vec<_>, Vec<_>) = batch
+            .intents
+            .into_iter()
+            .map(|intent| {
+                let id = intent.id;
+                (
+                    id,
+                    serde_json::from_value::<Intent>(intent.intent_json).unwrap(),
+                )
+            })
+            .unzip();
+        // intents are batched by decreasing fees, rather than by id
+        assert_eq!(ids, vec![2, 3, 1]);
+        assert_eq!(batch.root, Some(encode(batch_root(&intents))));
+    }
+
+    #[test]
+    fn it_accepts_proven_solutions() {
+        let storage = TestStorage::new("proven-solution");
+        let artifacts_dir = TestArtifactsDir::new("proven-solution-artifacts");
+        // the circuit is built, and its artifacts exported, as by `export_batch_circuit`
+        let mut registry = batch_solution_registry(&artifacts_dir.0);
+        let prover = batch_solution_prover(&mut registry, 2, 1).unwrap();
+
+        let default = SolinaConfig::default();
+        let token_prices = PriceSnapshot {
+            prices: BTreeMap::from([
+                (encode([1; 32]), BigUint::from(5_u8)),
+                (encode([2; 32]), BigUint::from(3_u8)),
+            ]),
+        };
+        let mut worker = SolinaWorker::new(SolinaConfig::new(
+            2,
+            default.max_intents_per_signer(),
+            default.mempool_ordering(),
+            default.intent_validation().to_vec(),
+            storage.0.clone(),
+            default.socket_address(),
+            default.auth_credential_timeout(),
+            artifacts_dir.0.clone(),
+            Some(batch_solution_shape(2, 1).id()),
+            default.batch_sealing_interval(),
+            default.solving_window(),
+            default.scoring_metric(),
+            default.selection_policy(),
+            token_prices.clone(),
+        ))
+        .unwrap();
+        // the batch is sealed once full, and solved once its solving window opens
+        store_intent(&mut worker, &signed_intent(1, (1, 2))).unwrap();
+        store_intent(&mut worker, &signed_intent(2, (2, 1))).unwrap();
+        worker
+            .advance_batch_lifecycle(Utc::now().naive_utc())
+            .unwrap();
+        let solver_address = "0x0000000000000000000000000000000000000001".to_string();
+        let mut tx = worker.storage_connection().create_transaction().unwrap();
+        tx.register_solver(solver_address.clone()).unwrap();
+        commit(&mut tx).unwrap();
+        drop(tx);
+
+        let batch = SolinaReader::new(worker.config().clone())
+            .unwrap()
+            .handle_get_latest_sealed_batch_request(GetLatestSealedBatchRequest {
+                offset: None,
+                limit: None,
+            })
+            .unwrap();
+        assert_eq!(batch.state, BatchState::Solving.as_str());
+        let intent_ids = batch
+            .intents
+            .iter()
+            .map(|intent| intent.id)
+            .collect::<Vec<_>>();
+        let intents = batch
+            .intents
+            .into_iter()
+            .map(|intent| serde_json::from_value::<Intent>(intent.intent_json).unwrap())
+            .collect::<Vec<_>>();
+        let solution = |received_amount: u64| {
+            BatchSolution::new(
+                vec![Match::new(
+                    intents[0].clone(),
+                    intents[1].clone(),
+                    SwappedAmount::new(BigUint::from(1_000_u64), BigUint::from(received_amount)),
+                )],
+                token_prices.clone(),
+            )
+        };
+        let proven_solution = solution(950);
+        let proof = prover
+            .prove(&SolutionWitness {
+                intents: &intents,
+                batch_root: batch_root(&intents),
+                batch_timestamp: batch.sealed_at.unwrap(),
+                solution: &proven_solution,
+                prices: &token_prices,
+            })
+            .unwrap();
+        let submit = |worker: &mut SolinaWorker, solution: &BatchSolution| {
+            worker.handle_submit_solution_request(SubmitSolutionRequest {
+                address: solver_address.clone(),
+                batch_id: batch.batch_id,
+                solution_json: serde_json::to_value(solution).unwrap(),
+                proof: encode(&proof.proof),
+            })
+        };
+
+        // the proof does not attest to another solution, although it is valid
+        match submit(&mut worker, &solution(960)) {
+            Err(Error::ProofVerificationFailed(_)) => {}
+            result => panic!("Unexpected result: {:?}", result),
+        }
+        assert!(submit(&mut worker, &proven_solution).unwrap().is_success);
+
+        // a solution stored without its proof attesting to it is never selected, nor filled
+        let mut tx = worker.storage_connection().create_transaction().unwrap();
+        tx.store_solution(NewSolution {
+            batch_id: batch.batch_id,
+            solver_address: solver_address.clone(),
+            solution: serde_json::to_string(&solution(990)).unwrap(),
+            proof: encode(&proof.proof),
+            score: "1000000".to_string(),
+            created_at: Utc::now().naive_utc(),
         })
+        .unwrap();
+        commit(&mut tx).unwrap();
+        drop(tx);
+        let after_solving_window =
+            Utc::now().naive_utc() + Duration::seconds(default.solving_window() as i64 + 1);
+        worker
+            .advance_batch_lifecycle(after_solving_window)
+            .unwrap();
+        let reader = SolinaReader::new(worker.config().clone()).unwrap();
+        let received_amounts = intent_ids
+            .into_iter()
+            .map(|id| {
+                reader
+                    .handle_get_intent_status_request(GetIntentStatusRequest {
+                        id: Some(id),
+                        structured_hash: None,
+                    })
+                    .unwrap()
+                    .intent
+                    .base_amount_received
+            })
+            .collect::<Vec<_>>();
+        // intent A receives the token B amount, and intent B the token A amount
+        assert_eq!(
+            received_amounts,
+            vec![Some("950".to_string()), Some("1000".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_processes_concurrent_submissions() {
+        let storage = TestStorage::new("actor");
+        let worker = SolinaWorker::new(config(&storage.0, 10)).unwrap();
+        let reader = SolinaReader::new(worker.config().clone()).unwrap();
+        let handle = worker.spawn().unwrap();
+
+        let submissions = (1..=5).map(|signer| {
+            let handle = handle.clone();
+            tokio::spawn(async move {
+                let request = StoreIntentRequest {
+                    intent_json: serde_json::to_value(intent(signer, 10 * signer as u64)).unwrap(),
+                };
+                handle
+                    .run(|worker| worker.handle_post_store_intent_request(request))
+                    .await
+                    .unwrap()
+                    .intent_id
+                    .unwrap()
+            })
+        });
+        let mut ids = futures::future::join_all(submissions)
+            .await
+            .into_iter()
+            .map(|id| id.unwrap())
+            .collect::<Vec<_>>();
+        ids.sort();
+        assert_eq!(ids, vec![1, 2, 3, 4, 5]);
+
+        // acknowledged intents are readable without going through the worker
+        let response = reader
+            .run(|reader| reader.handle_get_intent_request(GetIntentRequest { id: 3 }))
+            .await
+            .unwrap();
+        let pending_intent = serde_json::from_value::<Intent>(response.intent_json).unwrap();
+        assert!(pending_intent.inputs.quote_amount > BigUint::from(0_u8));
+
+        handle.shutdown().await.unwrap();
+        assert!(handle.run(|worker| Ok(worker.mempool.len())).await.is_err());
     }
 }
@@ -0,0 +1,420 @@
+// VERIFIER_DESIGN:
+//
+// 1. A batch solution proof is verified against the verifier data of the circuit it was
+//    generated with (see `registry`), and against the public inputs expected for the batch.
+// 2. Public inputs are laid out as follows:
+//      - batch root: 8 u32 limbs, big endian (as in `Poseidon::hash_bytes`),
+//      - batch timestamp: 2 u32 limbs, little endian (as in `expiry`),
+//      - score: 8 u32 limbs, little endian,
+//      - settlement digest: 4 field elements, folding the settlement of every match (see
+//        `BatchSolution::settlements_with`), in order: starting from the zero digest, each
+//        settlement is hashed with Poseidon, after the previous digest, as the little endian
+//        u32 limbs of its price, token A amount and token B amount (8 limbs each). It binds
+//        the proof to the swapped amounts and the prices of the submitted solution,
+//      - nullifiers: 4 field elements each (as in `nullifier`), the big endian u64 limbs
+//        of the nullifier bytes, in the order of `BatchSolution::match_nullifiers`. Circuits
+//        have a fixed number of match slots, so nullifiers are followed by zero elements for
+//        each unused slot (see `batch_circuit`).
+// 3. Every mismatch between the proof public inputs and the expected ones is reported
+//    with the name of the offending input, so that solvers know why a proof was rejected.
+// 4. This crate only depends on plonky2 (and its u32 gadgets) and `solina`, so that auditors
+//    and front-ends can check batch proofs independently, natively or from wasm32.
+// 5. Verifier data is (de)serialized with `SolinaGateSerializer`, which registers the gates
+//    of plonky2 and of the u32 gadgets the circuits are built with. Default gates keep the
+//    tags of `DefaultGateSerializer`, so that verifier data of circuits only using them
+//    still loads, and is written the same way.
+use chrono::NaiveDateTime;
+use num_bigint::BigUint;
+use plonky2::{
+    field::{extension::Extendable, types::Field},
+    gates::{
+        arithmetic_base::ArithmeticGate, arithmetic_extension::ArithmeticExtensionGate,
+        base_sum::BaseSumGate, constant::ConstantGate, coset_interpolation::CosetInterpolationGate,
+        exponentiation::ExponentiationGate, lookup::LookupGate, lookup_table::LookupTableGate,
+        multiplication_extension::MulExtensionGate, noop::NoopGate, poseidon::PoseidonGate,
+        poseidon_mds::PoseidonMdsGate, public_input::PublicInputGate,
+        random_access::RandomAccessGate, reducing::ReducingGate,
+        reducing_extension::ReducingExtensionGate,
+    },
+    get_gate_tag_impl,
+    hash::{
+        hash_types::{HashOut, RichField},
+        poseidon::PoseidonHash,
+    },
+    impl_gate_serializer,
+    plonk::{
+        circuit_data::VerifierCircuitData,
+        config::{GenericConfig, Hasher, PoseidonGoldilocksConfig},
+        proof::ProofWithPublicInputs,
+    },
+    read_gate_impl,
+    util::serialization::GateSerializer,
+};
+use plonky2_u32::gates::{
+    add_many_u32::U32AddManyGate, arithmetic_u32::U32ArithmeticGate, comparison::ComparisonGate,
+    range_check_u32::U32RangeCheckGate, subtraction_u32::U32SubtractionGate,
+};
+use serde::{Deserialize, Serialize};
+use solina::{
+    intent::Nullifier,
+    price_oracle::PriceOracle,
+    solver::{BatchSolution, MatchSettlement},
+    structured_hash::StructuredHash,
+};
+use thiserror::Error;
+
+pub const D: usize = 2;
+pub type C = PoseidonGoldilocksConfig;
+pub type F = <C as GenericConfig<D>>::F;
+
+pub const BATCH_ROOT_LIMBS: usize = 8;
+pub const TIMESTAMP_LIMBS: usize = 2;
+pub const SCORE_LIMBS: usize = 8;
+pub const SETTLEMENT_DIGEST_LEN: usize = 4;
+/// Number of u32 limbs of settled prices and amounts.
+pub const AMOUNT_LIMBS: usize = 8;
+/// Number of public inputs preceding the nullifiers.
+pub const PUBLIC_INPUTS_HEADER_LEN: usize =
+    BATCH_ROOT_LIMBS + TIMESTAMP_LIMBS + SCORE_LIMBS + SETTLEMENT_DIGEST_LEN;
+
+#[derive(Debug, Error)]
+pub enum ProofVerificationError {
+    #[error("Malformed verifier data: `{0}`")]
+    MalformedVerifierData(String),
+    #[error("Malformed proof: `{0}`")]
+    MalformedProof(String),
+    #[error("Score does not fit in {} u32 limbs", SCORE_LIMBS)]
+    ScoreOutOfRange,
+    #[error("Settled amount does not fit in {} u32 limbs", AMOUNT_LIMBS)]
+    AmountOutOfRange,
+    #[error("Public input mismatch: `{0}`")]
+    PublicInputMismatch(String),
+    #[error("Invalid proof: `{0}`")]
+    InvalidProof(String),
+    #[error("Unexpected proof backend: `{0}`")]
+    UnexpectedBackend(String),
+}
+
+/// Gate serializer of the Solina circuits. Gates are tagged as in `DefaultGateSerializer`,
+/// followed by the base 4 decomposition of non-native field elements and the gates of the
+/// u32 gadgets (expiry, amounts, ECDSA signatures).
+pub struct SolinaGateSerializer;
+
+impl<F: RichField + Extendable<D>, const D: usize> GateSerializer<F, D> for SolinaGateSerializer {
+    impl_gate_serializer! {
+        SolinaGateSerializer,
+        ArithmeticGate,
+        ArithmeticExtensionGate<D>,
+        BaseSumGate<2>,
+        ConstantGate,
+        CosetInterpolationGate<F, D>,
+        ExponentiationGate<F, D>,
+        LookupGate,
+        LookupTableGate,
+        MulExtensionGate<D>,
+        NoopGate,
+        PoseidonMdsGate<F, D>,
+        PoseidonGate<F, D>,
+        PublicInputGate,
+        RandomAccessGate<F, D>,
+        ReducingExtensionGate<D>,
+        ReducingGate<D>,
+        BaseSumGate<4>,
+        U32AddManyGate<F, D>,
+        U32ArithmeticGate<F, D>,
+        ComparisonGate<F, D>,
+        U32RangeCheckGate<F, D>,
+        U32SubtractionGate<F, D>
+    }
+}
+
+/// Big endian u32 limbs of a structured hash, as in `Poseidon::hash_bytes`.
+pub fn structured_hash_elements(hash: &StructuredHash) -> Vec<F> {
+    hash.chunks(4)
+        .map(|limb| F::from_canonical_u32(u32::from_be_bytes(limb.try_into().unwrap())))
+        .collect()
+}
+
+/// Little endian u32 limbs of `value`, zero padded to `N` limbs, if it fits.
+fn u32_limbs<const N: usize>(value: &BigUint) -> Option<Vec<F>> {
+    let mut limbs = value.to_u32_digits();
+    if limbs.len() > N {
+        return None;
+    }
+    limbs.resize(N, 0);
+    Some(limbs.into_iter().map(F::from_canonical_u32).collect())
+}
+
+/// The public inputs a batch solution proof is expected to expose.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BatchSolutionPublicInputs {
+    pub batch_root: StructuredHash,
+    pub batch_timestamp: NaiveDateTime,
+    pub score: BigUint,
+    pub settlements: Vec<MatchSettlement>,
+    pub nullifiers: Vec<Nullifier>,
+}
+
+impl BatchSolutionPublicInputs {
+    /// Public inputs of a proof of `solution`, scored and settled at the batch `prices`,
+    /// rather than at the total liquidity the solution claims.
+    pub fn new(
+        batch_root: StructuredHash,
+        batch_timestamp: NaiveDateTime,
+        solution: &BatchSolution,
+        prices: &impl PriceOracle,
+    ) -> Self {
+        Self {
+            batch_root,
+            batch_timestamp,
+            score: solution.total_liquidity_with(prices),
+            settlements: solution.settlements_with(prices),
+            nullifiers: solution.match_nullifiers(),
+        }
+    }
+
+    fn batch_root_elements(&self) -> Vec<F> {
+        structured_hash_elements(&self.batch_root)
+    }
+
+    fn batch_timestamp_elements(&self) -> Vec<F> {
+        let timestamp = u64::try_from(self.batch_timestamp.timestamp()).unwrap_or(0);
+        vec![
+            F::from_canonical_u32(timestamp as u32),
+            F::from_canonical_u32((timestamp >> 32) as u32),
+        ]
+    }
+
+    fn score_elements(&self) -> Result<Vec<F>, ProofVerificationError> {
+        u32_limbs::<SCORE_LIMBS>(&self.score).ok_or(ProofVerificationError::ScoreOutOfRange)
+    }
+
+    fn settlement_digest_elements(&self) -> Result<Vec<F>, ProofVerificationError> {
+        let mut digest = HashOut::<F>::ZERO;
+        for settlement in &self.settlements {
+            let mut elements = digest.elements.to_vec();
+            for amount in [
+                &settlement.price,
+                &settlement.token_a_amount,
+                &settlement.token_b_amount,
+            ] {
+                elements.extend(
+                    u32_limbs::<AMOUNT_LIMBS>(amount)
+                        .ok_or(ProofVerificationError::AmountOutOfRange)?,
+                );
+            }
+            digest = PoseidonHash::hash_no_pad(&elements);
+        }
+        Ok(digest.elements.to_vec())
+    }
+
+    pub fn nullifier_elements(&self) -> Vec<F> {
+        self.nullifiers
+            .iter()
+            .flat_map(|n| n.chunks(8))
+            .map(|limb| F::from_canonical_u64(u64::from_be_bytes(limb.try_into().unwrap())))
+            .collect()
+    }
+
+    /// Public inputs, in the order they are registered by the circuit.
+    pub fn to_field_elements(&self) -> Result<Vec<F>, ProofVerificationError> {
+        Ok([
+            self.batch_root_elements(),
+            self.batch_timestamp_elements(),
+            self.score_elements()?,
+            self.settlement_digest_elements()?,
+            self.nullifier_elements(),
+        ]
+        .concat())
+    }
+
+    fn check(&self, public_inputs: &[F]) -> Result<(), ProofVerificationError> {
+        let expected_len = self.to_field_elements()?.len();
+        if public_inputs.len() < expected_len || (public_inputs.len() - expected_len) % 4 != 0 {
+            return Err(ProofVerificationError::PublicInputMismatch(format!(
+                "expected {} public inputs, followed by zero padded nullifiers, found {}",
+                expected_len,
+                public_inputs.len()
+            )));
+        }
+
+        let mut offset = 0;
+        for (name, expected) in [
+            ("batch root", self.batch_root_elements()),
+            ("batch timestamp", self.batch_timestamp_elements()),
+            ("score", self.score_elements()?),
+            ("settlements", self.settlement_digest_elements()?),
+            ("nullifiers", self.nullifier_elements()),
+        ] {
+            let found = &public_inputs[offset..offset + expected.len()];
+            if found != expected.as_slice() {
+                return Err(ProofVerificationError::PublicInputMismatch(
+                    name.to_string(),
+                ));
+            }
+            offset += expected.len();
+        }
+        if public_inputs[offset..]
+            .iter()
+            .any(|element| !element.is_zero())
+        {
+            return Err(ProofVerificationError::PublicInputMismatch(
+                "nullifier padding".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Verifies a serialized batch solution proof against serialized verifier data, and
+/// the public inputs expected for the batch.
+pub fn verify_batch_solution_proof(
+    verifier_data: &[u8],
+    proof: &[u8],
+    expected_public_inputs: &BatchSolutionPublicInputs,
+) -> Result<(), ProofVerificationError> {
+    let verifier_data =
+        VerifierCircuitData::<F, C, D>::from_bytes(verifier_data.to_vec(), &SolinaGateSerializer)
+            .map_err(|e| ProofVerificationError::MalformedVerifierData(format!("{:?}", e)))?;
+    verify_batch_solution_proof_with(&verifier_data, proof, expected_public_inputs)
+}
+
+/// Same as `verify_batch_solution_proof`, with already deserialized verifier data.
+pub fn verify_batch_solution_proof_with(
+    verifier_data: &VerifierCircuitData<F, C, D>,
+    proof: &[u8],
+    expected_public_inputs: &BatchSolutionPublicInputs,
+) -> Result<(), ProofVerificationError> {
+    let proof = ProofWithPublicInputs::<F, C, D>::from_bytes(proof.to_vec(), &verifier_data.common)
+        .map_err(|e| ProofVerificationError::MalformedProof(format!("{:?}", e)))?;
+
+    expected_public_inputs.check(&proof.public_inputs)?;
+
+    verifier_data
+        .verify(proof)
+        .map_err(|e| ProofVerificationError::InvalidProof(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plonky2::{
+        gates::gate::{Gate, GateRef},
+        iop::witness::{PartialWitness, WitnessWrite},
+        plonk::{
+            circuit_builder::CircuitBuilder,
+            circuit_data::{CircuitConfig, CommonCircuitData},
+        },
+        util::serialization::{Buffer, DefaultGateSerializer, Write},
+    };
+
+    fn square_circuit() -> (VerifierCircuitData<F, C, D>, ProofWithPublicInputs<F, C, D>) {
+        let mut circuit_builder =
+            CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let x = circuit_builder.add_virtual_target();
+        let square = circuit_builder.square(x);
+        circuit_builder.register_public_input(square);
+        let circuit_data = circuit_builder.build::<C>();
+
+        let mut partial_witness = PartialWitness::new();
+        partial_witness.set_target(x, F::from_canonical_u8(3));
+        let proof = circuit_data.prove(partial_witness).unwrap();
+        (circuit_data.verifier_data(), proof)
+    }
+
+    /// Writes `gate` with `SolinaGateSerializer`, checks it is tagged with `tag`, and reads it
+    /// back.
+    fn assert_gate_round_trip<G: Gate<F, D>>(
+        tag: u32,
+        gate: G,
+        common_data: &CommonCircuitData<F, D>,
+    ) {
+        let mut expected_bytes = vec![];
+        expected_bytes.write_u32(tag).unwrap();
+        gate.serialize(&mut expected_bytes, common_data).unwrap();
+
+        let gate = GateRef::new(gate);
+        let mut bytes = vec![];
+        SolinaGateSerializer
+            .write_gate(&mut bytes, &gate, common_data)
+            .unwrap();
+        assert_eq!(bytes, expected_bytes);
+        let gate_ref = SolinaGateSerializer
+            .read_gate(&mut Buffer::new(&bytes), common_data)
+            .unwrap();
+        assert_eq!(gate_ref, gate);
+    }
+
+    #[test]
+    fn it_works_nullifier_padding_check() {
+        let public_inputs = BatchSolutionPublicInputs {
+            batch_root: [1u8; 32],
+            batch_timestamp: NaiveDateTime::from_timestamp_opt(1_700_000_000, 0).unwrap(),
+            score: BigUint::from(100_u64),
+            settlements: vec![],
+            nullifiers: vec![[2u8; 32]],
+        };
+        let elements = public_inputs.to_field_elements().unwrap();
+        public_inputs.check(&elements).unwrap();
+
+        // a circuit with two match slots, settling a single match
+        let padded = [elements.clone(), vec![F::ZERO; 4]].concat();
+        public_inputs.check(&padded).unwrap();
+
+        for public_input in [
+            [elements.clone(), vec![F::ZERO; 3]].concat(),
+            [elements.clone(), vec![F::ONE; 4]].concat(),
+            elements[..elements.len() - 4].to_vec(),
+        ] {
+            assert!(matches!(
+                public_inputs.check(&public_input),
+                Err(ProofVerificationError::PublicInputMismatch(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn it_works_default_gates_verifier_data_round_trip() {
+        let (verifier_data, proof) = square_circuit();
+        let bytes = verifier_data.to_bytes(&SolinaGateSerializer).unwrap();
+        assert_eq!(
+            bytes,
+            verifier_data.to_bytes(&DefaultGateSerializer).unwrap()
+        );
+
+        let verifier_data =
+            VerifierCircuitData::<F, C, D>::from_bytes(bytes, &SolinaGateSerializer).unwrap();
+        verifier_data.verify(proof).unwrap();
+    }
+
+    #[test]
+    fn it_works_u32_gates_round_trip() {
+        let config = CircuitConfig::standard_recursion_config();
+        let (verifier_data, _) = square_circuit();
+        let common_data = &verifier_data.common;
+
+        assert_gate_round_trip(
+            16,
+            BaseSumGate::<4>::new_from_config::<F>(&config),
+            common_data,
+        );
+        assert_gate_round_trip(
+            17,
+            U32AddManyGate::<F, D>::new_from_config(&config, 3),
+            common_data,
+        );
+        assert_gate_round_trip(
+            18,
+            U32ArithmeticGate::<F, D>::new_from_config(&config),
+            common_data,
+        );
+        assert_gate_round_trip(19, ComparisonGate::<F, D>::new(32, 16), common_data);
+        assert_gate_round_trip(20, U32RangeCheckGate::<F, D>::new(2), common_data);
+        assert_gate_round_trip(
+            21,
+            U32SubtractionGate::<F, D>::new_from_config(&config),
+            common_data,
+        );
+    }
+}
@@ -0,0 +1,35 @@
+use solina_verify::{verify_batch_solution_proof, BatchSolutionPublicInputs};
+use std::{env, fs, process::exit};
+
+const USAGE: &str =
+    "Usage: solina-verify <verifier data file> <proof file> <public inputs JSON file>";
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    if args.len() != 3 {
+        eprintln!("{}", USAGE);
+        exit(2);
+    }
+
+    if let Err(e) = verify(&args[0], &args[1], &args[2]) {
+        eprintln!("Proof verification failed: {}", e);
+        exit(1);
+    }
+    println!("Proof is valid");
+}
+
+fn verify(
+    verifier_data_path: &str,
+    proof_path: &str,
+    public_inputs_path: &str,
+) -> Result<(), String> {
+    let read = |path: &str| fs::read(path).map_err(|e| format!("Failed to read {}: {}", path, e));
+
+    let verifier_data = read(verifier_data_path)?;
+    let proof = read(proof_path)?;
+    let public_inputs: BatchSolutionPublicInputs =
+        serde_json::from_slice(&read(public_inputs_path)?)
+            .map_err(|e| format!("Invalid public inputs: {}", e))?;
+
+    verify_batch_solution_proof(&verifier_data, &proof, &public_inputs).map_err(|e| e.to_string())
+}
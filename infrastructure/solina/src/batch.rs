@@ -0,0 +1,320 @@
+use crate::{
+    intent::{Intent, Nullifier},
+    price_oracle::PriceSnapshot,
+    signature::verify_intent_signature,
+    solver::{BatchSolution, MatchSettlement, SolutionValidationError},
+    structured_hash::{
+        HashBackend, Poseidon, PoseidonDigest, StructuredHash, StructuredHashInterface,
+    },
+};
+use chrono::NaiveDateTime;
+use num_bigint::BigUint;
+use serde::{Deserialize, Serialize};
+
+/// Inputs of the RISC Zero batch validation guest.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BatchValidationInputs {
+    pub intents: Vec<Intent>,
+    pub batch_root: StructuredHash,
+    pub batch_timestamp: NaiveDateTime,
+    pub solution: BatchSolution,
+    /// Prices the solution is scored at, as snapshot when the batch was sealed.
+    pub prices: PriceSnapshot,
+}
+
+/// Public outputs committed by the RISC Zero batch validation guest to its journal.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct BatchValidationJournal {
+    pub batch_root: StructuredHash,
+    pub batch_timestamp: NaiveDateTime,
+    pub score: BigUint,
+    pub settlements: Vec<MatchSettlement>,
+    pub nullifiers: Vec<Nullifier>,
+    pub is_valid: bool,
+}
+
+impl BatchValidationInputs {
+    /// Checks the batch against its root and the signatures of its intents, and the solution
+    /// against the batch and its claimed total liquidity against the prices.
+    pub fn validate(&self) -> Result<(), SolutionValidationError> {
+        if batch_root(&self.intents) != self.batch_root {
+            return Err(SolutionValidationError::BatchRootMismatch);
+        }
+        if let Some(intent) = self
+            .intents
+            .iter()
+            .find(|intent| verify_intent_signature(intent).is_err())
+        {
+            return Err(SolutionValidationError::InvalidSignature(
+                intent.structured_hash(),
+            ));
+        }
+        if self.solution.total_liquidity_with(&self.prices) != *self.solution.total_liquidity() {
+            return Err(SolutionValidationError::LiquidityMismatch);
+        }
+        self.solution
+            .validate_batch(&self.intents, &self.batch_timestamp)
+    }
+
+    /// Validates the inputs, and derives the public outputs of the batch validation guest.
+    /// The score is the solution liquidity at the committed prices, never the claimed one.
+    pub fn journal(&self) -> BatchValidationJournal {
+        BatchValidationJournal {
+            batch_root: self.batch_root,
+            batch_timestamp: self.batch_timestamp,
+            score: self.solution.total_liquidity_with(&self.prices),
+            settlements: self.solution.settlements_with(&self.prices),
+            nullifiers: self.solution.match_nullifiers(),
+            is_valid: self.validate().is_ok(),
+        }
+    }
+}
+
+/// Merkle root of a batch of intents, whose leaves are the `batch_leaf` of each intent, in
+/// canonical (batch) order, so that batch proofs can recompute it in circuit. The root is the
+/// bytes of the Poseidon digest, as in `Poseidon::digest_to_bytes`.
+pub fn batch_root(intents: &[Intent]) -> StructuredHash {
+    let leaves = intents.iter().map(batch_leaf).collect::<Vec<_>>();
+    Poseidon::digest_to_bytes(&merkle_root(leaves))
+        .try_into()
+        .expect("Poseidon digests are 32 bytes long")
+}
+
+/// Leaf of an intent in its batch tree: the Poseidon hash of its signer public key and of its
+/// Poseidon structured hash. Structured hashes do not cover the signer, so that the leaf
+/// commits to who signed the intent, and intents re-signed by anyone else are not in the
+/// batch. The inputs are in the reverse order of `Intent::nullifier_with`, so that leaves and
+/// nullifiers differ.
+pub fn batch_leaf(intent: &Intent) -> PoseidonDigest {
+    Poseidon::two_to_one(
+        Poseidon::hash_bytes(&intent.public_key),
+        intent.structured_hash_with::<Poseidon>(),
+    )
+}
+
+/// Poseidon Merkle root of `leaves`, padded with zero digests to the next power of two.
+/// An empty tree has a zero root.
+pub fn merkle_root(mut leaves: Vec<PoseidonDigest>) -> PoseidonDigest {
+    if leaves.is_empty() {
+        return PoseidonDigest::ZERO;
+    }
+    leaves.resize(leaves.len().next_power_of_two(), PoseidonDigest::ZERO);
+
+    while leaves.len() > 1 {
+        leaves = leaves
+            .chunks(2)
+            .map(|pair| Poseidon::two_to_one(pair[0], pair[1]))
+            .collect();
+    }
+    leaves[0]
+}
+
+#[cfg(all(test, feature = "ecdsa"))]
+mod tests {
+    use super::*;
+    use crate::{
+        intent::{IntentConstraints, IntentInputs, TradeDirection},
+        price_oracle::{Price, PriceOracle},
+        signature::sign_intent,
+        solver::{Match, SwappedAmount},
+        Signature, TokenAddress,
+    };
+    use chrono::NaiveDate;
+    use plonky2::field::{secp256k1_scalar::Secp256K1Scalar, types::Field};
+    use plonky2_ecdsa::curve::ecdsa::ECDSASecretKey;
+
+    struct UnitPriceOracle;
+
+    impl PriceOracle for UnitPriceOracle {
+        fn get_current_price(&self, _token_address: TokenAddress) -> Price {
+            BigUint::from(1_u8)
+        }
+    }
+
+    struct DoublePriceOracle;
+
+    impl PriceOracle for DoublePriceOracle {
+        fn get_current_price(&self, _token_address: TokenAddress) -> Price {
+            BigUint::from(2_u8)
+        }
+    }
+
+    /// An intent signed by the secret key `quote_token`.
+    fn intent(quote_token: u8, base_token: u8, quote_amount: u64, min_base_amount: u64) -> Intent {
+        let mut intent = Intent::new(
+            [quote_token; 32],
+            IntentInputs::new(
+                [quote_token; 32],
+                [base_token; 32],
+                BigUint::from(quote_amount),
+                TradeDirection::Sell,
+            ),
+            IntentConstraints::new(BigUint::from(min_base_amount)),
+            Signature([0u8; 64]),
+            NaiveDate::from_ymd_opt(2023, 11, 14)
+                .unwrap()
+                .and_hms_opt(22, 13, 20)
+                .unwrap(),
+        );
+        sign_intent(
+            &mut intent,
+            ECDSASecretKey(Secp256K1Scalar::from_canonical_u64(quote_token.into())),
+        );
+        intent
+    }
+
+    fn inputs(intents: Vec<Intent>, matches: Vec<Match>) -> BatchValidationInputs {
+        BatchValidationInputs {
+            batch_root: batch_root(&intents),
+            intents,
+            batch_timestamp: NaiveDate::from_ymd_opt(2023, 11, 1)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap(),
+            solution: BatchSolution::new(matches, UnitPriceOracle),
+            prices: PriceSnapshot::new(&UnitPriceOracle, [[1u8; 32], [2u8; 32]]),
+        }
+    }
+
+    #[test]
+    fn it_works_merkle_root() {
+        let leaf = |byte: u8| Poseidon::hash_bytes(&[byte]);
+        assert_eq!(merkle_root(vec![]), PoseidonDigest::ZERO);
+        assert_eq!(merkle_root(vec![leaf(1)]), leaf(1));
+
+        let leaves = vec![leaf(1), leaf(2), leaf(3)];
+        let left = Poseidon::two_to_one(leaf(1), leaf(2));
+        let right = Poseidon::two_to_one(leaf(3), PoseidonDigest::ZERO);
+        assert_eq!(merkle_root(leaves), Poseidon::two_to_one(left, right));
+    }
+
+    #[test]
+    fn it_works_batch_root() {
+        assert_eq!(batch_root(&[]), [0u8; 32]);
+
+        let intents = vec![intent(1, 2, 100, 50), intent(2, 1, 60, 90)];
+        let leaves = intents.iter().map(batch_leaf).collect::<Vec<_>>();
+        assert_eq!(
+            batch_root(&intents).to_vec(),
+            Poseidon::digest_to_bytes(&Poseidon::two_to_one(leaves[0], leaves[1]))
+        );
+    }
+
+    #[test]
+    fn it_works_batch_validation() {
+        let intent_a = intent(1, 2, 100, 50);
+        let intent_b = intent(2, 1, 60, 90);
+        let intents = vec![intent_a.clone(), intent_b.clone()];
+
+        let swap = |a: u64, b: u64| SwappedAmount::new(BigUint::from(a), BigUint::from(b));
+        let valid = Match::new(intent_a.clone(), intent_b.clone(), swap(100, 60));
+
+        let journal = inputs(intents.clone(), vec![valid.clone()]).journal();
+        assert!(journal.is_valid);
+        assert_eq!(journal.batch_root, batch_root(&intents));
+        assert_eq!(journal.score, BigUint::from(60_u8));
+        assert_eq!(journal.nullifiers.len(), 2);
+        assert_eq!(
+            journal.settlements,
+            vec![MatchSettlement {
+                token_a_amount: BigUint::from(100_u8),
+                token_b_amount: BigUint::from(60_u8),
+                price: BigUint::from(1_u8),
+            }]
+        );
+
+        // a solution claiming more liquidity than it has at the batch prices
+        let mut overpriced = inputs(intents.clone(), vec![valid.clone()]);
+        overpriced.solution = BatchSolution::new(vec![valid.clone()], DoublePriceOracle);
+        assert_eq!(
+            overpriced.validate(),
+            Err(SolutionValidationError::LiquidityMismatch)
+        );
+        assert_eq!(overpriced.journal().score, BigUint::from(60_u8));
+
+        // a batch intent whose contents do not match its signature
+        let mut tampered = intent_b.clone();
+        tampered.inputs.quote_amount += 1_u8;
+        assert_eq!(
+            inputs(vec![intent_a.clone(), tampered.clone()], vec![]).validate(),
+            Err(SolutionValidationError::InvalidSignature(
+                tampered.structured_hash()
+            ))
+        );
+
+        let mut wrong_root = inputs(intents.clone(), vec![valid]);
+        wrong_root.batch_root = [1u8; 32];
+        assert_eq!(
+            wrong_root.validate(),
+            Err(SolutionValidationError::BatchRootMismatch)
+        );
+
+        let outsider = intent(2, 1, 61, 90);
+        assert_eq!(
+            inputs(
+                intents.clone(),
+                vec![Match::new(
+                    intent_a.clone(),
+                    outsider.clone(),
+                    swap(100, 60)
+                )]
+            )
+            .validate(),
+            Err(SolutionValidationError::UnknownIntent(
+                outsider.structured_hash()
+            ))
+        );
+
+        // the contents of intent B, re-signed by someone else
+        let mut forged = intent_b.clone();
+        forged.public_key = [9u8; 32];
+        assert_eq!(
+            inputs(
+                intents.clone(),
+                vec![Match::new(intent_a.clone(), forged, swap(100, 60))]
+            )
+            .validate(),
+            Err(SolutionValidationError::UnknownIntent(
+                intent_b.structured_hash()
+            ))
+        );
+
+        assert_eq!(
+            inputs(
+                intents.clone(),
+                vec![Match::new(intent_a.clone(), intent_a.clone(), swap(10, 10))]
+            )
+            .validate(),
+            Err(SolutionValidationError::TokenMismatch(
+                intent_a.structured_hash(),
+                intent_a.structured_hash()
+            ))
+        );
+
+        assert_eq!(
+            inputs(
+                intents.clone(),
+                vec![Match::new(
+                    intent_a.clone(),
+                    intent_b.clone(),
+                    swap(101, 60)
+                )]
+            )
+            .validate(),
+            Err(SolutionValidationError::ExceededQuoteAmount(
+                intent_a.structured_hash()
+            ))
+        );
+
+        assert_eq!(
+            inputs(
+                intents,
+                vec![Match::new(intent_a.clone(), intent_b, swap(100, 40))]
+            )
+            .validate(),
+            Err(SolutionValidationError::UnsatisfiedConstraints(
+                intent_a.structured_hash()
+            ))
+        );
+    }
+}
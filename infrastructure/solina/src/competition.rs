@@ -0,0 +1,258 @@
+use crate::{solver::BatchSolution, structured_hash::StructuredHash, PublicKey};
+use chrono::NaiveDateTime;
+use num_bigint::BigUint;
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, collections::BTreeMap};
+
+/// Metric solutions competing on the same batch are scored with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScoringMetric {
+    /// Total liquidity swapped by the solution, as committed to by its proof.
+    #[default]
+    TotalLiquidity,
+    /// Number of distinct intents settled by the solution.
+    MatchedIntents,
+}
+
+impl ScoringMetric {
+    pub fn score(&self, solution: &BatchSolution) -> BigUint {
+        match self {
+            Self::TotalLiquidity => solution.total_liquidity().clone(),
+            Self::MatchedIntents => BigUint::from(solution.nullifiers().len()),
+        }
+    }
+}
+
+/// How the settlement of a batch is selected among the submitted solutions.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SelectionPolicy {
+    /// The best submission settles the whole batch.
+    #[default]
+    Winner,
+    /// Compatible submissions are merged into a single settlement, see `combine_solutions`.
+    Combinatorial,
+}
+
+/// A scored solution, submitted by `solver` for some batch.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Submission {
+    pub id: i64,
+    pub solver: String,
+    pub score: BigUint,
+    pub submitted_at: NaiveDateTime,
+}
+
+impl Submission {
+    /// Total order on submissions, from best to worst: higher scores come first, and
+    /// ties are broken in favour of the earliest submission, then of the lowest id.
+    pub fn rank(&self, other: &Self) -> Ordering {
+        other
+            .score
+            .cmp(&self.score)
+            .then_with(|| self.submitted_at.cmp(&other.submitted_at))
+            .then_with(|| self.id.cmp(&other.id))
+    }
+}
+
+/// Orders `submissions` from best to worst, see `Submission::rank`.
+pub fn rank_submissions(submissions: &mut [Submission]) {
+    submissions.sort_by(Submission::rank);
+}
+
+/// Selects the winning submission, deterministically, independently of the order of
+/// `submissions`.
+pub fn select_winner(submissions: &[Submission]) -> Option<&Submission> {
+    submissions.iter().min_by(|a, b| a.rank(b))
+}
+
+/// Settlement merged from several submissions, with the score of each solver.
+#[derive(Clone, Debug)]
+pub struct CombinedSolution {
+    pub solution: BatchSolution,
+    /// Ids of the merged submissions, from best to worst.
+    pub submission_ids: Vec<i64>,
+    /// Sum of the scores of the merged submissions of each solver, for fees to be split
+    /// among them.
+    pub solver_scores: BTreeMap<String, BigUint>,
+}
+
+/// Merges compatible submissions into a single settlement. Submissions are considered
+/// from best to worst, and merged unless they would over-fill an intent, that is, if the
+/// quote amount swapped by any intent across merged submissions would exceed its own.
+///
+/// Finding the best combination is a weighted set packing problem, so the greedy order
+/// only approximates it, but keeps the selection deterministic and never worse than
+/// picking the single best submission.
+pub fn combine_solutions(
+    mut candidates: Vec<(Submission, BatchSolution)>,
+) -> Option<CombinedSolution> {
+    candidates.sort_by(|(a, _), (b, _)| a.rank(b));
+
+    let mut swapped_amounts = BTreeMap::<(StructuredHash, PublicKey), BigUint>::new();
+    let mut merged = vec![];
+    let mut submission_ids = vec![];
+    let mut solver_scores = BTreeMap::<String, BigUint>::new();
+    for (submission, solution) in candidates {
+        let quote_amounts = solution
+            .batch_matches()
+            .iter()
+            .flat_map(|m| [m.intent_a(), m.intent_b()])
+            .map(|intent| (intent.key(), &intent.inputs.quote_amount))
+            .collect::<BTreeMap<_, _>>();
+        let amounts = solution
+            .matched_amounts()
+            .into_iter()
+            .map(|(key, (swapped, _))| {
+                let total = swapped_amounts.get(&key).cloned().unwrap_or_default() + swapped;
+                (key, total)
+            })
+            .collect::<Vec<_>>();
+        if amounts
+            .iter()
+            .any(|(key, total)| total > quote_amounts[key])
+        {
+            continue;
+        }
+
+        swapped_amounts.extend(amounts);
+        submission_ids.push(submission.id);
+        *solver_scores.entry(submission.solver).or_default() += submission.score;
+        merged.push(solution);
+    }
+
+    (!merged.is_empty()).then(|| CombinedSolution {
+        solution: BatchSolution::merge(merged),
+        submission_ids,
+        solver_scores,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        intent::{Intent, IntentConstraints, IntentInputs, TradeDirection},
+        price_oracle::{Price, PriceOracle},
+        solver::{Match, SwappedAmount},
+        Signature, TokenAddress,
+    };
+    use chrono::NaiveDate;
+
+    struct UnitPriceOracle;
+
+    impl PriceOracle for UnitPriceOracle {
+        fn get_current_price(&self, _token_address: TokenAddress) -> Price {
+            BigUint::from(1_u8)
+        }
+    }
+
+    fn intent(quote_token: u8, base_token: u8, quote_amount: u64) -> Intent {
+        Intent::new(
+            [quote_token; 32],
+            IntentInputs::new(
+                [quote_token; 32],
+                [base_token; 32],
+                BigUint::from(quote_amount),
+                TradeDirection::Sell,
+            ),
+            IntentConstraints::new(BigUint::from(0_u8)),
+            Signature([0u8; 64]),
+            NaiveDate::from_ymd_opt(2023, 11, 14)
+                .unwrap()
+                .and_hms_opt(22, 13, 20)
+                .unwrap(),
+        )
+    }
+
+    fn solution(matches: Vec<(&Intent, &Intent, u64)>) -> BatchSolution {
+        BatchSolution::new(
+            matches
+                .into_iter()
+                .map(|(a, b, amount)| {
+                    Match::new(
+                        a.clone(),
+                        b.clone(),
+                        SwappedAmount::new(BigUint::from(amount), BigUint::from(amount)),
+                    )
+                })
+                .collect(),
+            UnitPriceOracle,
+        )
+    }
+
+    fn submission(id: i64, score: u64, second: u32) -> Submission {
+        Submission {
+            id,
+            solver: format!("solver-{}", id),
+            score: BigUint::from(score),
+            submitted_at: NaiveDate::from_ymd_opt(2023, 11, 1)
+                .unwrap()
+                .and_hms_opt(12, 0, second)
+                .unwrap(),
+        }
+    }
+
+    #[test]
+    fn it_works_select_winner() {
+        assert_eq!(select_winner(&[]), None);
+
+        // the highest score wins, then the earliest submission, then the lowest id
+        let submissions = vec![
+            submission(1, 10, 0),
+            submission(2, 30, 5),
+            submission(3, 30, 2),
+            submission(4, 30, 2),
+            submission(5, 20, 1),
+        ];
+        assert_eq!(select_winner(&submissions).unwrap().id, 3);
+
+        let mut reversed = submissions.clone();
+        reversed.reverse();
+        assert_eq!(select_winner(&reversed).unwrap().id, 3);
+
+        rank_submissions(&mut reversed);
+        assert_eq!(
+            reversed.iter().map(|s| s.id).collect::<Vec<_>>(),
+            vec![3, 4, 2, 5, 1]
+        );
+    }
+
+    #[test]
+    fn it_works_combine_solutions() {
+        assert!(combine_solutions(vec![]).is_none());
+
+        let (a, b) = (intent(1, 2, 100), intent(2, 1, 100));
+        let (c, d) = (intent(3, 4, 50), intent(4, 3, 50));
+        let candidates = vec![
+            (submission(1, 60, 0), solution(vec![(&a, &b, 60)])),
+            // over-fills intents a and b, together with the first submission
+            (submission(2, 50, 0), solution(vec![(&a, &b, 50)])),
+            // fills the remaining quote amount of intents a and b
+            (submission(3, 40, 0), solution(vec![(&a, &b, 40)])),
+            (submission(4, 50, 1), solution(vec![(&c, &d, 50)])),
+        ];
+
+        let mut candidates = candidates
+            .into_iter()
+            .map(|(mut s, solution)| {
+                s.solver = format!("solver-{}", s.id % 2);
+                (s, solution)
+            })
+            .collect::<Vec<_>>();
+        candidates.reverse();
+        let combined = combine_solutions(candidates).unwrap();
+
+        assert_eq!(combined.submission_ids, vec![1, 4, 3]);
+        assert_eq!(combined.solution.batch_matches().len(), 3);
+        assert_eq!(combined.solution.total_liquidity(), &BigUint::from(150_u8));
+        assert_eq!(
+            combined.solver_scores,
+            BTreeMap::from([
+                ("solver-0".to_string(), BigUint::from(50_u8)),
+                ("solver-1".to_string(), BigUint::from(100_u8)),
+            ])
+        );
+    }
+}
@@ -1,6 +1,10 @@
-use crate::{structured_hash::StructuredHashInterface, PublicKey, Signature, TokenAddress};
+use crate::{
+    structured_hash::{
+        HashBackend, Keccak, Poseidon, PoseidonDigest, StructuredHash, StructuredHashInterface,
+    },
+    PublicKey, Signature, TokenAddress,
+};
 use chrono::NaiveDateTime;
-use keccak_hash::keccak;
 use num_bigint::BigUint;
 use serde::{Deserialize, Serialize};
 
@@ -27,6 +31,20 @@ impl TradeDirection {
     }
 }
 
+/// Length of the encoding of an amount, as an Ethereum `uint256`.
+pub const AMOUNT_ENCODING_LEN: usize = 32;
+
+/// Big endian bytes of an amount, left padded with zeros to `AMOUNT_ENCODING_LEN` bytes, so
+/// that the encoding of an amount does not depend on its magnitude. Larger amounts are not
+/// padded. Amounts are hashed with this encoding by Poseidon only, Keccak digests keep the
+/// minimal encoding.
+pub fn encode_amount(amount: &BigUint) -> Vec<u8> {
+    let bytes = amount.to_bytes_be();
+    let mut encoding = vec![0u8; AMOUNT_ENCODING_LEN.saturating_sub(bytes.len())];
+    encoding.extend(bytes);
+    encoding
+}
+
 /// Inputs for a swap
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct IntentInputs {
@@ -45,17 +63,17 @@ impl StructuredHashInterface for IntentInputs {
         "IntentInputs(BigUint from,BigUint quote_token,BigUint base_token,BigUint quote_amount)"
             .to_string()
     }
-    fn data_encode(&self) -> Vec<u8> {
-        let quote_token_hash = keccak(self.quote_token).to_fixed_bytes();
-        let base_token_hash = keccak(self.base_token).to_fixed_bytes();
-        let quote_amount_hash = keccak(self.quote_amount.to_bytes_be()).to_fixed_bytes();
-        let direction = keccak([self.direction as u8]).to_fixed_bytes();
+    fn data_encode_with<H: HashBackend>(&self) -> Vec<u8> {
+        let quote_token_hash = H::hash_bytes(&self.quote_token);
+        let base_token_hash = H::hash_bytes(&self.base_token);
+        let quote_amount_hash = H::hash_bytes(&H::encode_amount(&self.quote_amount));
+        let direction = H::hash_bytes(&[self.direction as u8]);
 
         [
-            quote_token_hash,
-            base_token_hash,
-            quote_amount_hash,
-            direction,
+            H::digest_to_bytes(&quote_token_hash),
+            H::digest_to_bytes(&base_token_hash),
+            H::digest_to_bytes(&quote_amount_hash),
+            H::digest_to_bytes(&direction),
         ]
         .concat()
     }
@@ -87,10 +105,10 @@ impl StructuredHashInterface for IntentConstraints {
     fn type_encode() -> String {
         "IntentConstraints(BigUint min_base_token_amount)".to_string()
     }
-    fn data_encode(&self) -> Vec<u8> {
-        keccak(self.min_base_token_amount.to_bytes_be())
-            .as_fixed_bytes()
-            .to_vec()
+    fn data_encode_with<H: HashBackend>(&self) -> Vec<u8> {
+        H::digest_to_bytes(&H::hash_bytes(&H::encode_amount(
+            &self.min_base_token_amount,
+        )))
     }
 }
 
@@ -102,6 +120,8 @@ impl IntentConstraints {
     }
 }
 
+pub type Nullifier = StructuredHash;
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Intent {
     pub public_key: PublicKey,
@@ -139,6 +159,40 @@ impl Intent {
     pub fn expiry_date(&self) -> &NaiveDateTime {
         &self.expiry_date
     }
+
+    /// Unix seconds of the expiry date, as signed and as laid out in circuits. Dates prior
+    /// to the unix epoch are clamped to zero.
+    pub fn expiry_timestamp(&self) -> u64 {
+        u64::try_from(self.expiry_date.timestamp()).unwrap_or(0)
+    }
+
+    /// Structured hash and signer, which together identify a signed intent, as structured
+    /// hashes do not cover the signer.
+    pub fn key(&self) -> (StructuredHash, PublicKey) {
+        (self.structured_hash(), self.public_key)
+    }
+
+    /// An intent is expired at `timestamp` unless its expiry date is strictly later.
+    pub fn is_expired_at(&self, timestamp: &NaiveDateTime) -> bool {
+        self.expiry_date <= *timestamp
+    }
+
+    /// The nullifier of an intent is derived from its structured hash and its signer.
+    /// Once an intent is settled in a batch, its nullifier is spent, and the intent
+    /// cannot be matched again in any later batch. Nullifiers are computed with Poseidon,
+    /// as they are output by the circuits.
+    pub fn nullifier(&self) -> Nullifier {
+        Poseidon::digest_to_bytes(&self.nullifier_with::<Poseidon>())
+            .try_into()
+            .expect("Poseidon digests are 32 bytes long")
+    }
+
+    pub fn nullifier_with<H: HashBackend>(&self) -> H::Digest {
+        H::two_to_one(
+            self.structured_hash_with::<H>(),
+            H::hash_bytes(&self.public_key),
+        )
+    }
 }
 
 impl StructuredHashInterface for Intent {
@@ -146,22 +200,88 @@ impl StructuredHashInterface for Intent {
         let input_type_encoding = IntentInputs::type_encode();
         let constraints_type_encoding = IntentConstraints::type_encode();
         format!(
-            "Intent(IntentInputs inputs,IntentConstraints constraints){}{}",
+            "Intent(IntentInputs inputs,IntentConstraints constraints,uint64 expiry_date){}{}",
             constraints_type_encoding, input_type_encoding
         )
     }
 
-    fn data_encode(&self) -> Vec<u8> {
-        let input_data_encoding = self.inputs.structured_hash();
-        let constraints_data_encoding = self.constraints.structured_hash();
-        [input_data_encoding, constraints_data_encoding].concat()
+    fn data_encode_with<H: HashBackend>(&self) -> Vec<u8> {
+        let input_data_encoding = H::digest_to_bytes(&self.inputs.structured_hash_with::<H>());
+        let constraints_data_encoding =
+            H::digest_to_bytes(&self.constraints.structured_hash_with::<H>());
+        // the expiry date is signed, so that it cannot be extended by anyone but the signer
+        let expiry_date_data_encoding =
+            H::digest_to_bytes(&H::hash_bytes(&self.expiry_timestamp().to_be_bytes()));
+        [
+            input_data_encoding,
+            constraints_data_encoding,
+            expiry_date_data_encoding,
+        ]
+        .concat()
+    }
+}
+
+/// Both commitments to an intent: the EIP-712 digest, for Ethereum tooling, and the Poseidon
+/// commitment, which intents are signed over and circuits consume. Only the Poseidon
+/// commitment is authenticated by the signature: nothing binds the EIP-712 digest to it, so
+/// the digest must not be taken as signed by the intent signer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IntentCommitments {
+    pub eip712_digest: StructuredHash,
+    pub poseidon_commitment: PoseidonDigest,
+}
+
+impl IntentCommitments {
+    pub fn new(intent: &Intent) -> Self {
+        Self {
+            eip712_digest: intent.structured_hash_with::<Keccak>(),
+            poseidon_commitment: intent.structured_hash_with::<Poseidon>(),
+        }
+    }
+}
+
+impl Intent {
+    pub fn commitments(&self) -> IntentCommitments {
+        IntentCommitments::new(self)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use chrono::Utc;
+    use chrono::{NaiveDate, Utc};
+
+    /// An unsigned intent quoting `quote_amount` of token `0xff00..`, for at least 64 base
+    /// tokens `0x4000..`.
+    fn intent(quote_amount: u64, expiry_date: NaiveDateTime) -> Intent {
+        let mut quote_token = [0u8; 32];
+        quote_token[0] = 255;
+
+        let mut base_token = [0u8; 32];
+        base_token[0] = 64;
+
+        Intent {
+            public_key: [0u8; 32],
+            signature: Signature([0u8; 64]),
+            inputs: IntentInputs {
+                quote_amount: BigUint::from(quote_amount),
+                quote_token,
+                base_token,
+                direction: TradeDirection::Buy,
+            },
+            constraints: IntentConstraints {
+                min_base_token_amount: BigUint::from(64_u8),
+            },
+            expiry_date,
+        }
+    }
+
+    fn expiry_date() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2023, 11, 14)
+            .unwrap()
+            .and_hms_opt(22, 13, 20)
+            .unwrap()
+    }
 
     #[test]
     fn it_works_swap_inputs_type_encoding() {
@@ -184,7 +304,7 @@ mod tests {
         assert_eq!(
             Intent::type_encode(),
             format!(
-                "Intent(IntentInputs inputs,IntentConstraints constraints){}{}",
+                "Intent(IntentInputs inputs,IntentConstraints constraints,uint64 expiry_date){}{}",
                 IntentConstraints::type_encode(),
                 IntentInputs::type_encode(),
             )
@@ -234,59 +354,36 @@ mod tests {
 
     #[test]
     fn it_works_swap_intent_struct_hash() {
-        let mut quote_token = [0u8; 32];
-        quote_token[0] = 255;
-
-        let mut base_token = [0u8; 32];
-        base_token[0] = 64;
-
-        let intent = Intent {
-            public_key: [0u8; 32],
-            signature: Signature([0u8; 64]),
-            inputs: IntentInputs {
-                quote_amount: BigUint::from(1_000_000_000_000_u64),
-                quote_token,
-                base_token,
-                direction: TradeDirection::Buy,
-            },
-            constraints: IntentConstraints {
-                min_base_token_amount: BigUint::from(64_u8),
-            },
-            expiry_date: Utc::now().naive_utc(),
-        };
+        let intent = intent(1_000_000_000_000, expiry_date());
 
         let hash = intent.structured_hash();
         assert_eq!(
             hash,
             [
-                13, 34, 246, 195, 58, 50, 128, 107, 108, 146, 1, 227, 198, 214, 220, 240, 97, 224,
-                195, 42, 99, 23, 54, 92, 247, 146, 24, 108, 207, 175, 86, 231
+                14, 238, 73, 134, 126, 47, 53, 59, 247, 34, 95, 122, 103, 251, 133, 253, 27, 110,
+                89, 133, 133, 65, 94, 79, 105, 62, 67, 119, 135, 89, 94, 196
             ]
         );
     }
 
     #[test]
-    fn test_json_intent_deserialization() {
-        let mut quote_token = [0u8; 32];
-        quote_token[0] = 255;
-
-        let mut base_token = [0u8; 32];
-        base_token[0] = 64;
+    fn it_binds_the_struct_hash_to_the_expiry_date() {
+        let intent = intent(1_000, expiry_date());
+        assert_eq!(intent.expiry_timestamp(), 1_700_000_000);
+
+        let mut extended_intent = intent.clone();
+        extended_intent.expiry_date = expiry_date() + chrono::Duration::seconds(1);
+        assert_ne!(intent.structured_hash(), extended_intent.structured_hash());
+        assert_ne!(
+            intent.structured_hash_with::<Poseidon>(),
+            extended_intent.structured_hash_with::<Poseidon>()
+        );
+        assert_ne!(intent.nullifier(), extended_intent.nullifier());
+    }
 
-        let intent = Intent {
-            public_key: [0u8; 32],
-            signature: Signature([0u8; 64]),
-            inputs: IntentInputs {
-                quote_amount: BigUint::from(1_000_u64),
-                quote_token,
-                base_token,
-                direction: TradeDirection::Buy,
-            },
-            constraints: IntentConstraints {
-                min_base_token_amount: BigUint::from(64_u8),
-            },
-            expiry_date: Utc::now().naive_utc(),
-        };
+    #[test]
+    fn test_json_intent_deserialization() {
+        let intent = intent(1_000, Utc::now().naive_utc());
 
         let value = serde_json::to_value(intent).unwrap();
         let should_be_value_str = serde_json::json!(
@@ -307,4 +404,61 @@ mod tests {
         );
         assert_eq!(value, should_be_value_str);
     }
+
+    #[test]
+    fn it_works_intent_expiry() {
+        let expiry_date = expiry_date();
+        let intent = intent(1_000, expiry_date);
+
+        assert!(!intent.is_expired_at(&(expiry_date - chrono::Duration::seconds(1))));
+        assert!(intent.is_expired_at(&expiry_date));
+        assert!(intent.is_expired_at(&(expiry_date + chrono::Duration::seconds(1))));
+    }
+
+    #[test]
+    fn it_works_intent_expiry_serialization() {
+        let expiry_date = expiry_date();
+        let intent = intent(1_000, expiry_date);
+
+        let value = serde_json::to_value(&intent).unwrap();
+        assert_eq!(value["expiry_date"], "2023-11-14T22:13:20");
+        let deserialized = serde_json::from_value::<Intent>(value).unwrap();
+        assert_eq!(deserialized.expiry_date, expiry_date);
+    }
+
+    #[test]
+    fn it_works_intent_nullifier() {
+        let intent = intent(1_000, Utc::now().naive_utc());
+
+        let mut other_signer_intent = intent.clone();
+        other_signer_intent.public_key = [1u8; 32];
+
+        assert_eq!(intent.nullifier(), intent.clone().nullifier());
+        assert_eq!(
+            intent.structured_hash(),
+            other_signer_intent.structured_hash()
+        );
+        assert_ne!(intent.nullifier(), other_signer_intent.nullifier());
+    }
+
+    #[test]
+    fn it_works_intent_commitments() {
+        let intent = intent(1_000, Utc::now().naive_utc());
+
+        let commitments = intent.commitments();
+        assert_eq!(commitments.eip712_digest, intent.structured_hash());
+        assert_eq!(
+            commitments.poseidon_commitment,
+            intent.structured_hash_with::<Poseidon>()
+        );
+        // Keccak hashes minimal amount encodings, Poseidon fixed length ones
+        assert_eq!(
+            Keccak::encode_amount(&intent.inputs.quote_amount),
+            vec![3, 232]
+        );
+        assert_eq!(
+            Poseidon::encode_amount(&intent.inputs.quote_amount).len(),
+            AMOUNT_ENCODING_LEN
+        );
+    }
 }
@@ -1,7 +1,10 @@
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
+pub mod batch;
+pub mod competition;
 pub mod intent;
 pub mod price_oracle;
+pub mod signature;
 pub mod solver;
 pub mod structured_hash;
 
@@ -9,6 +12,8 @@ pub mod structured_hash;
 // TODO: refactor this directly
 pub type PublicKey = [u8; 32];
 pub type TokenAddress = [u8; 32];
+/// Identifier of an intent, assigned once accepted, and unique across restarts.
+pub type IntentId = i64;
 
 #[derive(Clone, Debug)]
 pub struct Signature(pub [u8; 64]);
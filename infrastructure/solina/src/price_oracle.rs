@@ -1,7 +1,10 @@
 // TODO: eventually, we might be able to compute volumes and total liquidity over intent batches
 // from the intent data alone. Currently, to simplify the logic, we assume we have access to an API
 // to query current prices of tokens, denominated say in ETH
+use hex::encode;
 use num_bigint::BigUint;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 use crate::TokenAddress;
 
@@ -10,3 +13,33 @@ pub type Price = BigUint;
 pub trait PriceOracle {
     fn get_current_price(&self, token_address: TokenAddress) -> Price;
 }
+
+/// Prices of a set of tokens, frozen at some point in time, indexed by hex encoded token
+/// address. Tokens missing from the snapshot are priced at zero.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct PriceSnapshot {
+    pub prices: BTreeMap<String, Price>,
+}
+
+impl PriceSnapshot {
+    /// Snapshot of the current prices of `tokens`, as given by `price_oracle`.
+    pub fn new(
+        price_oracle: &impl PriceOracle,
+        tokens: impl IntoIterator<Item = TokenAddress>,
+    ) -> Self {
+        let prices = tokens
+            .into_iter()
+            .map(|token| (encode(token), price_oracle.get_current_price(token)))
+            .collect();
+        Self { prices }
+    }
+}
+
+impl PriceOracle for PriceSnapshot {
+    fn get_current_price(&self, token_address: TokenAddress) -> Price {
+        self.prices
+            .get(&encode(token_address))
+            .cloned()
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,220 @@
+// Native verification of intent signatures, as done in circuit by `solina-circuits`, so that
+// the RISC Zero batch validation guest rejects forged intents too. The scheme is selected at
+// compile time, via the `ecdsa` (default) or the `eddsa` feature, which `solina-circuits`
+// forwards. See `solina_circuits::signature` for the message and key layouts.
+use crate::{
+    intent::Intent,
+    structured_hash::{HashBackend, Poseidon, StructuredHashInterface},
+};
+
+#[cfg(all(feature = "ecdsa", feature = "eddsa"))]
+compile_error!("Features `ecdsa` and `eddsa` are mutually exclusive");
+
+#[cfg(not(any(feature = "ecdsa", feature = "eddsa")))]
+compile_error!("One of the features `ecdsa` or `eddsa` must be enabled");
+
+#[cfg(feature = "ecdsa")]
+pub use ecdsa::*;
+
+#[cfg(feature = "eddsa")]
+pub use eddsa::*;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SignatureError {
+    /// The intent public key is not a valid curve point
+    InvalidPublicKey,
+    /// The intent signature does not verify
+    InvalidSignature,
+}
+
+/// Verifies the signature of `intent`, by its public key, over its Poseidon structured hash.
+pub fn verify_intent_signature(intent: &Intent) -> Result<(), SignatureError> {
+    let message = Poseidon::digest_to_bytes(&intent.structured_hash_with::<Poseidon>());
+    verify_signature(&message, &intent.public_key, &intent.signature.0)
+}
+
+#[cfg(feature = "ecdsa")]
+mod ecdsa {
+    use super::SignatureError;
+    use crate::{
+        intent::Intent,
+        structured_hash::{HashBackend, Poseidon, StructuredHashInterface},
+        Signature,
+    };
+    use num_bigint::BigUint;
+    use plonky2::field::{
+        secp256k1_base::Secp256K1Base,
+        secp256k1_scalar::Secp256K1Scalar,
+        types::{Field, PrimeField},
+    };
+    use plonky2_ecdsa::curve::{
+        curve_types::AffinePoint,
+        ecdsa::{sign_message, verify_message, ECDSAPublicKey, ECDSASecretKey, ECDSASignature},
+        secp256k1::Secp256K1,
+    };
+
+    pub(crate) fn verify_signature(
+        message: &[u8],
+        public_key: &[u8],
+        signature: &[u8],
+    ) -> Result<(), SignatureError> {
+        if signature.len() != 64 {
+            return Err(SignatureError::InvalidSignature);
+        }
+        let message = reduce_scalar(message);
+        let r = reduce_scalar(&signature[..32]);
+        let s = reduce_scalar(&signature[32..]);
+        lift_x(public_key, &message, &r, &s).map(|_| ())
+    }
+
+    /// Signs `intent` with `secret_key`, over its Poseidon structured hash, and sets its
+    /// public key to the x-coordinate of the signer public key.
+    pub fn sign_intent(intent: &mut Intent, secret_key: ECDSASecretKey<Secp256K1>) {
+        let public_key = secret_key.to_public();
+        intent.public_key = to_be_bytes::<32>(&public_key.0.x.to_canonical_biguint());
+
+        let message = reduce_scalar(&Poseidon::digest_to_bytes(
+            &intent.structured_hash_with::<Poseidon>(),
+        ));
+        let signature = sign_message(
+            Secp256K1Scalar::from_noncanonical_biguint(message),
+            secret_key,
+        );
+        let signature = [
+            to_be_bytes::<32>(&signature.r.to_canonical_biguint()),
+            to_be_bytes::<32>(&signature.s.to_canonical_biguint()),
+        ]
+        .concat();
+        intent.signature = Signature(signature.try_into().expect("Signatures are 64 bytes long"));
+    }
+
+    /// Big endian bytes of `value`, left padded with zeros to `N` bytes.
+    fn to_be_bytes<const N: usize>(value: &BigUint) -> [u8; N] {
+        let bytes = value.to_bytes_be();
+        let mut padded = [0u8; N];
+        padded[N - bytes.len()..].copy_from_slice(&bytes);
+        padded
+    }
+
+    /// Big endian `bytes`, reduced modulo the secp256k1 scalar field order.
+    pub fn reduce_scalar(bytes: &[u8]) -> BigUint {
+        Secp256K1Scalar::from_noncanonical_biguint(BigUint::from_bytes_be(bytes))
+            .to_canonical_biguint()
+    }
+
+    /// Lifts an x-coordinate to the secp256k1 point, of either parity, the signature
+    /// `(r, s)` of `message` verifies against.
+    pub fn lift_x(
+        x_bytes: &[u8],
+        message: &BigUint,
+        r: &BigUint,
+        s: &BigUint,
+    ) -> Result<(BigUint, BigUint), SignatureError> {
+        let p = Secp256K1Base::order();
+        let x = BigUint::from_bytes_be(x_bytes);
+        if x >= p {
+            return Err(SignatureError::InvalidPublicKey);
+        }
+
+        // secp256k1 base field has p = 3 mod 4, so a square root of c is c^((p + 1) / 4)
+        let c = (x.modpow(&BigUint::from(3_u8), &p) + BigUint::from(7_u8)) % &p;
+        let y = c.modpow(&((&p + BigUint::from(1_u8)) >> 2), &p);
+        if y.modpow(&BigUint::from(2_u8), &p) != c {
+            return Err(SignatureError::InvalidPublicKey);
+        }
+
+        let signature = ECDSASignature {
+            r: Secp256K1Scalar::from_noncanonical_biguint(r.clone()),
+            s: Secp256K1Scalar::from_noncanonical_biguint(s.clone()),
+        };
+        let message = Secp256K1Scalar::from_noncanonical_biguint(message.clone());
+        [y.clone(), &p - &y]
+            .into_iter()
+            .find(|y| {
+                let public_key = ECDSAPublicKey(AffinePoint::<Secp256K1>::nonzero(
+                    Secp256K1Base::from_noncanonical_biguint(x.clone()),
+                    Secp256K1Base::from_noncanonical_biguint(y.clone()),
+                ));
+                verify_message(message, signature, public_key)
+            })
+            .map(|y| (x.clone(), y))
+            .ok_or(SignatureError::InvalidSignature)
+    }
+}
+
+#[cfg(feature = "eddsa")]
+mod eddsa {
+    use super::SignatureError;
+    use plonky2_ed25519::curve::eddsa::verify_message;
+
+    pub(crate) fn verify_signature(
+        message: &[u8],
+        public_key: &[u8],
+        signature: &[u8],
+    ) -> Result<(), SignatureError> {
+        if public_key.len() != 32 {
+            return Err(SignatureError::InvalidPublicKey);
+        }
+        if signature.len() != 64 || !verify_message(message, signature, public_key) {
+            return Err(SignatureError::InvalidSignature);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "ecdsa"))]
+mod tests {
+    use super::*;
+    use crate::{
+        intent::{IntentConstraints, IntentInputs, TradeDirection},
+        Signature,
+    };
+    use chrono::NaiveDate;
+    use num_bigint::BigUint;
+    use plonky2::field::{secp256k1_scalar::Secp256K1Scalar, types::Field};
+    use plonky2_ecdsa::curve::ecdsa::ECDSASecretKey;
+
+    #[test]
+    fn it_works_intent_signature_verification() {
+        let mut intent = Intent::new(
+            [0u8; 32],
+            IntentInputs::new(
+                [1u8; 32],
+                [2u8; 32],
+                BigUint::from(100_u8),
+                TradeDirection::Sell,
+            ),
+            IntentConstraints::new(BigUint::from(50_u8)),
+            Signature([0u8; 64]),
+            NaiveDate::from_ymd_opt(2023, 11, 14)
+                .unwrap()
+                .and_hms_opt(22, 13, 20)
+                .unwrap(),
+        );
+        assert!(verify_intent_signature(&intent).is_err());
+
+        sign_intent(
+            &mut intent,
+            ECDSASecretKey(Secp256K1Scalar::from_canonical_u64(1)),
+        );
+        assert_eq!(verify_intent_signature(&intent), Ok(()));
+
+        let mut tampered = intent.clone();
+        tampered.inputs.quote_amount += 1_u8;
+        assert_eq!(
+            verify_intent_signature(&tampered),
+            Err(SignatureError::InvalidSignature)
+        );
+
+        let mut re_signed = intent;
+        sign_intent(
+            &mut re_signed,
+            ECDSASecretKey(Secp256K1Scalar::from_canonical_u64(2)),
+        );
+        re_signed.public_key = tampered.public_key;
+        assert_eq!(
+            verify_intent_signature(&re_signed),
+            Err(SignatureError::InvalidSignature)
+        );
+    }
+}
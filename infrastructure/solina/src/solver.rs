@@ -1,7 +1,15 @@
-use crate::{intent::Intent, price_oracle::PriceOracle};
+use crate::{
+    intent::{Intent, Nullifier},
+    price_oracle::{Price, PriceOracle},
+    structured_hash::{StructuredHash, StructuredHashInterface},
+    PublicKey,
+};
+use chrono::NaiveDateTime;
+use hex::encode;
 use num_bigint::BigUint;
 use num_traits::ops::checked::CheckedDiv;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct BatchSolution {
@@ -11,6 +19,19 @@ pub struct BatchSolution {
 
 impl BatchSolution {
     pub fn new(batch_matches: Vec<Match>, price_oracle: impl PriceOracle) -> Self {
+        let total_liquidity = Self::liquidity(&batch_matches, &price_oracle);
+        Self {
+            batch_matches,
+            total_liquidity,
+        }
+    }
+
+    /// Total liquidity of the solution, with prices given by `price_oracle`.
+    pub fn total_liquidity_with(&self, price_oracle: &impl PriceOracle) -> BigUint {
+        Self::liquidity(&self.batch_matches, price_oracle)
+    }
+
+    fn liquidity(batch_matches: &[Match], price_oracle: &impl PriceOracle) -> BigUint {
         // TODO: review this formula.
         //
         // 1. Notice we need to be invariant on the order of the proposed tokens.
@@ -18,20 +39,199 @@ impl BatchSolution {
         //
         // 2. Since we are denominating the volume in ETH, that might be actually be
         // already invariant, after denominating everything in ETH.
-        let total_liquidity = batch_matches
+        batch_matches
             .iter()
             .map(|m| {
                 m.swapped_amount.token_b_amount.clone()
                     * price_oracle.get_current_price(m.intent_b.inputs.quote_token)
             })
-            .sum();
-        Self {
-            batch_matches,
-            total_liquidity,
+            .sum()
+    }
+
+    /// Settlements of the solution matches, in order, with prices given by `price_oracle`.
+    pub fn settlements_with(&self, price_oracle: &impl PriceOracle) -> Vec<MatchSettlement> {
+        self.batch_matches
+            .iter()
+            .map(|m| MatchSettlement {
+                token_a_amount: m.swapped_amount.token_a_amount.clone(),
+                token_b_amount: m.swapped_amount.token_b_amount.clone(),
+                price: price_oracle.get_current_price(m.intent_b.inputs.quote_token),
+            })
+            .collect()
+    }
+
+    pub fn batch_matches(&self) -> &[Match] {
+        &self.batch_matches
+    }
+
+    pub fn total_liquidity(&self) -> &BigUint {
+        &self.total_liquidity
+    }
+
+    /// Nullifiers of all the intents settled by the solution. An intent matched
+    /// more than once in the same batch contributes a single nullifier.
+    pub fn nullifiers(&self) -> Vec<Nullifier> {
+        self.batch_matches
+            .iter()
+            .flat_map(|m| [m.intent_a().nullifier(), m.intent_b().nullifier()])
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    /// Nullifiers of the intents of each match, intent A first, in match order. This is the
+    /// order batch proofs output them in, so that an intent matched more than once
+    /// contributes as many nullifiers.
+    pub fn match_nullifiers(&self) -> Vec<Nullifier> {
+        self.batch_matches
+            .iter()
+            .flat_map(|m| [m.intent_a().nullifier(), m.intent_b().nullifier()])
+            .collect()
+    }
+
+    /// Total quote amount swapped and base amount received by each matched intent, indexed
+    /// by structured hash and signer, as structured hashes do not cover the signer. The
+    /// `token_a_amount` of a match is paid by `intent_a` in its quote token, and received by
+    /// `intent_b` in its base token, and conversely for `token_b_amount`.
+    pub fn matched_amounts(&self) -> BTreeMap<(StructuredHash, PublicKey), (BigUint, BigUint)> {
+        let mut amounts = BTreeMap::<(StructuredHash, PublicKey), (BigUint, BigUint)>::new();
+        for m in &self.batch_matches {
+            let (swapped, received) = amounts.entry(m.intent_a.key()).or_default();
+            *swapped += &m.swapped_amount.token_a_amount;
+            *received += &m.swapped_amount.token_b_amount;
+            let (swapped, received) = amounts.entry(m.intent_b.key()).or_default();
+            *swapped += &m.swapped_amount.token_b_amount;
+            *received += &m.swapped_amount.token_a_amount;
         }
+        amounts
+    }
+
+    /// Merges solutions of the same batch into a single settlement, whose total liquidity
+    /// is the sum of theirs. Solutions are not checked for compatibility.
+    pub fn merge(solutions: impl IntoIterator<Item = BatchSolution>) -> Self {
+        solutions.into_iter().fold(
+            Self {
+                batch_matches: vec![],
+                total_liquidity: BigUint::default(),
+            },
+            |mut merged, solution| {
+                merged.batch_matches.extend(solution.batch_matches);
+                merged.total_liquidity += solution.total_liquidity;
+                merged
+            },
+        )
     }
+
+    /// Checks every constraint of the solution against the full batch of `intents` it
+    /// solves, sealed at `batch_timestamp`:
+    ///
+    /// 1. every matched intent belongs to the batch, signed by the same signer, and has not
+    ///    expired,
+    /// 2. the tokens of both intents of a match are in reverse order,
+    /// 3. the total quote amount swapped by an intent does not exceed its quote amount,
+    /// 4. the total base amount received by an intent satisfies its constraints.
+    ///
+    /// See `matched_amounts` for the amounts swapped and received by each intent. Intent
+    /// signatures are left to the callers, see `BatchValidationInputs::validate`.
+    pub fn validate_batch(
+        &self,
+        intents: &[Intent],
+        batch_timestamp: &NaiveDateTime,
+    ) -> Result<(), SolutionValidationError> {
+        let batch = intents
+            .iter()
+            .map(|intent| (intent.key(), intent))
+            .collect::<BTreeMap<_, _>>();
+        for m in &self.batch_matches {
+            let intent_a_hash = m.intent_a.structured_hash();
+            let intent_b_hash = m.intent_b.structured_hash();
+            for (hash, intent) in [(intent_a_hash, &m.intent_a), (intent_b_hash, &m.intent_b)] {
+                if !batch.contains_key(&(hash, intent.public_key)) {
+                    return Err(SolutionValidationError::UnknownIntent(hash));
+                }
+                if intent.is_expired_at(batch_timestamp) {
+                    return Err(SolutionValidationError::ExpiredIntent(hash));
+                }
+            }
+
+            if m.intent_a.inputs.quote_token != m.intent_b.inputs.base_token
+                || m.intent_a.inputs.base_token != m.intent_b.inputs.quote_token
+            {
+                return Err(SolutionValidationError::TokenMismatch(
+                    intent_a_hash,
+                    intent_b_hash,
+                ));
+            }
+        }
+
+        for (key, (swapped, received)) in self.matched_amounts() {
+            let intent = batch[&key];
+            let hash = key.0;
+            if swapped > intent.inputs.quote_amount {
+                return Err(SolutionValidationError::ExceededQuoteAmount(hash));
+            }
+            if received < intent.constraints.min_base_token_amount {
+                return Err(SolutionValidationError::UnsatisfiedConstraints(hash));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SolutionValidationError {
+    /// The intent, with given structured hash, expired before the batch was sealed
+    ExpiredIntent(StructuredHash),
+    /// The intent, with given structured hash, does not belong to the batch, or was signed
+    /// by another signer
+    UnknownIntent(StructuredHash),
+    /// The intents, with given structured hashes, do not trade the same tokens
+    TokenMismatch(StructuredHash, StructuredHash),
+    /// The intent, with given structured hash, swaps more than its quote amount
+    ExceededQuoteAmount(StructuredHash),
+    /// The intent, with given structured hash, receives less than its minimum base amount
+    UnsatisfiedConstraints(StructuredHash),
+    /// The batch root does not commit to the batch intents
+    BatchRootMismatch,
+    /// The intent, with given structured hash, is not signed by its public key
+    InvalidSignature(StructuredHash),
+    /// The solution total liquidity does not match its liquidity at the batch prices
+    LiquidityMismatch,
 }
 
+impl std::fmt::Display for SolutionValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ExpiredIntent(hash) => write!(f, "Intent {} has expired", encode(hash)),
+            Self::UnknownIntent(hash) => {
+                write!(f, "Intent {} does not belong to the batch", encode(hash))
+            }
+            Self::TokenMismatch(hash_a, hash_b) => write!(
+                f,
+                "Intents {} and {} do not trade the same tokens",
+                encode(hash_a),
+                encode(hash_b)
+            ),
+            Self::ExceededQuoteAmount(hash) => {
+                write!(f, "Intent {} exceeds its quote amount", encode(hash))
+            }
+            Self::UnsatisfiedConstraints(hash) => {
+                write!(f, "Intent {} constraints are not satisfied", encode(hash))
+            }
+            Self::BatchRootMismatch => write!(f, "Batch root does not match the batch intents"),
+            Self::InvalidSignature(hash) => {
+                write!(f, "Intent {} has an invalid signature", encode(hash))
+            }
+            Self::LiquidityMismatch => {
+                write!(f, "Total liquidity does not match the batch prices")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SolutionValidationError {}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Match {
     pub(crate) intent_a: Intent,
@@ -61,6 +261,15 @@ impl Match {
     }
 }
 
+/// A match, as settled by a solution: the amounts it swaps, and the price of the quote token
+/// of intent B its liquidity is computed with (see `BatchSolution::total_liquidity_with`).
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct MatchSettlement {
+    pub token_a_amount: BigUint,
+    pub token_b_amount: BigUint,
+    pub price: Price,
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct SwappedAmount {
     token_a_amount: BigUint,
@@ -1,15 +1,120 @@
 use keccak_hash::keccak;
+use num_bigint::BigUint;
 
-#[allow(dead_code)]
-pub(crate) type StructuredHash = [u8; 32];
+pub type StructuredHash = [u8; 32];
+
+/// A hash function over which structured hashes can be computed.
+pub trait HashBackend {
+    type Digest: Clone;
+
+    fn hash_bytes(bytes: &[u8]) -> Self::Digest;
+    fn two_to_one(left: Self::Digest, right: Self::Digest) -> Self::Digest;
+    fn digest_to_bytes(digest: &Self::Digest) -> Vec<u8>;
+    /// Bytes an amount is hashed as.
+    fn encode_amount(amount: &BigUint) -> Vec<u8>;
+}
+
+/// Keccak backend, compatible with Ethereum's EIP-712 structured hashes.
+pub struct Keccak;
+
+impl HashBackend for Keccak {
+    type Digest = StructuredHash;
+
+    fn hash_bytes(bytes: &[u8]) -> Self::Digest {
+        keccak(bytes).to_fixed_bytes()
+    }
+
+    fn two_to_one(left: Self::Digest, right: Self::Digest) -> Self::Digest {
+        keccak([left, right].concat()).to_fixed_bytes()
+    }
+
+    fn digest_to_bytes(digest: &Self::Digest) -> Vec<u8> {
+        digest.to_vec()
+    }
+
+    /// Minimal big endian bytes, as EIP-712 digests have always been computed over.
+    fn encode_amount(amount: &BigUint) -> Vec<u8> {
+        amount.to_bytes_be()
+    }
+}
 
 pub trait StructuredHashInterface {
     fn type_encode() -> String;
-    fn data_encode(&self) -> Vec<u8>;
+    fn data_encode_with<H: HashBackend>(&self) -> Vec<u8>;
+
+    fn data_encode(&self) -> Vec<u8> {
+        self.data_encode_with::<Keccak>()
+    }
+
     fn structured_hash(&self) -> StructuredHash {
-        let type_encoding = keccak(Self::type_encode()).to_fixed_bytes();
-        let data_encoding = keccak(self.data_encode()).to_fixed_bytes();
-        let output = keccak([type_encoding, data_encoding].concat());
-        output.to_fixed_bytes()
+        self.structured_hash_with::<Keccak>()
+    }
+
+    fn structured_hash_with<H: HashBackend>(&self) -> H::Digest {
+        let type_encoding = H::hash_bytes(Self::type_encode().as_bytes());
+        let data_encoding = H::hash_bytes(&self.data_encode_with::<H>());
+        H::two_to_one(type_encoding, data_encoding)
+    }
+}
+
+pub use poseidon::{Poseidon, PoseidonDigest};
+
+mod poseidon {
+    use super::HashBackend;
+    use crate::intent::encode_amount;
+    use num_bigint::BigUint;
+    use plonky2::{
+        field::{
+            goldilocks_field::GoldilocksField,
+            types::{Field, PrimeField64},
+        },
+        hash::{hash_types::HashOut, poseidon::PoseidonHash},
+        plonk::config::Hasher,
+    };
+
+    pub type PoseidonDigest = HashOut<GoldilocksField>;
+
+    /// Poseidon backend over the Goldilocks field, matching plonky2's in-circuit Poseidon.
+    ///
+    /// Bytes are packed into big endian 32-bit limbs (zero padded on the right),
+    /// one limb per field element, which is how `U32Target`s are laid out in circuits.
+    pub struct Poseidon;
+
+    impl Poseidon {
+        pub fn bytes_to_field_elements(bytes: &[u8]) -> Vec<GoldilocksField> {
+            bytes
+                .chunks(4)
+                .map(|chunk| {
+                    let mut limb = [0u8; 4];
+                    limb[..chunk.len()].copy_from_slice(chunk);
+                    GoldilocksField::from_canonical_u32(u32::from_be_bytes(limb))
+                })
+                .collect()
+        }
+    }
+
+    impl HashBackend for Poseidon {
+        type Digest = PoseidonDigest;
+
+        fn hash_bytes(bytes: &[u8]) -> Self::Digest {
+            PoseidonHash::hash_no_pad(&Self::bytes_to_field_elements(bytes))
+        }
+
+        fn two_to_one(left: Self::Digest, right: Self::Digest) -> Self::Digest {
+            PoseidonHash::two_to_one(left, right)
+        }
+
+        fn digest_to_bytes(digest: &Self::Digest) -> Vec<u8> {
+            digest
+                .elements
+                .iter()
+                .flat_map(|e| e.to_canonical_u64().to_be_bytes())
+                .collect()
+        }
+
+        /// Fixed length bytes, so that circuits hash amounts of any magnitude alike.
+        fn encode_amount(amount: &BigUint) -> Vec<u8> {
+            encode_amount(amount)
+        }
     }
 }
@@ -0,0 +1,6 @@
+CREATE TABLE nullifiers (
+    id         INTEGER  NOT NULL  PRIMARY KEY AUTOINCREMENT,
+    nullifier  TEXT     NOT NULL  UNIQUE,
+    batch_id   INTEGER  NOT NULL,
+    created_at DATETIME NOT NULL
+);
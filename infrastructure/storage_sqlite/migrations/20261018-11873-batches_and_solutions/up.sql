@@ -0,0 +1,17 @@
+CREATE TABLE batches (
+    id         INTEGER  NOT NULL  PRIMARY KEY,
+    root       TEXT     NOT NULL,
+    sealed_at  DATETIME NOT NULL
+);
+
+CREATE TABLE solutions (
+    id             INTEGER  NOT NULL  PRIMARY KEY AUTOINCREMENT,
+    batch_id       INTEGER  NOT NULL,
+    solver_address TEXT     NOT NULL,
+    solution       TEXT     NOT NULL,
+    proof          TEXT     NOT NULL,
+    score          TEXT     NOT NULL,
+    created_at     DATETIME NOT NULL
+);
+
+CREATE INDEX solutions_batch_id ON solutions (batch_id);
@@ -0,0 +1,11 @@
+CREATE TABLE batches_sealed (
+    id         INTEGER  NOT NULL  PRIMARY KEY,
+    root       TEXT     NOT NULL,
+    sealed_at  DATETIME NOT NULL
+);
+
+INSERT INTO batches_sealed (id, root, sealed_at)
+SELECT id, root, sealed_at FROM batches WHERE root IS NOT NULL AND sealed_at IS NOT NULL;
+
+DROP TABLE batches;
+ALTER TABLE batches_sealed RENAME TO batches;
@@ -0,0 +1,19 @@
+-- Batches are now tracked from the moment they start collecting intents, so the root
+-- and the sealing timestamp are only set once a batch is sealed.
+CREATE TABLE batches_lifecycle (
+    id                INTEGER  NOT NULL  PRIMARY KEY,
+    state             TEXT     NOT NULL,
+    root              TEXT,
+    created_at        DATETIME NOT NULL,
+    sealed_at         DATETIME,
+    solving_deadline  DATETIME,
+    updated_at        DATETIME NOT NULL
+);
+
+INSERT INTO batches_lifecycle (id, state, root, created_at, sealed_at, solving_deadline, updated_at)
+SELECT id, 'sealed', root, sealed_at, sealed_at, NULL, sealed_at FROM batches;
+
+DROP TABLE batches;
+ALTER TABLE batches_lifecycle RENAME TO batches;
+
+CREATE INDEX batches_state ON batches (state);
@@ -0,0 +1,18 @@
+-- SQLite cannot drop a column referencing another table, so the table is rebuilt without it.
+CREATE TABLE batches_unselected (
+    id                INTEGER  NOT NULL  PRIMARY KEY,
+    state             TEXT     NOT NULL,
+    root              TEXT,
+    created_at        DATETIME NOT NULL,
+    sealed_at         DATETIME,
+    solving_deadline  DATETIME,
+    updated_at        DATETIME NOT NULL
+);
+
+INSERT INTO batches_unselected (id, state, root, created_at, sealed_at, solving_deadline, updated_at)
+SELECT id, state, root, created_at, sealed_at, solving_deadline, updated_at FROM batches;
+
+DROP TABLE batches;
+ALTER TABLE batches_unselected RENAME TO batches;
+
+CREATE INDEX batches_state ON batches (state);
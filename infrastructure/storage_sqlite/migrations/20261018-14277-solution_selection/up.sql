@@ -0,0 +1 @@
+ALTER TABLE batches ADD COLUMN winning_solution_id INTEGER REFERENCES solutions (id);
@@ -0,0 +1 @@
+ALTER TABLE solutions ADD COLUMN is_selected BOOLEAN NOT NULL DEFAULT 0;
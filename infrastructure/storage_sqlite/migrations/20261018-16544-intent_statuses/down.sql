@@ -0,0 +1 @@
+DROP TABLE intent_statuses;
@@ -0,0 +1,17 @@
+CREATE TABLE intent_statuses (
+    intent_id             INTEGER  NOT NULL  PRIMARY KEY,
+    structured_hash       TEXT     NOT NULL,
+    signer                TEXT     NOT NULL,
+    base_token            TEXT     NOT NULL,
+    quote_token           TEXT     NOT NULL,
+    status                TEXT     NOT NULL,
+    batch_id              INTEGER,
+    quote_amount_swapped  TEXT,
+    base_amount_received  TEXT,
+    created_at            DATETIME NOT NULL,
+    updated_at            DATETIME NOT NULL
+);
+
+CREATE INDEX intent_statuses_structured_hash ON intent_statuses (structured_hash);
+CREATE INDEX intent_statuses_signer ON intent_statuses (signer);
+CREATE INDEX intent_statuses_batch_id ON intent_statuses (batch_id);
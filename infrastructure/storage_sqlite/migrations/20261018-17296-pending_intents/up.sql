@@ -0,0 +1,7 @@
+-- Write-ahead log of the intents accepted into the mempool, from which the mempool is
+-- rebuilt on startup. Intents are removed once sealed in a batch, or expired.
+CREATE TABLE pending_intents (
+    id          INTEGER  NOT NULL  PRIMARY KEY,
+    intent      TEXT     NOT NULL,
+    created_at  DATETIME NOT NULL
+);
@@ -0,0 +1,3 @@
+-- Position of intents in their batch, in the order the batch root is computed over.
+-- Intents batched beforehand keep their id order.
+ALTER TABLE intents ADD COLUMN batch_position INTEGER NOT NULL DEFAULT 0;
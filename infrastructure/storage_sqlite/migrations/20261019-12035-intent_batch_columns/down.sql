@@ -0,0 +1,2 @@
+ALTER TABLE intents DROP COLUMN expiry_date;
+ALTER TABLE intents DROP COLUMN batch_id;
@@ -0,0 +1,4 @@
+-- Batch columns of the intents, as meant by 20230913-13225-update_intents_table. SQLite only
+-- adds NOT NULL columns with a default value.
+ALTER TABLE intents ADD COLUMN batch_id INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE intents ADD COLUMN expiry_date DATETIME NOT NULL DEFAULT '1970-01-01 00:00:00';
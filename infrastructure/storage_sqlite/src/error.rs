@@ -6,4 +6,6 @@ pub enum SolinaStorageError {
     StorageError(String),
     #[error("Conversino Error: `{0}`")]
     ConversionError(String),
+    #[error("Not Found: `{0}`")]
+    NotFound(String),
 }
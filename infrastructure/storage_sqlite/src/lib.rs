@@ -6,8 +6,7 @@ mod models;
 mod reader_writer;
 mod schema;
 
-use crate::{error::SolinaStorageError, reader_writer::ReadWriterTransaction};
-use diesel::{sql_query, Connection, RunQueryDsl, SqliteConnection};
+use diesel::{connection::SimpleConnection, sql_query, Connection, RunQueryDsl, SqliteConnection};
 use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
 use std::{
     fs::create_dir_all,
@@ -15,7 +14,15 @@ use std::{
     sync::{Arc, Mutex},
 };
 
-pub use models::AuthCredentials;
+pub use error::SolinaStorageError;
+pub use models::{
+    AuthCredentials, Batch, BatchState, IntentStatus, NewSolution, NewTrackedIntent, PendingIntent,
+    Solution, TrackedIntent,
+};
+pub use reader_writer::ReadWriterTransaction;
+
+/// Time waited for a lock held by another connection, before failing with a busy error.
+pub const BUSY_TIMEOUT_MS: u32 = 5000;
 
 #[derive(Clone)]
 pub struct SolinaStorage {
@@ -37,6 +44,14 @@ impl SolinaStorage {
         sql_query("PRAGMA foreign_keys = ON;")
             .execute(&mut connection)
             .map_err(|e| SolinaStorageError::StorageError(e.to_string()))?;
+        // with write-ahead logging, readers on other connections do not block on the
+        // writer, nor the writer on them
+        connection
+            .batch_execute(&format!(
+                "PRAGMA journal_mode = WAL; PRAGMA busy_timeout = {};",
+                BUSY_TIMEOUT_MS
+            ))
+            .map_err(|e| SolinaStorageError::StorageError(e.to_string()))?;
 
         Ok(Self {
             connection: Arc::new(Mutex::new(connection)),
@@ -56,6 +71,6 @@ impl SolinaStorage {
 impl SolinaStorage {
     pub fn create_transaction(&self) -> Result<ReadWriterTransaction<'_>, SolinaStorageError> {
         let lock = self.connection.lock().unwrap();
-        Ok(ReadWriterTransaction::new(lock))
+        ReadWriterTransaction::begin(lock)
     }
 }
@@ -0,0 +1,77 @@
+use crate::error::SolinaStorageError;
+use crate::schema::batches;
+use chrono::NaiveDateTime;
+use diesel::{Identifiable, Insertable, Queryable};
+use std::str::FromStr;
+
+/// Lifecycle of a batch. A batch collects intents until it is sealed, is then open to
+/// solutions for the duration of its solving window, after which the winning solution is
+/// selected and settled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BatchState {
+    Collecting,
+    Sealed,
+    Solving,
+    SolutionSelected,
+    Settled,
+}
+
+impl BatchState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Collecting => "collecting",
+            Self::Sealed => "sealed",
+            Self::Solving => "solving",
+            Self::SolutionSelected => "solution_selected",
+            Self::Settled => "settled",
+        }
+    }
+}
+
+impl FromStr for BatchState {
+    type Err = SolinaStorageError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "collecting" => Ok(Self::Collecting),
+            "sealed" => Ok(Self::Sealed),
+            "solving" => Ok(Self::Solving),
+            "solution_selected" => Ok(Self::SolutionSelected),
+            "settled" => Ok(Self::Settled),
+            _ => Err(SolinaStorageError::ConversionError(format!(
+                "Invalid batch state: {}",
+                s
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Queryable, Identifiable)]
+#[diesel(table_name=batches)]
+pub struct Batch {
+    pub id: i32,
+    pub state: String,
+    pub root: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub sealed_at: Option<NaiveDateTime>,
+    pub solving_deadline: Option<NaiveDateTime>,
+    pub updated_at: NaiveDateTime,
+    pub winning_solution_id: Option<i32>,
+    /// JSON encoded prices of the batch tokens at sealing time, used to score solutions.
+    pub price_snapshot: Option<String>,
+}
+
+impl Batch {
+    pub fn state(&self) -> Result<BatchState, SolinaStorageError> {
+        self.state.parse()
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name=batches)]
+pub struct NewBatch {
+    pub id: i32,
+    pub state: String,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
@@ -0,0 +1,84 @@
+use crate::error::SolinaStorageError;
+use crate::schema::intent_statuses;
+use chrono::NaiveDateTime;
+use diesel::{Identifiable, Insertable, Queryable};
+use solina::IntentId;
+use std::str::FromStr;
+
+/// Status of an intent, from its submission to its settlement. Intents wait in the mempool
+/// until the next batch is sealed, are either included in it or expire, and are then
+/// matched by the selected solution of their batch, if any, before being settled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IntentStatus {
+    Pending,
+    Batched,
+    Matched,
+    Settled,
+    Expired,
+}
+
+impl IntentStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Batched => "batched",
+            Self::Matched => "matched",
+            Self::Settled => "settled",
+            Self::Expired => "expired",
+        }
+    }
+}
+
+impl FromStr for IntentStatus {
+    type Err = SolinaStorageError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(Self::Pending),
+            "batched" => Ok(Self::Batched),
+            "matched" => Ok(Self::Matched),
+            "settled" => Ok(Self::Settled),
+            "expired" => Ok(Self::Expired),
+            _ => Err(SolinaStorageError::ConversionError(format!(
+                "Invalid intent status: {}",
+                s
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Queryable, Identifiable)]
+#[diesel(table_name=intent_statuses, primary_key(intent_id))]
+pub struct TrackedIntent {
+    pub intent_id: IntentId,
+    pub structured_hash: String,
+    pub signer: String,
+    pub base_token: String,
+    pub quote_token: String,
+    pub status: String,
+    pub batch_id: Option<i32>,
+    /// Amounts filled by the selected solution of the batch, once matched.
+    pub quote_amount_swapped: Option<String>,
+    pub base_amount_received: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl TrackedIntent {
+    pub fn status(&self) -> Result<IntentStatus, SolinaStorageError> {
+        self.status.parse()
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name=intent_statuses)]
+pub struct NewTrackedIntent {
+    pub intent_id: IntentId,
+    pub structured_hash: String,
+    pub signer: String,
+    pub base_token: String,
+    pub quote_token: String,
+    pub status: String,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
@@ -8,13 +8,13 @@ use num_traits::cast::ToPrimitive;
 use solina::structured_hash::StructuredHashInterface;
 use solina::{
     intent::{Intent as SolinaIntent, IntentConstraints, IntentInputs, TradeDirection},
-    Signature,
+    IntentId, Signature,
 };
 
 #[derive(Debug, Queryable, Identifiable, Insertable)]
 #[diesel(table_name = intents)]
 pub struct Intent {
-    pub id: i32,
+    pub id: IntentId,
     pub structured_hash: String,
     pub public_key: String,
     pub signature: String,
@@ -26,10 +26,17 @@ pub struct Intent {
     pub created_at: NaiveDateTime,
     pub batch_id: i32,
     pub expiry_date: NaiveDateTime,
+    /// Position of the intent in its batch.
+    pub batch_position: i32,
 }
 
 impl Intent {
-    pub fn from_intent(intent: &SolinaIntent, id: i32, batch_id: i32) -> Self {
+    pub fn from_intent(
+        intent: &SolinaIntent,
+        id: IntentId,
+        batch_id: i32,
+        batch_position: i32,
+    ) -> Self {
         let structured_hash = encode(intent.structured_hash());
         let public_key = encode(intent.public_key);
         let signature = encode(intent.signature.0);
@@ -53,6 +60,7 @@ impl Intent {
             direction,
             expiry_date: intent.expiry_date,
             batch_id,
+            batch_position,
         }
     }
 
@@ -78,7 +86,7 @@ impl Intent {
         let direction = TradeDirection::from_bool(self.direction);
 
         let intent_constraints = IntentConstraints::new(min_base_token_amount);
-        let intent_inputs = IntentInputs::new(base_token, quote_token, quote_amount, direction);
+        let intent_inputs = IntentInputs::new(quote_token, base_token, quote_amount, direction);
 
         Ok(SolinaIntent::new(
             public_key,
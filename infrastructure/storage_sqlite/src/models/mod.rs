@@ -1,8 +1,19 @@
 mod auth_credentials;
+mod batches;
 mod current_batch_id;
+mod intent_statuses;
 mod intents;
+mod nullifiers;
+mod pending_intents;
+mod solutions;
 mod solvers;
 
 pub use auth_credentials::{AuthCredentials, NewAuthCredentials};
+pub use batches::{Batch, BatchState, NewBatch};
+pub use current_batch_id::CurrentBatchId;
+pub use intent_statuses::{IntentStatus, NewTrackedIntent, TrackedIntent};
 pub use intents::Intent;
+pub use nullifiers::NewNullifier;
+pub use pending_intents::PendingIntent;
+pub use solutions::{NewSolution, Solution};
 pub use solvers::NewSolver;
@@ -0,0 +1,20 @@
+use crate::schema::nullifiers;
+use chrono::NaiveDateTime;
+use diesel::{Identifiable, Insertable, Queryable};
+
+#[derive(Debug, Queryable, Identifiable)]
+#[diesel(table_name=nullifiers)]
+pub struct Nullifier {
+    pub id: i32,
+    pub nullifier: String,
+    pub batch_id: i32,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name=nullifiers)]
+pub struct NewNullifier {
+    pub nullifier: String,
+    pub batch_id: i32,
+    pub created_at: NaiveDateTime,
+}
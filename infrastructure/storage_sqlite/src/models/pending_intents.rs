@@ -0,0 +1,14 @@
+use crate::schema::pending_intents;
+use chrono::NaiveDateTime;
+use diesel::{Identifiable, Insertable, Queryable};
+use solina::IntentId;
+
+/// An intent accepted into the mempool, but not sealed in a batch yet.
+#[derive(Debug, Queryable, Identifiable, Insertable)]
+#[diesel(table_name=pending_intents)]
+pub struct PendingIntent {
+    pub id: IntentId,
+    /// JSON encoded intent.
+    pub intent: String,
+    pub created_at: NaiveDateTime,
+}
@@ -0,0 +1,27 @@
+use crate::schema::solutions;
+use chrono::NaiveDateTime;
+use diesel::{Identifiable, Insertable, Queryable};
+
+#[derive(Debug, Queryable, Identifiable)]
+#[diesel(table_name=solutions)]
+pub struct Solution {
+    pub id: i32,
+    pub batch_id: i32,
+    pub solver_address: String,
+    pub solution: String,
+    pub proof: String,
+    pub score: String,
+    pub created_at: NaiveDateTime,
+    pub is_selected: bool,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name=solutions)]
+pub struct NewSolution {
+    pub batch_id: i32,
+    pub solver_address: String,
+    pub solution: String,
+    pub proof: String,
+    pub score: String,
+    pub created_at: NaiveDateTime,
+}
@@ -1,28 +1,47 @@
 use crate::{
     error::SolinaStorageError,
-    models::{AuthCredentials, Intent, NewAuthCredentials, NewSolver},
+    models::{
+        AuthCredentials, Batch, BatchState, CurrentBatchId, Intent, IntentStatus,
+        NewAuthCredentials, NewBatch, NewNullifier, NewSolution, NewSolver, NewTrackedIntent,
+        PendingIntent, Solution, TrackedIntent,
+    },
+    schema::intent_statuses,
 };
-use chrono::Utc;
+use chrono::{NaiveDateTime, Utc};
 use diesel::{
-    sql_query, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl, SqliteConnection,
+    connection::{AnsiTransactionManager, TransactionManager},
+    dsl::max,
+    sqlite::Sqlite,
+    ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl, SqliteConnection,
 };
-use solina::intent;
+use solina::{intent, IntentId};
 use std::sync::MutexGuard;
 
 // Sqlite does not make a distinction between read and write transactions.
 // Therefore, any transaction is writable. We will need to refactor this,
 // once we get past Sqlite.
+// Writes are only persisted once the transaction is committed, transactions dropped
+// before are rolled back. Transactions go through the diesel transaction manager, so
+// that diesel's own transactions are nested within them.
 pub struct ReadWriterTransaction<'a> {
     connection: MutexGuard<'a, SqliteConnection>,
     is_done: bool,
 }
 
 impl<'a> ReadWriterTransaction<'a> {
-    pub fn new(connection: MutexGuard<'a, SqliteConnection>) -> Self {
-        Self {
+    pub fn begin(
+        mut connection: MutexGuard<'a, SqliteConnection>,
+    ) -> Result<Self, SolinaStorageError> {
+        AnsiTransactionManager::begin_transaction(&mut *connection).map_err(|e| {
+            SolinaStorageError::StorageError(format!(
+                "Failed to begin transaction, with error: {}",
+                e
+            ))
+        })?;
+        Ok(Self {
             connection,
             is_done: false,
-        }
+        })
     }
 
     pub fn connection(&mut self) -> &mut SqliteConnection {
@@ -30,35 +49,40 @@ impl<'a> ReadWriterTransaction<'a> {
     }
 
     pub fn commit(&mut self) -> Result<(), SolinaStorageError> {
-        sql_query("COMMIT")
-            .execute(self.connection())
-            .map_err(|e| {
-                SolinaStorageError::StorageError(format!(
-                    "Failed to commit transaction, with error: {}",
-                    e
-                ))
-            })?;
+        AnsiTransactionManager::commit_transaction(self.connection()).map_err(|e| {
+            SolinaStorageError::StorageError(format!(
+                "Failed to commit transaction, with error: {}",
+                e
+            ))
+        })?;
         self.is_done = true;
         Ok(())
     }
 
     pub fn rollback(&mut self) -> Result<(), SolinaStorageError> {
-        sql_query("ROLLBACK")
-            .execute(self.connection())
-            .map_err(|e| {
-                SolinaStorageError::StorageError(format!(
-                    "Failed to rollback transaction, with error: {}",
-                    e
-                ))
-            })?;
+        AnsiTransactionManager::rollback_transaction(self.connection()).map_err(|e| {
+            SolinaStorageError::StorageError(format!(
+                "Failed to rollback transaction, with error: {}",
+                e
+            ))
+        })?;
         self.is_done = true;
         Ok(())
     }
 }
 
+impl<'a> Drop for ReadWriterTransaction<'a> {
+    fn drop(&mut self) {
+        if !self.is_done {
+            // nothing else can be done if the rollback fails, the connection is unusable
+            let _ = self.rollback();
+        }
+    }
+}
+
 impl<'a> ReadWriterTransaction<'a> {
     // ----------------------------------------------- Read methods -----------------------------------------------
-    pub fn get_intent(&mut self, id: i32) -> Result<Intent, SolinaStorageError> {
+    pub fn get_intent(&mut self, id: IntentId) -> Result<Intent, SolinaStorageError> {
         use crate::schema::intents;
 
         let result = intents::table
@@ -69,28 +93,53 @@ impl<'a> ReadWriterTransaction<'a> {
 
         match result {
             Some(output) => Ok(output),
-            None => Err(SolinaStorageError::StorageError(format!(
+            None => Err(SolinaStorageError::NotFound(format!(
                 "Could not find stored intent with id: {}",
                 id,
             ))),
         }
     }
 
-    pub fn get_intents_batch(&mut self, ids: &[i32]) -> Result<Vec<Intent>, SolinaStorageError> {
+    /// Returns the stored intents among `ids`. Ids of intents not stored are skipped.
+    pub fn get_intents_batch(
+        &mut self,
+        ids: &[IntentId],
+    ) -> Result<Vec<Intent>, SolinaStorageError> {
         use crate::schema::intents;
 
-        let results = intents::table
+        intents::table
             .filter(intents::id.eq_any(ids))
             .load::<Intent>(self.connection())
-            .map_err(|e| SolinaStorageError::StorageError(e.to_string()))?;
+            .map_err(|e| SolinaStorageError::StorageError(e.to_string()))
+    }
 
-        if results.is_empty() {
-            Err(SolinaStorageError::StorageError(
-                "Could not find stored intents for the provided ids".to_string(),
-            ))
-        } else {
-            Ok(results)
-        }
+    /// Returns a page of the intents of batch `batch_id`, in canonical order, the one its
+    /// root is computed over.
+    pub fn get_batch_intents(
+        &mut self,
+        batch_id: i32,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<Intent>, SolinaStorageError> {
+        use crate::schema::intents;
+
+        intents::table
+            .filter(intents::batch_id.eq(batch_id))
+            .order((intents::batch_position.asc(), intents::id.asc()))
+            .offset(offset)
+            .limit(limit)
+            .load::<Intent>(self.connection())
+            .map_err(|e| SolinaStorageError::StorageError(e.to_string()))
+    }
+
+    pub fn count_batch_intents(&mut self, batch_id: i32) -> Result<i64, SolinaStorageError> {
+        use crate::schema::intents;
+
+        intents::table
+            .filter(intents::batch_id.eq(batch_id))
+            .count()
+            .get_result(self.connection())
+            .map_err(|e| SolinaStorageError::StorageError(e.to_string()))
     }
 
     pub fn get_current_auth_credential(
@@ -108,34 +157,250 @@ impl<'a> ReadWriterTransaction<'a> {
 
         match credential {
             Some(output) => Ok(output),
-            None => Err(SolinaStorageError::StorageError(format!(
+            None => Err(SolinaStorageError::NotFound(format!(
                 "Could not find credential for address: {}",
                 address,
             ))),
         }
     }
 
-    // ----------------------------------------------- Write methods -----------------------------------------------
-    pub fn store_intents(
-        &mut self,
-        intents: &[(i64, intent::Intent)],
-    ) -> Result<(), SolinaStorageError> {
+    pub fn get_current_batch_id(&mut self) -> Result<i32, SolinaStorageError> {
         use crate::schema::current_batch_id;
-        use crate::schema::intents;
 
-        let current_batch_id = current_batch_id::table
+        current_batch_id::table
             .select(current_batch_id::id)
             .order(current_batch_id::id.desc())
             .first(self.connection())
             .optional()
-            .map_err(|e| SolinaStorageError::StorageError(e.to_string()))?
-            .unwrap_or(0 as i32);
+            .map_err(|e| SolinaStorageError::StorageError(e.to_string()))
+            .map(|id| id.unwrap_or(0_i32))
+    }
+
+    pub fn get_batch(&mut self, id: i32) -> Result<Batch, SolinaStorageError> {
+        use crate::schema::batches;
+
+        let result = batches::table
+            .filter(batches::id.eq(id))
+            .first(self.connection())
+            .optional()
+            .map_err(|e| SolinaStorageError::StorageError(e.to_string()))?;
+
+        match result {
+            Some(output) => Ok(output),
+            None => Err(SolinaStorageError::NotFound(format!(
+                "Could not find batch with id: {}",
+                id,
+            ))),
+        }
+    }
+
+    /// Returns the most recently sealed batch, if any batch has been sealed yet.
+    pub fn get_latest_sealed_batch(&mut self) -> Result<Option<Batch>, SolinaStorageError> {
+        use crate::schema::batches;
+
+        batches::table
+            .filter(batches::sealed_at.is_not_null())
+            .order(batches::id.desc())
+            .first(self.connection())
+            .optional()
+            .map_err(|e| SolinaStorageError::StorageError(e.to_string()))
+    }
+
+    /// Returns the batches currently in `state`, in increasing id order.
+    pub fn get_batches_in_state(
+        &mut self,
+        state: BatchState,
+    ) -> Result<Vec<Batch>, SolinaStorageError> {
+        use crate::schema::batches;
+
+        batches::table
+            .filter(batches::state.eq(state.as_str()))
+            .order(batches::id.asc())
+            .load::<Batch>(self.connection())
+            .map_err(|e| SolinaStorageError::StorageError(e.to_string()))
+    }
+
+    /// Returns all the solutions submitted for batch `batch_id`, in submission order.
+    pub fn get_solutions(&mut self, batch_id: i32) -> Result<Vec<Solution>, SolinaStorageError> {
+        use crate::schema::solutions;
+
+        solutions::table
+            .filter(solutions::batch_id.eq(batch_id))
+            .order(solutions::id.asc())
+            .load::<Solution>(self.connection())
+            .map_err(|e| SolinaStorageError::StorageError(e.to_string()))
+    }
+
+    pub fn is_registered_solver(&mut self, address: &str) -> Result<bool, SolinaStorageError> {
+        use crate::schema::solvers;
+
+        let count: i64 = solvers::table
+            .filter(solvers::address.eq(address))
+            .count()
+            .get_result(self.connection())
+            .map_err(|e| SolinaStorageError::StorageError(e.to_string()))?;
+
+        Ok(count > 0)
+    }
+
+    /// Returns those of the provided (hex encoded) nullifiers that have already been spent.
+    pub fn get_spent_nullifiers(
+        &mut self,
+        nullifiers: &[String],
+    ) -> Result<Vec<String>, SolinaStorageError> {
+        use crate::schema::nullifiers;
+
+        nullifiers::table
+            .select(nullifiers::nullifier)
+            .filter(nullifiers::nullifier.eq_any(nullifiers))
+            .load::<String>(self.connection())
+            .map_err(|e| SolinaStorageError::StorageError(e.to_string()))
+    }
+
+    pub fn get_tracked_intent(
+        &mut self,
+        intent_id: IntentId,
+    ) -> Result<Option<TrackedIntent>, SolinaStorageError> {
+        intent_statuses::table
+            .filter(intent_statuses::intent_id.eq(intent_id))
+            .first(self.connection())
+            .optional()
+            .map_err(|e| SolinaStorageError::StorageError(e.to_string()))
+    }
+
+    /// Returns the most recently submitted intent with the given (hex encoded) structured
+    /// hash, if any.
+    pub fn get_tracked_intent_by_hash(
+        &mut self,
+        structured_hash: &str,
+    ) -> Result<Option<TrackedIntent>, SolinaStorageError> {
+        intent_statuses::table
+            .filter(intent_statuses::structured_hash.eq(structured_hash))
+            .order(intent_statuses::intent_id.desc())
+            .first(self.connection())
+            .optional()
+            .map_err(|e| SolinaStorageError::StorageError(e.to_string()))
+    }
+
+    /// Returns a page of the intents signed by `signer`, optionally in `status` only, from
+    /// the most recent to the oldest.
+    pub fn get_signer_intents(
+        &mut self,
+        signer: &str,
+        status: Option<IntentStatus>,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<TrackedIntent>, SolinaStorageError> {
+        signer_intents(signer, status)
+            .order(intent_statuses::intent_id.desc())
+            .offset(offset)
+            .limit(limit)
+            .load::<TrackedIntent>(self.connection())
+            .map_err(|e| SolinaStorageError::StorageError(e.to_string()))
+    }
+
+    pub fn count_signer_intents(
+        &mut self,
+        signer: &str,
+        status: Option<IntentStatus>,
+    ) -> Result<i64, SolinaStorageError> {
+        signer_intents(signer, status)
+            .count()
+            .get_result(self.connection())
+            .map_err(|e| SolinaStorageError::StorageError(e.to_string()))
+    }
+
+    /// Returns the highest intent id assigned so far, if any intent has been accepted.
+    pub fn get_last_intent_id(&mut self) -> Result<Option<IntentId>, SolinaStorageError> {
+        use crate::schema::intents;
+
+        let last_tracked_id = intent_statuses::table
+            .select(max(intent_statuses::intent_id))
+            .first::<Option<IntentId>>(self.connection())
+            .map_err(|e| SolinaStorageError::StorageError(e.to_string()))?;
+        // intents stored before their statuses were tracked
+        let last_stored_id = intents::table
+            .select(max(intents::id))
+            .first::<Option<IntentId>>(self.connection())
+            .map_err(|e| SolinaStorageError::StorageError(e.to_string()))?;
+
+        Ok(last_tracked_id.max(last_stored_id))
+    }
+
+    /// Returns the intents pending in the mempool, in increasing id order.
+    pub fn get_pending_intents(&mut self) -> Result<Vec<PendingIntent>, SolinaStorageError> {
+        use crate::schema::pending_intents;
+
+        pending_intents::table
+            .order(pending_intents::id.asc())
+            .load::<PendingIntent>(self.connection())
+            .map_err(|e| SolinaStorageError::StorageError(e.to_string()))
+    }
+
+    /// Returns the pending intents among `ids`, in increasing id order.
+    pub fn get_pending_intents_batch(
+        &mut self,
+        ids: &[IntentId],
+    ) -> Result<Vec<PendingIntent>, SolinaStorageError> {
+        use crate::schema::pending_intents;
+
+        pending_intents::table
+            .filter(pending_intents::id.eq_any(ids))
+            .order(pending_intents::id.asc())
+            .load::<PendingIntent>(self.connection())
+            .map_err(|e| SolinaStorageError::StorageError(e.to_string()))
+    }
+
+    // ----------------------------------------------- Write methods -----------------------------------------------
+    /// Stores a batch of intents in the current batch, about to be sealed at `sealed_at`, in
+    /// canonical order. Intents expired at `sealed_at` are not part of the batch, and are
+    /// skipped. Returns the ids of the stored intents.
+    pub fn store_intents(
+        &mut self,
+        intents: &[(IntentId, intent::Intent)],
+        sealed_at: NaiveDateTime,
+    ) -> Result<Vec<IntentId>, SolinaStorageError> {
+        use crate::schema::intents;
+
+        let current_batch_id = self.get_current_batch_id()?;
         let intents = intents
             .iter()
-            .map(|(id, intent)| Intent::from_intent(intent, *id as i32, current_batch_id))
+            .filter(|(_, intent)| intent.expiry_date > sealed_at)
+            .enumerate()
+            .map(|(position, (id, intent))| {
+                Intent::from_intent(intent, *id, current_batch_id, position as i32)
+            })
             .collect::<Vec<_>>();
-        diesel::insert_into(intents::table)
-            .values(intents)
+        let ids = intents.iter().map(|intent| intent.id).collect();
+        if !intents.is_empty() {
+            diesel::insert_into(intents::table)
+                .values(intents)
+                .execute(self.connection())
+                .map_err(|e| SolinaStorageError::StorageError(e.to_string()))?;
+        }
+
+        Ok(ids)
+    }
+
+    pub fn insert_pending_intent(
+        &mut self,
+        pending_intent: PendingIntent,
+    ) -> Result<(), SolinaStorageError> {
+        use crate::schema::pending_intents;
+
+        diesel::insert_into(pending_intents::table)
+            .values(pending_intent)
+            .execute(self.connection())
+            .map_err(|e| SolinaStorageError::StorageError(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Removes intents from the pending ones, once sealed in a batch, or expired.
+    pub fn remove_pending_intents(&mut self, ids: &[IntentId]) -> Result<(), SolinaStorageError> {
+        use crate::schema::pending_intents;
+
+        diesel::delete(pending_intents::table.filter(pending_intents::id.eq_any(ids)))
             .execute(self.connection())
             .map_err(|e| SolinaStorageError::StorageError(e.to_string()))?;
 
@@ -195,4 +460,252 @@ impl<'a> ReadWriterTransaction<'a> {
 
         Ok(())
     }
+
+    pub fn insert_nullifiers(
+        &mut self,
+        nullifiers: &[String],
+        batch_id: i32,
+    ) -> Result<(), SolinaStorageError> {
+        use crate::schema::nullifiers;
+
+        let created_at = Utc::now().naive_utc();
+        let nullifiers = nullifiers
+            .iter()
+            .map(|nullifier| NewNullifier {
+                nullifier: nullifier.clone(),
+                batch_id,
+                created_at,
+            })
+            .collect::<Vec<_>>();
+        diesel::insert_into(nullifiers::table)
+            .values(nullifiers)
+            .execute(self.connection())
+            .map_err(|e| SolinaStorageError::StorageError(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Opens batch `id` for collecting intents, unless it has already been opened.
+    pub fn open_batch(
+        &mut self,
+        id: i32,
+        created_at: NaiveDateTime,
+    ) -> Result<(), SolinaStorageError> {
+        use crate::schema::batches;
+
+        diesel::insert_or_ignore_into(batches::table)
+            .values(NewBatch {
+                id,
+                state: BatchState::Collecting.as_str().to_string(),
+                created_at,
+                updated_at: created_at,
+            })
+            .execute(self.connection())
+            .map_err(|e| SolinaStorageError::StorageError(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Seals the current batch, with the given Merkle root and price snapshot, and opens the
+    /// next batch. Returns the id of the sealed batch.
+    pub fn seal_batch(
+        &mut self,
+        root: String,
+        price_snapshot: String,
+        sealed_at: NaiveDateTime,
+    ) -> Result<i32, SolinaStorageError> {
+        use crate::schema::{batches, current_batch_id};
+
+        let id = self.get_current_batch_id()?;
+        self.open_batch(id, sealed_at)?;
+        diesel::update(batches::table.filter(batches::id.eq(id)))
+            .set((
+                batches::state.eq(BatchState::Sealed.as_str()),
+                batches::root.eq(root),
+                batches::sealed_at.eq(sealed_at),
+                batches::price_snapshot.eq(price_snapshot),
+                batches::updated_at.eq(sealed_at),
+            ))
+            .execute(self.connection())
+            .map_err(|e| SolinaStorageError::StorageError(e.to_string()))?;
+        diesel::insert_into(current_batch_id::table)
+            .values(CurrentBatchId { id: id + 1 })
+            .execute(self.connection())
+            .map_err(|e| SolinaStorageError::StorageError(e.to_string()))?;
+        self.open_batch(id + 1, sealed_at)?;
+
+        Ok(id)
+    }
+
+    /// Opens the solving window of a sealed batch, until `solving_deadline`.
+    pub fn open_solving_window(
+        &mut self,
+        id: i32,
+        solving_deadline: NaiveDateTime,
+        updated_at: NaiveDateTime,
+    ) -> Result<(), SolinaStorageError> {
+        use crate::schema::batches;
+
+        diesel::update(batches::table.filter(batches::id.eq(id)))
+            .set((
+                batches::state.eq(BatchState::Solving.as_str()),
+                batches::solving_deadline.eq(solving_deadline),
+                batches::updated_at.eq(updated_at),
+            ))
+            .execute(self.connection())
+            .map_err(|e| SolinaStorageError::StorageError(e.to_string()))?;
+
+        Ok(())
+    }
+
+    pub fn update_batch_state(
+        &mut self,
+        id: i32,
+        state: BatchState,
+        updated_at: NaiveDateTime,
+    ) -> Result<(), SolinaStorageError> {
+        use crate::schema::batches;
+
+        diesel::update(batches::table.filter(batches::id.eq(id)))
+            .set((
+                batches::state.eq(state.as_str()),
+                batches::updated_at.eq(updated_at),
+            ))
+            .execute(self.connection())
+            .map_err(|e| SolinaStorageError::StorageError(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Records the solutions settling batch `id`, from best to worst. The first one is
+    /// recorded as the winning solution of the batch.
+    pub fn select_solutions(
+        &mut self,
+        id: i32,
+        solution_ids: &[i32],
+        updated_at: NaiveDateTime,
+    ) -> Result<(), SolinaStorageError> {
+        use crate::schema::{batches, solutions};
+
+        diesel::update(solutions::table.filter(solutions::id.eq_any(solution_ids)))
+            .set(solutions::is_selected.eq(true))
+            .execute(self.connection())
+            .map_err(|e| SolinaStorageError::StorageError(e.to_string()))?;
+        diesel::update(batches::table.filter(batches::id.eq(id)))
+            .set((
+                batches::state.eq(BatchState::SolutionSelected.as_str()),
+                batches::winning_solution_id.eq(solution_ids.first()),
+                batches::updated_at.eq(updated_at),
+            ))
+            .execute(self.connection())
+            .map_err(|e| SolinaStorageError::StorageError(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Starts tracking the status of a newly submitted intent, replacing any previous
+    /// intent with the same id.
+    pub fn track_intent(&mut self, intent: NewTrackedIntent) -> Result<(), SolinaStorageError> {
+        diesel::replace_into(intent_statuses::table)
+            .values(intent)
+            .execute(self.connection())
+            .map_err(|e| SolinaStorageError::StorageError(e.to_string()))?;
+
+        Ok(())
+    }
+
+    pub fn update_intent_statuses(
+        &mut self,
+        intent_ids: &[IntentId],
+        status: IntentStatus,
+        batch_id: Option<i32>,
+        updated_at: NaiveDateTime,
+    ) -> Result<(), SolinaStorageError> {
+        diesel::update(
+            intent_statuses::table.filter(intent_statuses::intent_id.eq_any(intent_ids)),
+        )
+        .set((
+            intent_statuses::status.eq(status.as_str()),
+            intent_statuses::batch_id.eq(batch_id),
+            intent_statuses::updated_at.eq(updated_at),
+        ))
+        .execute(self.connection())
+        .map_err(|e| SolinaStorageError::StorageError(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Marks the intent of batch `batch_id` with the given (hex encoded) structured hash and
+    /// signer as matched, with the amounts filled by the selected solution.
+    pub fn fill_intent(
+        &mut self,
+        batch_id: i32,
+        structured_hash: &str,
+        signer: &str,
+        quote_amount_swapped: String,
+        base_amount_received: String,
+        updated_at: NaiveDateTime,
+    ) -> Result<(), SolinaStorageError> {
+        diesel::update(
+            intent_statuses::table
+                .filter(intent_statuses::batch_id.eq(batch_id))
+                .filter(intent_statuses::structured_hash.eq(structured_hash))
+                .filter(intent_statuses::signer.eq(signer)),
+        )
+        .set((
+            intent_statuses::status.eq(IntentStatus::Matched.as_str()),
+            intent_statuses::quote_amount_swapped.eq(quote_amount_swapped),
+            intent_statuses::base_amount_received.eq(base_amount_received),
+            intent_statuses::updated_at.eq(updated_at),
+        ))
+        .execute(self.connection())
+        .map_err(|e| SolinaStorageError::StorageError(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Marks the matched intents of batch `batch_id` as settled.
+    pub fn settle_batch_intents(
+        &mut self,
+        batch_id: i32,
+        updated_at: NaiveDateTime,
+    ) -> Result<(), SolinaStorageError> {
+        diesel::update(
+            intent_statuses::table
+                .filter(intent_statuses::batch_id.eq(batch_id))
+                .filter(intent_statuses::status.eq(IntentStatus::Matched.as_str())),
+        )
+        .set((
+            intent_statuses::status.eq(IntentStatus::Settled.as_str()),
+            intent_statuses::updated_at.eq(updated_at),
+        ))
+        .execute(self.connection())
+        .map_err(|e| SolinaStorageError::StorageError(e.to_string()))?;
+
+        Ok(())
+    }
+
+    pub fn store_solution(&mut self, solution: NewSolution) -> Result<(), SolinaStorageError> {
+        use crate::schema::solutions;
+
+        diesel::insert_into(solutions::table)
+            .values(solution)
+            .execute(self.connection())
+            .map_err(|e| SolinaStorageError::StorageError(e.to_string()))?;
+
+        Ok(())
+    }
+}
+
+fn signer_intents(
+    signer: &str,
+    status: Option<IntentStatus>,
+) -> intent_statuses::BoxedQuery<'_, Sqlite> {
+    let query = intent_statuses::table
+        .filter(intent_statuses::signer.eq(signer))
+        .into_boxed();
+    match status {
+        Some(status) => query.filter(intent_statuses::status.eq(status.as_str())),
+        None => query,
+    }
 }
@@ -1,6 +1,6 @@
 table! {
     intents (id) {
-        id -> diesel::sql_types::Integer,
+        id -> diesel::sql_types::BigInt,
         structured_hash -> Text,
         public_key -> Text,
         signature -> Text,
@@ -12,6 +12,7 @@ table! {
         created_at -> Timestamp,
         batch_id -> diesel::sql_types::Integer,
         expiry_date -> Timestamp,
+        batch_position -> diesel::sql_types::Integer,
     }
 }
 
@@ -38,3 +39,63 @@ table! {
         id -> diesel::sql_types::Integer,
     }
 }
+
+table! {
+    nullifiers(id) {
+        id -> diesel::sql_types::Integer,
+        nullifier -> Text,
+        batch_id -> diesel::sql_types::Integer,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    batches(id) {
+        id -> diesel::sql_types::Integer,
+        state -> Text,
+        root -> Nullable<Text>,
+        created_at -> Timestamp,
+        sealed_at -> Nullable<Timestamp>,
+        solving_deadline -> Nullable<Timestamp>,
+        updated_at -> Timestamp,
+        winning_solution_id -> Nullable<diesel::sql_types::Integer>,
+        price_snapshot -> Nullable<Text>,
+    }
+}
+
+table! {
+    solutions(id) {
+        id -> diesel::sql_types::Integer,
+        batch_id -> diesel::sql_types::Integer,
+        solver_address -> Text,
+        solution -> Text,
+        proof -> Text,
+        score -> Text,
+        created_at -> Timestamp,
+        is_selected -> Bool,
+    }
+}
+
+table! {
+    intent_statuses(intent_id) {
+        intent_id -> diesel::sql_types::BigInt,
+        structured_hash -> Text,
+        signer -> Text,
+        base_token -> Text,
+        quote_token -> Text,
+        status -> Text,
+        batch_id -> Nullable<diesel::sql_types::Integer>,
+        quote_amount_swapped -> Nullable<Text>,
+        base_amount_received -> Nullable<Text>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    pending_intents(id) {
+        id -> diesel::sql_types::BigInt,
+        intent -> Text,
+        created_at -> Timestamp,
+    }
+}